@@ -0,0 +1,656 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use super::RootConfig;
+
+/// File name looked up in the working directory for project-local settings
+pub const PROJECT_CONFIG_FILE: &str = "cpuv.toml";
+
+/// Where the effective value of a config key came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigSource {
+    Default,
+    User(PathBuf),
+    Project(PathBuf),
+    Cli(PathBuf),
+    /// A command line flag, like `--write-out`
+    Flag(&'static str),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::User(path) => write!(f, "user config {}", path.display()),
+            ConfigSource::Project(path) => write!(f, "project config {}", path.display()),
+            ConfigSource::Cli(path) => write!(f, "--config-path {}", path.display()),
+            ConfigSource::Flag(flag) => write!(f, "{}", flag),
+        }
+    }
+}
+
+/// Values set on the command line, applied on top of every config file
+#[derive(Debug, Default, Clone)]
+pub struct ConfigOverrides {
+    pub config_path: Option<String>,
+    pub program_path: Option<String>,
+    pub write_out: Option<bool>,
+}
+
+/// The effective config along with the layer each key was taken from
+#[derive(Debug)]
+pub struct LoadedConfig {
+    pub config: RootConfig,
+    merged: Value,
+    /// Dotted key path (`engine.tps`) to the layer that set it
+    sources: BTreeMap<String, ConfigSource>,
+    /// Unknown keys found in the config files, these are ignored
+    pub warnings: Vec<String>,
+}
+
+/// `$XDG_CONFIG_HOME/cpuv/config.toml`, falling back to `~/.config/cpuv/config.toml`
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("cpuv").join("config.toml"))
+}
+
+/// Every file that can make up the config, lowest precedence first, whether it exists or not
+pub fn config_files(overrides: &ConfigOverrides) -> Vec<(PathBuf, ConfigSource)> {
+    let mut files = Vec::new();
+    if let Some(path) = user_config_path() {
+        files.push((path.clone(), ConfigSource::User(path)));
+    }
+
+    let project = PathBuf::from(PROJECT_CONFIG_FILE);
+    files.push((project.clone(), ConfigSource::Project(project)));
+
+    if let Some(path) = &overrides.config_path {
+        let path = PathBuf::from(path);
+        files.push((path.clone(), ConfigSource::Cli(path)));
+    }
+
+    files
+}
+
+/// The file settings changed at runtime are saved to: `--config-path` if given, else
+/// `./cpuv.toml` if it exists, else the user config
+pub fn writable_config_path(overrides: &ConfigOverrides) -> PathBuf {
+    let project = PathBuf::from(PROJECT_CONFIG_FILE);
+    match &overrides.config_path {
+        Some(path) => PathBuf::from(path),
+        None if project.is_file() => project,
+        None => user_config_path().unwrap_or(project),
+    }
+}
+
+/// Validates the config and writes the keys that differ from `saved`, the config the file was
+/// loaded with, to `path`. Everything else in the file is kept as is, comments included, so values
+/// coming from other layers are not copied into it. Missing parent directories are created.
+pub fn save_config(path: &Path, config: &RootConfig, saved: &RootConfig) -> Result<(), String> {
+    config.validate().map_err(|errors| errors.join(", "))?;
+    let config = Value::try_from(config).map_err(|e| e.to_string())?;
+    let saved = Value::try_from(saved).map_err(|e| e.to_string())?;
+
+    let mut changes = Vec::new();
+    changed_keys(&saved, &config, &mut Vec::new(), &mut changes);
+
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+    };
+    let mut document = raw
+        .parse::<DocumentMut>()
+        .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+
+    for (keys, value) in changes {
+        set_key(&mut document, &keys, &value)
+            .map_err(|e| format!("Could not set {}: {}", keys.join("."), e))?;
+    }
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Could not create {}: {}", parent.display(), e))?;
+    }
+
+    fs::write(path, document.to_string())
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+/// Dotted paths and new values of the keys whose value differs between `old` and `new`
+fn changed_keys(
+    old: &Value,
+    new: &Value,
+    path: &mut Vec<String>,
+    changes: &mut Vec<(Vec<String>, Value)>,
+) {
+    match (old, new) {
+        (Value::Table(old), Value::Table(new)) => {
+            for (key, value) in new {
+                path.push(key.clone());
+                match old.get(key) {
+                    Some(old) => changed_keys(old, value, path, changes),
+                    None => changes.push((path.clone(), value.clone())),
+                }
+                path.pop();
+            }
+        }
+        (old, new) if old != new => changes.push((path.clone(), new.clone())),
+        _ => {}
+    }
+}
+
+/// Sets a single key of a TOML document, creating the tables on its path. An existing value keeps
+/// its comments.
+fn set_key(document: &mut DocumentMut, keys: &[String], value: &Value) -> Result<(), String> {
+    let Some((key, tables)) = keys.split_last() else {
+        return Ok(());
+    };
+
+    let mut table = document.as_table_mut() as &mut dyn TableLike;
+    for name in tables {
+        table = table
+            .entry(name)
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .ok_or_else(|| format!("{} is not a table", name))?;
+    }
+
+    let mut value = value
+        .to_string()
+        .parse::<toml_edit::Value>()
+        .map_err(|e| e.to_string())?;
+    match table.get_mut(key).and_then(Item::as_value_mut) {
+        Some(existing) => {
+            *value.decor_mut() = existing.decor().clone();
+            *existing = value;
+        }
+        None => {
+            table.insert(key, Item::Value(value));
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads and merges the config layers, later layers overriding single fields of earlier ones:
+/// defaults, then the user config, then `./cpuv.toml`, then `--config-path`, then the other flags.
+///
+/// Missing user and project configs are skipped, a missing `--config-path` is an error.
+pub fn load_config(overrides: &ConfigOverrides) -> Result<LoadedConfig, String> {
+    let defaults = Value::try_from(RootConfig::default()).map_err(|e| e.to_string())?;
+    let mut loaded = LoadedConfig {
+        config: RootConfig::default(),
+        merged: Value::Table(Table::new()),
+        sources: BTreeMap::new(),
+        warnings: Vec::new(),
+    };
+    let mut known_keys = Vec::new();
+    collect_keys(&defaults, "", &mut known_keys);
+    loaded.merge(defaults, &ConfigSource::Default);
+
+    for (path, source) in config_files(overrides) {
+        if !path.is_file() {
+            if let ConfigSource::Cli(_) = source {
+                return Err(format!("Config file not found at {}", path.display()));
+            }
+            continue;
+        }
+
+        let (layer, unknown) = read_layer(&path)?;
+        loaded.warnings.extend(
+            unknown
+                .iter()
+                .map(|key| unknown_key_warning(&path, key, &known_keys)),
+        );
+        loaded.merge(layer, &source);
+    }
+
+    if let Some(program_path) = &overrides.program_path {
+        loaded.merge(
+            layer(&["program", "program-path"], program_path.clone().into()),
+            &ConfigSource::Flag("--file"),
+        );
+    }
+
+    if let Some(write_out) = overrides.write_out {
+        loaded.merge(
+            layer(
+                &["program", "output-parsing-results", "should-write"],
+                write_out.into(),
+            ),
+            &ConfigSource::Flag("--write-out"),
+        );
+    }
+
+    loaded.config = loaded
+        .merged
+        .clone()
+        .try_into()
+        .map_err(|e: toml::de::Error| e.to_string())?;
+
+    loaded.config.validate().map_err(|errors| {
+        let errors = errors
+            .iter()
+            .map(|e| format!("- {}", e))
+            .collect::<Vec<String>>();
+        format!("Invalid config:\n{}", errors.join("\n"))
+    })?;
+
+    Ok(loaded)
+}
+
+/// Reads a config file, checking it against [`RootConfig`] on its own so errors point at the file.
+/// Returns the raw layer and the dotted paths of the keys [`RootConfig`] does not know about.
+fn read_layer(path: &Path) -> Result<(Value, Vec<String>), String> {
+    let raw = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read config {}: {}", path.display(), e))?;
+
+    let (_, unknown) =
+        check_config(&raw).map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+
+    let layer = toml::from_str::<Table>(&raw)
+        .map(Value::Table)
+        .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+
+    Ok((layer, unknown))
+}
+
+/// Deserializes a config on its own, collecting every key that was ignored on the way
+pub fn check_config(raw: &str) -> Result<(RootConfig, Vec<String>), toml::de::Error> {
+    let mut unknown = Vec::new();
+    let config = serde_ignored::deserialize(toml::Deserializer::new(raw), |path| {
+        unknown.push(path.to_string())
+    })?;
+
+    Ok((config, unknown))
+}
+
+fn unknown_key_warning(path: &Path, key: &str, known_keys: &[String]) -> String {
+    let mut warning = format!("Unknown config key `{}` in {}", key, path.display());
+
+    let closest = known_keys
+        .iter()
+        .map(|known| (edit_distance(&key.replace('_', "-"), known), known))
+        .min();
+    if let Some((distance, known)) = closest {
+        if distance <= 3 {
+            warning.push_str(&format!(", did you mean `{}`?", known));
+        }
+    }
+
+    warning
+}
+
+/// Levenshtein distance between two keys
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Every dotted key path in the value, tables included
+fn collect_keys(value: &Value, prefix: &str, keys: &mut Vec<String>) {
+    if let Value::Table(table) = value {
+        for (key, value) in table {
+            let path = join(prefix, key);
+            collect_keys(value, &path, keys);
+            keys.push(path);
+        }
+    }
+}
+
+/// A single value nested under `path`
+fn layer(path: &[&str], value: Value) -> Value {
+    path.iter().rev().fold(value, |value, key| {
+        let mut table = Table::new();
+        table.insert(key.to_string(), value);
+        Value::Table(table)
+    })
+}
+
+impl LoadedConfig {
+    fn merge(&mut self, layer: Value, source: &ConfigSource) {
+        merge_value(&mut self.merged, layer, source, "", &mut self.sources);
+    }
+
+    pub fn source(&self, key: &str) -> Option<&ConfigSource> {
+        self.sources.get(key)
+    }
+
+    /// The effective config as TOML, each value annotated with the layer it came from
+    pub fn show(&self) -> String {
+        let mut out = String::new();
+        if let Value::Table(table) = &self.merged {
+            self.show_table(table, "", &mut out);
+        }
+
+        if !self.warnings.is_empty() {
+            out.push('\n');
+        }
+        for warning in &self.warnings {
+            out.push_str(&format!("# warning: {}\n", warning));
+        }
+
+        out.trim_start().to_string()
+    }
+
+    fn show_table(&self, table: &Table, prefix: &str, out: &mut String) {
+        if !prefix.is_empty() && table.values().any(|value| !value.is_table()) {
+            out.push_str(&format!("\n[{}]\n", prefix));
+        }
+
+        for (key, value) in table.iter().filter(|(_, value)| !value.is_table()) {
+            let path = join(prefix, key);
+            let source = self.source(&path).unwrap_or(&ConfigSource::Default);
+            out.push_str(&format!("{} = {} # {}\n", key, value, source));
+        }
+
+        for (key, value) in table {
+            if let Value::Table(child) = value {
+                self.show_table(child, &join(prefix, key), out);
+            }
+        }
+    }
+}
+
+fn merge_value(
+    base: &mut Value,
+    layer: Value,
+    source: &ConfigSource,
+    path: &str,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match (base, layer) {
+        (Value::Table(base), Value::Table(layer)) => {
+            for (key, value) in layer {
+                let child_path = join(path, &key);
+                match base.get_mut(&key) {
+                    Some(existing) if existing.is_table() && value.is_table() => {
+                        merge_value(existing, value, source, &child_path, sources);
+                    }
+                    _ => {
+                        record_sources(&value, source, &child_path, sources);
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => {
+            record_sources(&layer, source, path, sources);
+            *base = layer;
+        }
+    }
+}
+
+fn record_sources(
+    value: &Value,
+    source: &ConfigSource,
+    path: &str,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                record_sources(value, source, &join(path, key), sources);
+            }
+        }
+        _ => {
+            sources.insert(path.to_string(), source.clone());
+        }
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Rate, Timing};
+
+    const DEFAULT_CONFIG: &str = include_str!("../../../examples/default-config.toml");
+
+    #[test]
+    fn default_config_has_no_unknown_keys() {
+        let (_, unknown) = check_config(DEFAULT_CONFIG).expect("default config should parse");
+        assert!(unknown.is_empty(), "unknown keys: {:?}", unknown);
+    }
+
+    #[test]
+    fn default_config_values_are_applied() {
+        let (config, _) = check_config(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config.program.program_path, "main.cpu");
+        assert!(config.program.output_logs.should_write);
+    }
+
+    #[test]
+    fn rates_accept_integers_and_fractions() {
+        let (config, _) =
+            check_config("[engine]\ntps = 10\nipt = \"1/4\"\n").expect("rates should parse");
+        assert_eq!(config.engine.tps, Rate::new(10, 1));
+        assert_eq!(config.engine.ipt, Rate::new(1, 4));
+
+        assert!(check_config("[engine]\ntps = \"fast\"\n").is_err());
+        assert!(check_config("[engine]\ntps = -1\n").is_err());
+    }
+
+    #[test]
+    fn zero_rates_are_rejected() {
+        let (config, _) = check_config("[engine]\ntps = 0\nipt = \"1/0\"\n").unwrap();
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("engine.tps"));
+        assert!(errors[1].starts_with("engine.ipt"));
+    }
+
+    #[test]
+    fn cycle_costs_keep_unset_defaults() {
+        let (config, unknown) = check_config(
+            "[engine]\ntiming = \"cycles\"\ncycle-costs.instructions = { div = 40 }\n",
+        )
+        .unwrap();
+        assert!(unknown.is_empty(), "unknown keys: {:?}", unknown);
+        assert_eq!(config.engine.timing, Timing::Cycles);
+        assert_eq!(config.engine.cycle_costs.instructions.div, 40);
+        assert_eq!(config.engine.cycle_costs.instructions.mul, 3);
+
+        let (config, _) = check_config("[engine.cycle-costs.instructions]\nnop = 0\n").unwrap();
+        let errors = config.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            ["engine.cycle-costs.instructions.nop must be at least 1 cycle"]
+        );
+    }
+
+    #[test]
+    fn palette_colors_are_validated() {
+        let (config, unknown) = check_config(
+            "[ui]\nfont-color = \"#000000\"\npalette = { background = \"#ffffff\", keywords = \"red\" }\n",
+        )
+        .unwrap();
+        assert!(unknown.is_empty());
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("ui.palette.keywords"));
+    }
+
+    #[test]
+    fn unknown_keys_suggest_the_documented_key() {
+        let (_, unknown) = check_config("[program]\nprogram_path = \"a.cpu\"\n").unwrap();
+        assert_eq!(unknown, vec!["program.program_path"]);
+
+        let defaults = Value::try_from(RootConfig::default()).unwrap();
+        let mut known_keys = Vec::new();
+        collect_keys(&defaults, "", &mut known_keys);
+
+        let warning = unknown_key_warning(Path::new("cpuv.toml"), &unknown[0], &known_keys);
+        assert!(warning.ends_with("did you mean `program.program-path`?"));
+    }
+
+    #[test]
+    fn saving_only_writes_changed_keys() {
+        let dir = env::temp_dir().join(format!("cpuv-save-config-{}", std::process::id()));
+        let path = dir.join("cpuv.toml");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            &path,
+            "# project settings\n[engine]\n# fast enough\ntps = 10 # ticks\nlog-level = \"warn\"\n",
+        )
+        .unwrap();
+
+        let (saved, _) = check_config(&fs::read_to_string(&path).unwrap()).unwrap();
+        let mut config = saved.clone();
+        config.engine.tps = Rate::new(20, 1);
+        config.engine.heap_access_simulation.jitter = 3;
+
+        save_config(&path, &config, &saved).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            written,
+            "# project settings\n[engine]\n# fast enough\ntps = 20 # ticks\nlog-level = \"warn\"\n\n\
+             [engine.heap-access-simulation]\njitter = 3\n"
+        );
+    }
+
+    fn merged(layers: &[(&str, ConfigSource)]) -> LoadedConfig {
+        let mut loaded = LoadedConfig {
+            config: RootConfig::default(),
+            merged: Value::Table(Table::new()),
+            sources: BTreeMap::new(),
+            warnings: Vec::new(),
+        };
+        for (raw, source) in layers {
+            loaded.merge(Value::Table(toml::from_str(raw).unwrap()), source);
+        }
+        loaded
+    }
+
+    #[test]
+    fn nested_tables_merge_key_by_key() {
+        let user = ConfigSource::User(PathBuf::from("config.toml"));
+        let loaded = merged(&[
+            (
+                "[engine]\ntps = 10\nlog-level = \"warn\"\n[engine.heap]\nlatency = 1\njitter = 2\n",
+                ConfigSource::Default,
+            ),
+            ("[engine]\ntps = 20\n[engine.heap]\njitter = 3\n", user.clone()),
+        ]);
+
+        let expected = toml::from_str::<Table>(
+            "[engine]\ntps = 20\nlog-level = \"warn\"\n[engine.heap]\nlatency = 1\njitter = 3\n",
+        )
+        .unwrap();
+        assert_eq!(loaded.merged, Value::Table(expected));
+        assert_eq!(loaded.source("engine.tps"), Some(&user));
+        assert_eq!(
+            loaded.source("engine.log-level"),
+            Some(&ConfigSource::Default)
+        );
+        assert_eq!(
+            loaded.source("engine.heap.latency"),
+            Some(&ConfigSource::Default)
+        );
+        assert_eq!(loaded.source("engine.heap.jitter"), Some(&user));
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let project = ConfigSource::Project(PathBuf::from("cpuv.toml"));
+        let loaded = merged(&[
+            ("[engine]\ntps = 10\n", ConfigSource::Default),
+            (
+                "[engine]\ntps = 20\n",
+                ConfigSource::User(PathBuf::from("config.toml")),
+            ),
+            ("[engine]\ntps = 30\n", project.clone()),
+            ("[ui]\nscale = 2\n", ConfigSource::Flag("--scale")),
+        ]);
+
+        assert_eq!(loaded.merged["engine"]["tps"], Value::Integer(30));
+        assert_eq!(loaded.source("engine.tps"), Some(&project));
+        assert_eq!(
+            loaded.source("ui.scale"),
+            Some(&ConfigSource::Flag("--scale"))
+        );
+    }
+
+    #[test]
+    fn arrays_are_replaced() {
+        let project = ConfigSource::Project(PathBuf::from("cpuv.toml"));
+        let loaded = merged(&[
+            (
+                "[ui]\nfonts = [\"a\", \"b\", \"c\"]\n",
+                ConfigSource::Default,
+            ),
+            ("[ui]\nfonts = [\"d\"]\n", project.clone()),
+        ]);
+
+        assert_eq!(
+            loaded.merged["ui"]["fonts"],
+            Value::Array(vec![Value::String("d".to_string())])
+        );
+        assert_eq!(loaded.source("ui.fonts"), Some(&project));
+    }
+
+    #[test]
+    fn config_path_and_flags_are_applied_last() {
+        let dir = env::temp_dir().join(format!("cpuv-load-config-{}", std::process::id()));
+        let path = dir.join("custom.toml");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            &path,
+            "[engine]\ntps = 20\n[program]\nprogram-path = \"a.cpu\"\n",
+        )
+        .unwrap();
+
+        let loaded = load_config(&ConfigOverrides {
+            config_path: Some(path.display().to_string()),
+            program_path: Some("b.cpu".to_string()),
+            write_out: None,
+        });
+        let missing = load_config(&ConfigOverrides {
+            config_path: Some(dir.join("missing.toml").display().to_string()),
+            ..Default::default()
+        });
+        fs::remove_dir_all(&dir).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.config.engine.tps, Rate::new(20, 1));
+        assert_eq!(loaded.config.program.program_path, "b.cpu");
+        assert_eq!(loaded.source("engine.tps"), Some(&ConfigSource::Cli(path)));
+        assert_eq!(
+            loaded.source("program.program-path"),
+            Some(&ConfigSource::Flag("--file"))
+        );
+        assert!(missing.unwrap_err().starts_with("Config file not found"));
+    }
+}
@@ -0,0 +1,449 @@
+mod loader;
+mod rate;
+mod watcher;
+
+use serde::{Deserialize, Serialize};
+
+pub use loader::*;
+pub use rate::*;
+pub use watcher::*;
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RootConfig {
+    pub program: ProgramConfig,
+    pub engine: EngineConfig,
+    pub ui: UIConfig,
+}
+
+impl RootConfig {
+    /// Checks the values serde can't, returning one message per invalid option
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let engine = &self.engine;
+
+        if !engine.tps.is_valid() {
+            errors.push(format!(
+                "engine.tps must be greater than 0, got {}. \
+                 Use a whole number of ticks per second (tps = 5) or a fraction for \
+                 seconds per tick (tps = \"1/2\" ticks once every 2 seconds)",
+                engine.tps
+            ));
+        }
+
+        if !engine.ipt.is_valid() {
+            errors.push(format!(
+                "engine.ipt must be greater than 0, got {}. \
+                 Use a whole number of instructions per tick (ipt = 2) or a fraction for \
+                 ticks per instruction (ipt = \"1/3\" runs an instruction every 3 ticks)",
+                engine.ipt
+            ));
+        }
+
+        for (key, cycles) in engine.cycle_costs.instructions.costs() {
+            if cycles == 0 {
+                errors.push(format!(
+                    "engine.cycle-costs.instructions.{} must be at least 1 cycle",
+                    key
+                ));
+            }
+        }
+
+        if engine.heap_memory_size == 0 {
+            errors.push("engine.heap-memory-size must be at least 1 byte".to_string());
+        }
+
+        if self.ui.font_size == 0 {
+            errors.push("ui.font-size must be greater than 0".to_string());
+        }
+
+        if self.ui.font_color != "default" && parse_color(&self.ui.font_color).is_none() {
+            errors.push(format!(
+                "ui.font-color = {:?} is not a color, use \"default\" or a hex color like \"#000000\"",
+                self.ui.font_color
+            ));
+        }
+
+        for (key, color) in self.ui.palette.colors() {
+            if let Some(color) = color.as_ref().filter(|c| parse_color(c).is_none()) {
+                errors.push(format!(
+                    "ui.palette.{} = {:?} is not a color, use a hex color like \"#1d2021\"",
+                    key, color
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ProgramConfig {
+    pub program_path: String,
+    pub output_parsing_results: ParsingResultsConfig,
+    pub output_logs: LogsConfig,
+    pub output_trace: TraceConfig,
+    pub stdout: Vec<StdoutOption>,
+    /// File the program output is written to when the `file` stdout option is set
+    pub stdout_path: String,
+}
+
+impl Default for ProgramConfig {
+    fn default() -> Self {
+        Self {
+            program_path: "./main.cpu".to_string(),
+            output_parsing_results: Default::default(),
+            output_logs: Default::default(),
+            output_trace: Default::default(),
+            stdout: vec![StdoutOption::Virtual],
+            stdout_path: "out/stdout.txt".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ParsingResultsConfig {
+    pub should_write: bool,
+    pub path: String,
+    pub format: ParsingResultsFormat,
+}
+
+impl Default for ParsingResultsConfig {
+    fn default() -> Self {
+        Self {
+            should_write: false,
+            path: "out/parsed.ptree".to_string(),
+            format: Default::default(),
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum ParsingResultsFormat {
+    Json,
+    Ron,
+    #[default]
+    Tree,
+}
+
+/// Where the execution trace of a program is written, one entry per executed instruction
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TraceConfig {
+    pub should_write: bool,
+    pub path: String,
+    pub format: TraceFormat,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            should_write: false,
+            path: "out/trace.jsonl".to_string(),
+            format: Default::default(),
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceFormat {
+    /// One JSON object per line
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LogsConfig {
+    pub should_write: bool,
+    pub path: String,
+    /// Size in bytes after which the log file is rotated, 0 disables rotation
+    pub max_size: u64,
+    /// Number of rotated log files to keep
+    pub max_files: usize,
+    pub mirror_stderr: bool,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        Self {
+            should_write: false,
+            path: "logs.txt".to_string(),
+            max_size: 1024 * 1024,
+            max_files: 3,
+            mirror_stderr: false,
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StdoutOption {
+    #[serde(alias = "All")]
+    All,
+    #[default]
+    Virtual,
+    Console,
+    File,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct EngineConfig {
+    /// Ticks per second
+    pub tps: Rate,
+    /// Instructions per tick, or cycles per tick with cycle timing
+    pub ipt: Rate,
+    pub timing: Timing,
+    /// Latencies the cycle count and cycle timing are based on
+    pub cycle_costs: CycleCostConfig,
+    /// Run ticks back to back instead of `tps` times a second
+    pub unthrottled: bool,
+    pub heap_memory_size: usize,
+    pub heap_access_simulation: HeapAccessSimulationConfig,
+    pub log_level: LogLevel,
+    /// Executed instructions that can be stepped back through
+    pub history_size: usize,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            tps: Rate::from(5),
+            ipt: Rate::from(1),
+            timing: Default::default(),
+            cycle_costs: Default::default(),
+            unthrottled: false,
+            heap_memory_size: 2048,
+            heap_access_simulation: Default::default(),
+            log_level: Default::default(),
+            history_size: 10_000,
+        }
+    }
+}
+
+/// What `ipt` counts
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Timing {
+    /// Every instruction takes the same time, `ipt` instructions run on each tick
+    #[default]
+    Instructions,
+    /// `ipt` cycles are spent on each tick, slower instructions take more ticks to run
+    Cycles,
+}
+
+/// Cycles an instruction takes: the cost of the instruction plus the cost of each of its operands
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CycleCostConfig {
+    pub instructions: InstructionCosts,
+    pub operands: OperandCosts,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct InstructionCosts {
+    pub call: u32,
+    pub load: u32,
+    pub r#move: u32,
+    pub inc: u32,
+    pub dec: u32,
+    pub add: u32,
+    pub sub: u32,
+    pub mul: u32,
+    pub div: u32,
+    pub cmp: u32,
+    pub jeq: u32,
+    pub jlt: u32,
+    pub jgt: u32,
+    pub jmp: u32,
+    pub ret: u32,
+    pub nop: u32,
+    pub brk: u32,
+    pub exit: u32,
+}
+
+impl Default for InstructionCosts {
+    fn default() -> Self {
+        Self {
+            call: 2,
+            load: 1,
+            r#move: 1,
+            inc: 1,
+            dec: 1,
+            add: 1,
+            sub: 1,
+            mul: 3,
+            div: 20,
+            cmp: 1,
+            jeq: 1,
+            jlt: 1,
+            jgt: 1,
+            jmp: 1,
+            ret: 2,
+            nop: 1,
+            brk: 1,
+            exit: 1,
+        }
+    }
+}
+
+impl InstructionCosts {
+    /// Every cost with its config key, for validation
+    pub fn costs(&self) -> [(&'static str, u32); 18] {
+        [
+            ("call", self.call),
+            ("load", self.load),
+            ("move", self.r#move),
+            ("inc", self.inc),
+            ("dec", self.dec),
+            ("add", self.add),
+            ("sub", self.sub),
+            ("mul", self.mul),
+            ("div", self.div),
+            ("cmp", self.cmp),
+            ("jeq", self.jeq),
+            ("jlt", self.jlt),
+            ("jgt", self.jgt),
+            ("jmp", self.jmp),
+            ("ret", self.ret),
+            ("nop", self.nop),
+            ("brk", self.brk),
+            ("exit", self.exit),
+        ]
+    }
+}
+
+/// Cycles added for each operand of a kind, labels and functions are free
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct OperandCosts {
+    pub register: u32,
+    pub immediate: u32,
+    /// Variables, which are read from or written to the heap
+    pub memory: u32,
+}
+
+impl Default for OperandCosts {
+    fn default() -> Self {
+        Self {
+            register: 0,
+            immediate: 0,
+            memory: 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HeapAccessSimulationConfig {
+    pub enabled: bool,
+    /// Ticks an instruction reading or writing the heap waits before it runs
+    pub minimum_delay: u32,
+    /// Up to this many extra ticks are added at random to each wait, 0 disables the jitter
+    pub jitter: u32,
+    /// Seed of the jitter, a program waits the same ticks on every run with the same seed
+    pub seed: u64,
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct UIConfig {
+    pub theme: UITheme,
+    /// `"default"` to use the color of the theme, or a hex color like `"#000000"`
+    pub font_color: String,
+    pub font_size: u32,
+    pub palette: PaletteConfig,
+}
+
+impl Default for UIConfig {
+    fn default() -> Self {
+        Self {
+            theme: UITheme::dark,
+            font_color: "default".to_string(),
+            font_size: 16,
+            palette: Default::default(),
+        }
+    }
+}
+
+/// Hex colors (`"#rrggbb"`) replacing single colors of the theme, unset ones are kept
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PaletteConfig {
+    /// Panels and windows
+    pub background: Option<String>,
+    /// Text fields, the code editor and table stripes
+    pub surface: Option<String>,
+    /// Selections, hovered widgets and links
+    pub accent: Option<String>,
+
+    // code editor highlighting
+    pub comments: Option<String>,
+    pub functions: Option<String>,
+    pub keywords: Option<String>,
+    pub literals: Option<String>,
+    pub numerics: Option<String>,
+    pub punctuation: Option<String>,
+    pub strings: Option<String>,
+    pub types: Option<String>,
+    pub special: Option<String>,
+}
+
+impl PaletteConfig {
+    /// Every color with its config key, for validation and applying
+    pub fn colors(&self) -> [(&'static str, &Option<String>); 12] {
+        [
+            ("background", &self.background),
+            ("surface", &self.surface),
+            ("accent", &self.accent),
+            ("comments", &self.comments),
+            ("functions", &self.functions),
+            ("keywords", &self.keywords),
+            ("literals", &self.literals),
+            ("numerics", &self.numerics),
+            ("punctuation", &self.punctuation),
+            ("strings", &self.strings),
+            ("types", &self.types),
+            ("special", &self.special),
+        ]
+    }
+}
+
+/// Parses a `"#rrggbb"` color
+pub fn parse_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[allow(warnings)]
+#[derive(Default, Deserialize, Serialize, Debug, Clone)]
+pub enum UITheme {
+    light,
+    #[default]
+    dark,
+}
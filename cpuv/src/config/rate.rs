@@ -0,0 +1,168 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A rate of `numerator` events per `denominator` units, written as an integer (`5`) or as a
+/// fraction (`"1/5"`, one event every 5 units).
+///
+/// Zero is representable so that validation can point at the offending key, see
+/// [`RootConfig::validate`](super::RootConfig::validate).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl Rate {
+    pub const fn new(numerator: u64, denominator: u64) -> Self {
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.numerator > 0 && self.denominator > 0
+    }
+
+    /// Events per unit as a float, for displaying and editing
+    pub fn per_unit(&self) -> f64 {
+        self.numerator as f64 / self.denominator.max(1) as f64
+    }
+
+    /// The closest rate to `value` events per unit that is either a whole number (`5`) or one
+    /// event every whole number of units (`"1/5"`). Zero, negative and non-finite values are an
+    /// error.
+    pub fn from_per_unit(value: f64) -> Result<Self, String> {
+        if !value.is_finite() || value <= 0.0 {
+            return Err(format!("rate must be a positive number, got {}", value));
+        }
+
+        if value >= 1.0 {
+            Ok(Self::from(value.round() as u64))
+        } else {
+            Ok(Self::new(1, (1.0 / value).round() as u64))
+        }
+    }
+
+    /// Time between two events when one unit is a second, saturating at `u64::MAX` nanoseconds
+    pub fn interval(&self) -> Duration {
+        let nanos = 1_000_000_000u128 * self.denominator as u128 / self.numerator.max(1) as u128;
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+}
+
+impl From<u64> for Rate {
+    fn from(value: u64) -> Self {
+        Self::new(value, 1)
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |part: &str| {
+            part.trim().parse::<u64>().map_err(|_| {
+                format!(
+                    "invalid rate {:?}, expected a whole number like \"5\" or a fraction like \"1/5\"",
+                    s
+                )
+            })
+        };
+
+        match s.split_once('/') {
+            Some((numerator, denominator)) => Ok(Self::new(parse(numerator)?, parse(denominator)?)),
+            None => Ok(Self::new(parse(s)?, 1)),
+        }
+    }
+}
+
+impl Serialize for Rate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.denominator == 1 {
+            serializer.serialize_u64(self.numerator)
+        } else {
+            serializer.serialize_str(&self.to_string())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Rate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RateVisitor;
+
+        impl Visitor<'_> for RateVisitor {
+            type Value = Rate;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a whole number like 5 or a fraction like \"1/5\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Rate, E> {
+                Ok(Rate::from(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Rate, E> {
+                u64::try_from(value)
+                    .map(Rate::from)
+                    .map_err(|_| E::custom(format!("rate must not be negative, got {}", value)))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Rate, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(RateVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_unit_values_round_to_the_closest_rate() {
+        assert_eq!(Rate::from_per_unit(5.4), Ok(Rate::new(5, 1)));
+        assert_eq!(Rate::from_per_unit(1.0), Ok(Rate::new(1, 1)));
+        assert_eq!(Rate::from_per_unit(0.2), Ok(Rate::new(1, 5)));
+        assert_eq!(Rate::from_per_unit(0.3), Ok(Rate::new(1, 3)));
+    }
+
+    #[test]
+    fn intervals_saturate() {
+        assert_eq!(Rate::new(4, 1).interval(), Duration::from_millis(250));
+        assert_eq!(Rate::new(1, 5).interval(), Duration::from_secs(5));
+        assert_eq!(Rate::new(0, 1).interval(), Duration::from_secs(1));
+        assert_eq!(
+            Rate::new(1, u64::MAX).interval(),
+            Duration::from_nanos(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn per_unit_values_must_be_positive_and_finite() {
+        for value in [0.0, -0.0, -1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let error = Rate::from_per_unit(value).unwrap_err();
+            assert!(
+                error.starts_with("rate must be a positive number"),
+                "{}",
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use super::{config_files, load_config, ConfigOverrides, RootConfig};
+use crate::core::engine::{ClientCommandType, ClientCommands};
+
+/// How often the config files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Result of reloading the config after one of its files changed
+pub type ConfigReload = Result<RootConfig, String>;
+
+/// Watches every file that makes up the config and reloads it when one of them is created,
+/// changed or removed.
+///
+/// A valid config is sent to the engine as [`ClientCommandType::UpdateConfig`] and to the UI,
+/// which applies its own settings on the next frame. Errors only go to the UI, the running config
+/// is kept.
+pub fn watch_config(
+    overrides: ConfigOverrides,
+    command_sender: mpsc::Sender<ClientCommands>,
+    ui_sender: mpsc::Sender<ConfigReload>,
+) {
+    let paths = config_files(&overrides)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect::<Vec<PathBuf>>();
+
+    thread::spawn(move || {
+        let mut last_seen = modified_times(&paths);
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let seen = modified_times(&paths);
+            if seen == last_seen {
+                continue;
+            }
+            last_seen = seen;
+
+            let reload = load_config(&overrides).and_then(|loaded| {
+                for warning in &loaded.warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+
+                let payload = toml::to_string(&loaded.config).map_err(|e| e.to_string())?;
+                let _ = command_sender.send(ClientCommands {
+                    command_type: ClientCommandType::UpdateConfig,
+                    payload: Some(payload),
+                });
+
+                Ok(loaded.config)
+            });
+
+            // both ends are gone once the window is closed
+            if ui_sender.send(reload).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+fn modified_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
@@ -0,0 +1,48 @@
+//! Latencies of the instructions, taken from `engine.cycle-costs`.
+//!
+//! Cycles are counted for every executed instruction whatever the timing is, stepping back does
+//! not take them back. With `engine.timing = "cycles"` they also decide how many ticks an
+//! instruction takes to run.
+
+use irv::{InstructionType, IrInstruction, Operand};
+
+use crate::config::CycleCostConfig;
+
+/// Cycles the instruction takes, never less than 1
+pub fn cost(costs: &CycleCostConfig, instruction: &IrInstruction) -> u64 {
+    let table = &costs.instructions;
+    let base = match instruction.ty {
+        InstructionType::CALL => table.call,
+        InstructionType::LOAD => table.load,
+        InstructionType::MOVE => table.r#move,
+        InstructionType::INC => table.inc,
+        InstructionType::DEC => table.dec,
+        InstructionType::ADD => table.add,
+        InstructionType::SUB => table.sub,
+        InstructionType::MUL => table.mul,
+        InstructionType::DIV => table.div,
+        InstructionType::CMP => table.cmp,
+        InstructionType::JEQ => table.jeq,
+        InstructionType::JLT => table.jlt,
+        InstructionType::JGT => table.jgt,
+        InstructionType::JMP => table.jmp,
+        InstructionType::RET => table.ret,
+        InstructionType::NOP => table.nop,
+        InstructionType::BRK => table.brk,
+        InstructionType::EXIT => table.exit,
+    };
+
+    let operands = instruction
+        .operands
+        .iter()
+        .map(|operand| match operand {
+            Operand::Register(_) => costs.operands.register,
+            Operand::Immediate(_) => costs.operands.immediate,
+            Operand::Variable { .. } => costs.operands.memory,
+            Operand::Label { .. } | Operand::Function(_) => 0,
+        })
+        .map(u64::from)
+        .sum::<u64>();
+
+    (u64::from(base) + operands).max(1)
+}
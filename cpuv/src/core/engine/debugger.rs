@@ -0,0 +1,401 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use irv::{parse_immediate, DataType, IrProgram, Registers};
+
+use super::expression::Expr;
+use super::runner::{is_string, number};
+
+/// A breakpoint as set from the editor, on the first instruction of a source line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BreakpointSpec {
+    pub line: usize,
+    /// Only pause when this expression is not 0, always pause when empty
+    #[serde(default)]
+    pub condition: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WatchKind {
+    /// Pause after every instruction writing to the target, even if the value stays the same
+    Write,
+    /// Pause when the value of the target is different after an instruction
+    Change,
+}
+
+/// A watchpoint as set from the editor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchpointSpec {
+    /// A register (`rax`), a variable of the data section, a heap address (`mem[16]`) or a range
+    /// of heap addresses, end excluded (`mem[16..24]`)
+    pub target: String,
+    pub kind: WatchKind,
+}
+
+/// State of a breakpoint or watchpoint, sent to the UI with the spec it belongs to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebugPointStatus<T> {
+    pub spec: T,
+    /// Instruction address of a breakpoint, none if no code is on or after its line
+    pub address: Option<usize>,
+    pub hits: usize,
+    /// Why the condition or target could not be used
+    pub error: Option<String>,
+}
+
+struct Breakpoint {
+    status: DebugPointStatus<BreakpointSpec>,
+    condition: Option<Expr>,
+}
+
+struct Watchpoint {
+    status: DebugPointStatus<WatchpointSpec>,
+    target: Option<WatchTarget>,
+    /// Value of the target after the last instruction
+    last: Vec<u8>,
+    written: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum WatchTarget {
+    Register(String),
+    /// Heap bytes, shown as a number like the runner reads them when they hold an integer variable
+    Memory {
+        range: Range<usize>,
+        ty: Option<DataType>,
+    },
+}
+
+/// A watchpoint that fired, with the values of its target before and after the instruction
+pub struct WatchHit {
+    pub target: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Conditional breakpoints and watchpoints of the engine
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl Debugger {
+    /// Replaces the breakpoints, returning the conditions that could not be parsed. Breakpoints
+    /// that did not change keep their hit count.
+    pub fn set_breakpoints(&mut self, specs: Vec<BreakpointSpec>) -> Vec<String> {
+        let previous = std::mem::take(&mut self.breakpoints);
+        self.breakpoints = specs
+            .into_iter()
+            .map(|spec| {
+                let condition = match spec.condition.trim() {
+                    "" => Ok(None),
+                    condition => Expr::parse(condition).map(Some),
+                };
+                let hits = previous
+                    .iter()
+                    .find(|breakpoint| breakpoint.status.spec == spec)
+                    .map_or(0, |breakpoint| breakpoint.status.hits);
+
+                Breakpoint {
+                    condition: condition.clone().unwrap_or_default(),
+                    status: DebugPointStatus {
+                        error: condition.err(),
+                        spec,
+                        address: None,
+                        hits,
+                    },
+                }
+            })
+            .collect();
+
+        self.breakpoints
+            .iter()
+            .filter_map(|breakpoint| {
+                let status = &breakpoint.status;
+                let error = status.error.as_ref()?;
+                Some(format!(
+                    "Breakpoint on line {}: {}",
+                    status.spec.line, error
+                ))
+            })
+            .collect()
+    }
+
+    /// Replaces the watchpoints, the targets are checked by [`Debugger::resolve`]
+    pub fn set_watchpoints(&mut self, specs: Vec<WatchpointSpec>) {
+        let previous = std::mem::take(&mut self.watchpoints);
+        self.watchpoints = specs
+            .into_iter()
+            .map(|spec| Watchpoint {
+                status: DebugPointStatus {
+                    hits: previous
+                        .iter()
+                        .find(|watchpoint| watchpoint.status.spec == spec)
+                        .map_or(0, |watchpoint| watchpoint.status.hits),
+                    spec,
+                    address: None,
+                    error: None,
+                },
+                target: None,
+                last: Vec::new(),
+                written: false,
+            })
+            .collect();
+    }
+
+    /// Maps breakpoint lines to addresses and watchpoint targets to registers and heap ranges of
+    /// the loaded program, and takes the current values as the baseline for changes. Returns the
+    /// watchpoint targets that could not be resolved.
+    pub fn resolve(
+        &mut self,
+        ir: Option<&IrProgram>,
+        registers: &Registers,
+        memory: &[u8],
+    ) -> Vec<String> {
+        for breakpoint in &mut self.breakpoints {
+            let line = breakpoint.status.spec.line;
+            breakpoint.status.address = ir.and_then(|ir| ir.address_for_line(line));
+        }
+
+        let mut errors = Vec::new();
+        for watchpoint in &mut self.watchpoints {
+            let target = &watchpoint.status.spec.target;
+            let resolved = resolve_target(target.trim(), ir, registers, memory.len());
+
+            watchpoint.status.error = resolved.as_ref().err().cloned();
+            if let Err(e) = &resolved {
+                errors.push(format!("Watchpoint {}: {}", target, e));
+            }
+
+            watchpoint.target = resolved.ok();
+            watchpoint.last = watchpoint
+                .target
+                .as_ref()
+                .map(|target| value(target, registers, memory))
+                .unwrap_or_default();
+            watchpoint.written = false;
+        }
+
+        errors
+    }
+
+    /// Called when a program is loaded, hits are counted per run
+    pub fn reset_hits(&mut self) {
+        for breakpoint in &mut self.breakpoints {
+            breakpoint.status.hits = 0;
+        }
+        for watchpoint in &mut self.watchpoints {
+            watchpoint.status.hits = 0;
+        }
+    }
+
+    pub fn breakpoints_at(&self, address: usize) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints
+            .iter()
+            .enumerate()
+            // a condition that does not parse disables the breakpoint until it is fixed
+            .filter(move |(_, breakpoint)| {
+                breakpoint.status.address == Some(address) && breakpoint.status.error.is_none()
+            })
+            .map(|(index, _)| index)
+    }
+
+    pub fn condition(&self, index: usize) -> Option<&Expr> {
+        self.breakpoints[index].condition.as_ref()
+    }
+
+    /// Counts a hit of the breakpoint, returning the condition it stopped on
+    pub fn hit_breakpoint(&mut self, index: usize) -> Option<String> {
+        let status = &mut self.breakpoints[index].status;
+        status.hits += 1;
+        Some(status.spec.condition.trim().to_string()).filter(|condition| !condition.is_empty())
+    }
+
+    pub fn record_register_write(&mut self, name: &str) {
+        for watchpoint in &mut self.watchpoints {
+            if matches!(&watchpoint.target, Some(WatchTarget::Register(register)) if register == name)
+            {
+                watchpoint.written = true;
+            }
+        }
+    }
+
+    pub fn record_memory_write(&mut self, range: Range<usize>) {
+        for watchpoint in &mut self.watchpoints {
+            if let Some(WatchTarget::Memory { range: watched, .. }) = &watchpoint.target {
+                if watched.start < range.end && range.start < watched.end {
+                    watchpoint.written = true;
+                }
+            }
+        }
+    }
+
+    /// Takes the current values as the baseline for changes, after the state was changed without
+    /// running instructions
+    pub fn sync_watchpoints(&mut self, registers: &Registers, memory: &[u8]) {
+        for watchpoint in &mut self.watchpoints {
+            if let Some(target) = &watchpoint.target {
+                watchpoint.last = value(target, registers, memory);
+            }
+            watchpoint.written = false;
+        }
+    }
+
+    /// Checks the watchpoints after an instruction ran, counting a hit for every one that fired.
+    /// Returns the first of them.
+    pub fn check_watchpoints(&mut self, registers: &Registers, memory: &[u8]) -> Option<WatchHit> {
+        let mut first = None;
+
+        for watchpoint in &mut self.watchpoints {
+            let Some(target) = &watchpoint.target else {
+                continue;
+            };
+
+            let current = value(target, registers, memory);
+            let fired = match watchpoint.status.spec.kind {
+                WatchKind::Write => watchpoint.written,
+                WatchKind::Change => current != watchpoint.last,
+            };
+
+            if fired {
+                watchpoint.status.hits += 1;
+                first.get_or_insert_with(|| WatchHit {
+                    target: watchpoint.status.spec.target.clone(),
+                    old: format_value(target, &watchpoint.last),
+                    new: format_value(target, &current),
+                });
+            }
+
+            watchpoint.last = current;
+            watchpoint.written = false;
+        }
+
+        first
+    }
+
+    pub fn breakpoint_status(&self) -> Vec<DebugPointStatus<BreakpointSpec>> {
+        self.breakpoints
+            .iter()
+            .map(|breakpoint| breakpoint.status.clone())
+            .collect()
+    }
+
+    pub fn watchpoint_status(&self) -> Vec<DebugPointStatus<WatchpointSpec>> {
+        self.watchpoints
+            .iter()
+            .map(|watchpoint| watchpoint.status.clone())
+            .collect()
+    }
+}
+
+fn resolve_target(
+    target: &str,
+    ir: Option<&IrProgram>,
+    registers: &Registers,
+    heap_size: usize,
+) -> Result<WatchTarget, String> {
+    if registers.get(target).is_some() {
+        return Ok(WatchTarget::Register(target.to_string()));
+    }
+
+    let (range, ty) = if let Some(inner) = target
+        .strip_prefix("mem[")
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        let address = |part: &str| {
+            parse_immediate(part.trim())
+                .and_then(|address| usize::try_from(address).ok())
+                .ok_or_else(|| format!("Invalid address {}", part.trim()))
+        };
+
+        let range = match inner.split_once("..") {
+            Some((start, end)) => address(start)?..address(end)?,
+            None => {
+                let start = address(inner)?;
+                let end = start
+                    .checked_add(1)
+                    .ok_or_else(|| format!("Invalid address {}", inner.trim()))?;
+                start..end
+            }
+        };
+        (range, None)
+    } else {
+        let ir = ir.ok_or("Variables can only be watched once the program is loaded")?;
+        let slot = ir
+            .data
+            .iter()
+            .find(|slot| slot.name == target)
+            .ok_or_else(|| format!("No register or variable called {}", target))?;
+        let integer = !is_string(&slot.ty) && !matches!(slot.ty, DataType::Real4 | DataType::Real8);
+        (
+            slot.address..slot.address + slot.ty.size(),
+            integer.then(|| slot.ty.clone()),
+        )
+    };
+
+    if range.is_empty() || range.end > heap_size {
+        return Err(format!(
+            "{}..{} is not a range inside of the {} byte heap",
+            range.start, range.end, heap_size
+        ));
+    }
+
+    Ok(WatchTarget::Memory { range, ty })
+}
+
+fn value(target: &WatchTarget, registers: &Registers, memory: &[u8]) -> Vec<u8> {
+    match target {
+        WatchTarget::Register(name) => registers
+            .get(name)
+            .unwrap_or_default()
+            .to_le_bytes()
+            .to_vec(),
+        WatchTarget::Memory { range, .. } => memory.get(range.clone()).unwrap_or_default().to_vec(),
+    }
+}
+
+/// Registers and integer variables as numbers, other heap ranges as hex bytes
+fn format_value(target: &WatchTarget, bytes: &[u8]) -> String {
+    match target {
+        // registers are stored as their value, which is already sign extended
+        WatchTarget::Register(_) if bytes.len() == 16 => {
+            i128::from_le_bytes(bytes.try_into().unwrap()).to_string()
+        }
+        WatchTarget::Memory { ty: Some(ty), .. } if bytes.len() == ty.size() => {
+            number("", ty, bytes, 0).map_or_else(|e| e.to_string(), |value| value.to_string())
+        }
+        _ => bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<String>>()
+            .join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(ty: Option<DataType>, bytes: &[u8]) -> String {
+        let target = WatchTarget::Memory {
+            range: 0..bytes.len(),
+            ty,
+        };
+        format_value(&target, bytes)
+    }
+
+    #[test]
+    fn watched_values_are_formatted_like_the_runner_reads_them() {
+        assert_eq!(memory(Some(DataType::Byte), &[200]), "200");
+        assert_eq!(memory(Some(DataType::SByte), &[200]), "-56");
+        assert_eq!(memory(Some(DataType::Word), &[0x18, 0xfc]), "64536");
+        assert_eq!(memory(Some(DataType::SWord), &[0x18, 0xfc]), "-1000");
+        assert_eq!(memory(Some(DataType::QWord), &[0xff; 8]), "-1");
+        assert_eq!(memory(None, &[200, 1]), "c8 01");
+
+        let register = WatchTarget::Register("srax".to_string());
+        assert_eq!(format_value(&register, &(-7i128).to_le_bytes()), "-7");
+    }
+}
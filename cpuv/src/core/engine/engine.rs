@@ -1,15 +1,33 @@
-use std::sync::mpsc;
-use std::thread::{self};
-use std::time::{self, Duration};
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 use strum::EnumIter;
 
-use super::runner::{run_instruction, InstructionExecutionError};
-use crate::RootConfig;
-use irv::{parse, Label, Program, Registers};
+use super::cycles;
+use super::debugger::{BreakpointSpec, DebugPointStatus, Debugger, WatchpointSpec};
+use super::heap_latency::{self, HeapLatency, Stall};
+use super::history::History;
+use super::logger::Logger;
+use super::parsing_results::write_parsing_results;
+use super::publisher::{self, StatePublisher, StateReceiver};
+use super::runner::{
+    load_data, run_instruction, InstructionExecutionError, InstructionExecutionSeccess,
+};
+use super::snapshot::{Snapshot, SNAPSHOT_VERSION};
+use super::stdout::StdoutRouter;
+use super::trace::Tracer;
+use crate::config::Timing;
+use crate::{RootConfig, FPS};
+use irv::{
+    assemble, disassemble, translate, AsmFrontend, Frontend, FrontendRegistry, InstructionType,
+    IrProgram, Operand, Program, Registers,
+};
 
-const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Instructions run between two checks for client commands while running to a stop condition
+const RUN_UNTIL_BATCH: usize = 10_000;
 
 type Payload = Option<String>;
 
@@ -24,32 +42,147 @@ impl PayloadImpl for Payload {
 }
 
 pub struct Engine {
-    pub program: Option<Program>,
-    registers: Registers,
+    /// Shared with the states sent to the UI, which only clone the pointer
+    pub program: Option<Arc<Program>>,
+    /// Source the running program was compiled from, kept for snapshots
+    pub source: Option<String>,
+    pub ir: Option<IrProgram>,
+    /// `ir` as text, rendered once whenever it changes
+    ir_text: Arc<String>,
+    pub registers: Registers,
+    /// The heap, the data section is laid out at its start
+    pub memory: Vec<u8>,
+    pub debugger: Debugger,
+    pub history: History,
+    heap_latency: HeapLatency,
 
-    engine_data_sender: mpsc::Sender<EngineData>,
+    engine_data_sender: StatePublisher,
     client_command_reciever: mpsc::Receiver<ClientCommands>,
-    stdout_sender: mpsc::Sender<StdLogMessage>,
+    logger: Logger,
+    stdout: StdoutRouter,
+    pub tracer: Tracer,
 
     pub options: RootConfig,
 
+    frontends: FrontendRegistry,
+    frontend: Arc<dyn Frontend>,
+
     pub state: EngineState,
-    pub current_label: Option<Label>,
 }
 
 pub struct EngineState {
     pub tick: usize,
     pub instruction_ptr: usize,
+    /// Instructions, or cycles with cycle timing, owed to the program, in `1/ipt.denominator`
+    /// units
+    pub instruction_budget: u64,
+    /// Cycles spent on the executed instructions, per `engine.cycle-costs`
+    pub cycles: u64,
+    /// Instructions executed since the program was started, including the ones run again after
+    /// stepping back
+    pub instructions: u64,
+    /// Set while the next instruction waits for the heap
+    pub stall: Option<Stall>,
+    pub flags: Flags,
+    /// Return addresses of the CALLs to labels that have not returned yet
+    pub call_stack: Vec<usize>,
+    /// Set while stepping over, stepping out or running to the cursor. The program then runs as
+    /// fast as possible and pauses once the condition is met.
+    pub run_until: Option<RunUntil>,
+    /// Why the program last paused or stopped, cleared when it runs again
+    pub stop_reason: Option<StopReason>,
+    /// Address the program was paused at, its breakpoint is skipped once when resuming
+    resume_from: Option<usize>,
     running_state: EngineRunningState,
 }
 
+/// Set by CMP and read by the conditional jumps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Flags {
+    /// Both operands were equal
+    pub zero: bool,
+    /// The first operand was less than the second
+    pub negative: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunUntil {
+    /// Until the call stack is at most this deep, after the CALLs being stepped over returned
+    CallDepth(usize),
+    /// Until the instruction at this address is next
+    Address(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StopReason {
+    /// Paused or stopped from the controls
+    User,
+    /// A step finished
+    Step,
+    /// Reached the stop condition of a step over, step out or run to cursor
+    Reached,
+    /// About to run the instruction at this address, which has a breakpoint whose condition, if
+    /// any, held
+    Breakpoint {
+        address: usize,
+        condition: Option<String>,
+    },
+    /// The last instruction wrote to or changed a watched register or heap range
+    Watchpoint {
+        target: String,
+        old: String,
+        new: String,
+    },
+    /// Ran the BRK instruction at this address
+    Break(usize),
+    /// The program ran EXIT with this exit code
+    Exit(i128),
+    /// The program ran past its last instruction
+    EndOfProgram,
+    /// Moved to an earlier or later point of the history
+    History,
+    /// Reverse Continue reached the oldest recorded instruction
+    HistoryStart,
+    /// Restored from a snapshot, paused where it was saved
+    Restored,
+    Error(String),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::User => write!(f, "Stopped from the controls"),
+            StopReason::Step => write!(f, "Step finished"),
+            StopReason::Reached => write!(f, "Reached the target"),
+            StopReason::Breakpoint {
+                address,
+                condition: None,
+            } => write!(f, "Breakpoint at {:04}", address),
+            StopReason::Breakpoint {
+                address,
+                condition: Some(condition),
+            } => write!(f, "Breakpoint at {:04}, {} holds", address, condition),
+            StopReason::Watchpoint { target, old, new } => {
+                write!(f, "Watchpoint on {}: {} -> {}", target, old, new)
+            }
+            StopReason::Break(address) => write!(f, "BRK at {:04}", address),
+            StopReason::Exit(code) => write!(f, "Exited with code {}", code),
+            StopReason::EndOfProgram => write!(f, "Ran past the last instruction"),
+            StopReason::History => write!(f, "Moved through the history"),
+            StopReason::HistoryStart => write!(f, "Reached the oldest recorded instruction"),
+            StopReason::Restored => write!(f, "Restored from a snapshot"),
+            StopReason::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct StdLogMessage {
     pub message: String,
     pub log_level: StdLogLevel,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[allow(warnings)]
 pub enum StdLogLevel {
     INFO,
@@ -67,30 +200,54 @@ pub enum EngineRunningState {
     Paused,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct EngineData {
     #[serde(rename = "Tick")]
     pub tick: usize,
+    #[serde(rename = "Cycles")]
+    pub cycles: u64,
+    #[serde(rename = "Instructions")]
+    pub instructions: u64,
+    #[serde(rename = "Memory Stall")]
+    pub stall: Option<Stall>,
     #[serde(rename = "Current State")]
     pub engine_running_state: EngineRunningState,
     #[serde(rename = "Parsing Result")]
-    pub program: Option<Program>,
+    pub program: Option<Arc<Program>>,
     #[serde(rename = "IR Repserentation")]
-    pub ir_repsersentation: String,
+    pub ir_repsersentation: Arc<String>,
     pub responding_to: Option<ClientCommandType>,
     pub registers: Registers,
+    #[serde(rename = "Frontend")]
+    pub frontend: String,
+    #[serde(rename = "Instruction Pointer")]
+    pub instruction_ptr: usize,
+    /// Source line of the next instruction
+    #[serde(rename = "Current Line")]
+    pub current_line: Option<usize>,
+    #[serde(rename = "Flags")]
+    pub flags: Flags,
+    #[serde(rename = "Call Depth")]
+    pub call_depth: usize,
+    /// Recorded instructions that can be stepped back through
+    #[serde(rename = "History")]
+    pub history_len: usize,
+    /// How many of the recorded instructions are applied, less than `history_len` after stepping
+    /// back
+    #[serde(rename = "History Position")]
+    pub history_position: usize,
+    #[serde(rename = "Stop Reason")]
+    pub stop_reason: Option<StopReason>,
+    pub breakpoints: Vec<DebugPointStatus<BreakpointSpec>>,
+    pub watchpoints: Vec<DebugPointStatus<WatchpointSpec>>,
+    /// Source of a restored snapshot, only set in the response to LoadSnapshot
+    pub source: Option<String>,
 }
 
-impl Default for EngineData {
-    fn default() -> Self {
-        Self {
-            tick: Default::default(),
-            engine_running_state: Default::default(),
-            program: Default::default(),
-            ir_repsersentation: "TODO".to_string(),
-            responding_to: Default::default(),
-            registers: Default::default(),
-        }
+impl EngineData {
+    /// Average cycles per instruction, none before the first instruction ran
+    pub fn cpi(&self) -> Option<f64> {
+        (self.instructions > 0).then(|| self.cycles as f64 / self.instructions as f64)
     }
 }
 
@@ -111,6 +268,50 @@ pub enum ClientCommandType {
 
     TranslateToIR,
     TranslateToIRWithoutUpdate,
+
+    /// Runs the next instruction. When stopped, the program in the payload is loaded and paused
+    /// before its first instruction instead.
+    StepInstruction,
+    /// Like StepInstruction, but a CALL to a label runs until it returns
+    StepOver,
+    /// Runs until the label that is being executed returns
+    StepOut,
+    /// Runs until the first instruction of the source line in the payload is next
+    #[strum(disabled)]
+    RunToCursor,
+    /// Takes back the last instruction
+    StepBack,
+    /// Takes back instructions until a breakpoint or watchpoint is hit or the history runs out
+    ReverseContinue,
+    /// Moves to the point of the history in the payload, the number of applied instructions
+    #[strum(disabled)]
+    SeekHistory,
+
+    /// Replaces the breakpoints with the JSON list of [`BreakpointSpec`]s in the payload
+    #[strum(disabled)]
+    SetBreakpoints,
+    /// Replaces the watchpoints with the JSON list of [`WatchpointSpec`]s in the payload
+    #[strum(disabled)]
+    SetWatchpoints,
+
+    /// Picks the frontend used to compile the source from the extension of the path in the payload
+    #[strum(disabled)]
+    SelectFrontend,
+    /// Loads the irv binary at the path in the payload
+    #[strum(disabled)]
+    LoadBinary,
+    /// Assembles the current program into an irv binary at the path in the payload
+    #[strum(disabled)]
+    SaveBinary,
+    /// Writes the state of the running or paused program to the snapshot at the path in the payload
+    #[strum(disabled)]
+    SaveSnapshot,
+    /// Restores the snapshot at the path in the payload, paused where it was saved
+    #[strum(disabled)]
+    LoadSnapshot,
+    /// Replaces the engine settings with the TOML config in the payload
+    #[strum(disabled)]
+    UpdateConfig,
 }
 
 impl Engine {
@@ -119,28 +320,63 @@ impl Engine {
     ) -> (
         Self,
         mpsc::Sender<ClientCommands>,
-        mpsc::Receiver<EngineData>,
+        StateReceiver,
         mpsc::Receiver<StdLogMessage>,
     ) {
-        let (data_send, data_recv) = mpsc::channel::<EngineData>();
+        let (data_send, data_recv) = publisher::channel();
         let (client_send, client_recv) = mpsc::channel::<ClientCommands>();
         let (log_send, log_recv) = mpsc::channel::<StdLogMessage>();
 
+        let (stdout, stdout_error) = StdoutRouter::new(&options.program, log_send.clone());
+        let logger = Logger::new(
+            options.engine.log_level,
+            &options.program.output_logs,
+            log_send,
+        );
+        if let Some(e) = stdout_error {
+            logger.log(StdLogLevel::ERROR, e.as_str());
+        }
+
+        let frontends = FrontendRegistry::default();
+        let frontend = frontends
+            .for_path(&options.program.program_path)
+            .unwrap_or_else(|| Arc::new(AsmFrontend));
+
         let engine = Self {
             program: None,
-            current_label: None,
+            source: None,
+            ir: None,
+            ir_text: Arc::default(),
             registers: Registers {
                 ..Default::default()
             },
+            memory: Vec::new(),
+            debugger: Debugger::default(),
+            history: History::new(options.engine.history_size),
+            heap_latency: HeapLatency::default(),
             options,
 
+            frontends,
+            frontend,
+
             engine_data_sender: data_send,
             client_command_reciever: client_recv,
-            stdout_sender: log_send,
+            logger,
+            stdout,
+            tracer: Tracer::default(),
 
             state: EngineState {
                 tick: 0,
                 instruction_ptr: 0,
+                instruction_budget: 0,
+                cycles: 0,
+                instructions: 0,
+                stall: None,
+                flags: Flags::default(),
+                call_stack: Vec::new(),
+                run_until: None,
+                stop_reason: None,
+                resume_from: None,
                 running_state: EngineRunningState::Stopped,
             },
         };
@@ -151,123 +387,1425 @@ impl Engine {
     pub fn get_current_state(&self, responding_to: Option<ClientCommandType>) -> EngineData {
         EngineData {
             tick: self.state.tick,
+            cycles: self.state.cycles,
+            instructions: self.state.instructions,
+            stall: self.state.stall,
             engine_running_state: self.state.running_state.clone(),
             program: self.program.clone(),
-            ir_repsersentation: "TODO".to_string(),
+            ir_repsersentation: Arc::clone(&self.ir_text),
             responding_to,
             registers: self.registers.clone(),
+            frontend: self.frontend.name().to_string(),
+            instruction_ptr: self.state.instruction_ptr,
+            current_line: self.ir.as_ref().and_then(|ir| {
+                if self.state.running_state == EngineRunningState::Stopped {
+                    return None;
+                }
+
+                ir.instructions
+                    .get(self.state.instruction_ptr)
+                    .and_then(|instruction| instruction.line)
+            }),
+            flags: self.state.flags,
+            call_depth: self.state.call_stack.len(),
+            history_len: self.history.recorded(),
+            history_position: self.history.position(),
+            stop_reason: self.state.stop_reason.clone(),
+            breakpoints: self.debugger.breakpoint_status(),
+            watchpoints: self.debugger.watchpoint_status(),
+            source: None,
         }
     }
 
-    fn send_stdlog(&self, log_level: StdLogLevel, message: &str) {
-        let message = format!(
-            "[{:?}] {:?} - {}",
-            log_level,
-            chrono::Local::now().format(TIME_FORMAT).to_string(),
-            message
+    fn set_ir(&mut self, ir: Option<IrProgram>) {
+        self.ir_text = Arc::new(ir.as_ref().map(IrProgram::to_string).unwrap_or_default());
+        self.ir = ir;
+    }
+
+    /// Compiles the source with the selected frontend, reporting any diagnostics to the system log
+    fn compile(&self, source: &str) -> Option<Program> {
+        match self.frontend.compile(source) {
+            Ok(program) => {
+                let config = &self.options.program.output_parsing_results;
+                if config.should_write {
+                    if let Err(e) = write_parsing_results(config, &program) {
+                        self.send_stdlog(
+                            StdLogLevel::ERROR,
+                            format!("Failed to write parsing results! {}", e).as_str(),
+                        );
+                    }
+                }
+                Some(program)
+            }
+            Err(diagnostics) => {
+                for diagnostic in diagnostics.iter() {
+                    self.send_stdlog(
+                        StdLogLevel::ERROR,
+                        format!("{} Error! {}", self.frontend.name(), diagnostic).as_str(),
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    /// Applies a config while the engine is running. Rates take effect on the next tick, the heap
+    /// size on the next start.
+    fn update_config(&mut self, options: RootConfig) {
+        self.logger
+            .reconfigure(options.engine.log_level, &options.program.output_logs);
+        if let Some(e) = self.stdout.reconfigure(&options.program) {
+            self.send_stdlog(StdLogLevel::ERROR, e.as_str());
+        }
+
+        self.history.set_capacity(options.engine.history_size);
+        self.options = options;
+        self.send_stdlog(
+            StdLogLevel::INFO,
+            format!(
+                "Config updated, tps {} ipt {}{}{}",
+                self.options.engine.tps,
+                self.options.engine.ipt,
+                if self.options.engine.timing == Timing::Cycles {
+                    " cycles"
+                } else {
+                    ""
+                },
+                if self.options.engine.unthrottled {
+                    " unthrottled"
+                } else {
+                    ""
+                }
+            )
+            .as_str(),
         );
+    }
+
+    pub fn send_stdlog(&self, log_level: StdLogLevel, message: &str) {
+        self.logger.log(log_level, message);
+    }
 
-        let _ = self
-            .stdout_sender
-            .send(StdLogMessage { message, log_level });
+    /// Writes program output to every configured stdout sink
+    pub fn print(&self, text: &str) {
+        self.stdout.print(text);
     }
 
     // TODO: Fix bug where parsing result is sent but overwritted when engine is not in started
     // state
+    /// Runs until the client hangs up. While stopped or paused the thread blocks on the next
+    /// command, while running it waits for the deadline of the next tick or a command, whichever
+    /// comes first.
     pub fn run(mut self) {
         self.send_stdlog(StdLogLevel::INFO, "Initalizing Engine...");
-        let _start_time = time::Instant::now();
+        let mut next_tick = Instant::now();
 
         loop {
-            if let Ok(client_command) = self.client_command_reciever.try_recv() {
-                self.run_client_commands(client_command);
-            }
+            let command = if self.state.running_state == EngineRunningState::Running {
+                let timeout = next_tick.saturating_duration_since(Instant::now());
+                match self.client_command_reciever.recv_timeout(timeout) {
+                    Ok(command) => Some(command),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            } else {
+                match self.client_command_reciever.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => return,
+                }
+            };
 
-            if self.state.running_state != EngineRunningState::Running {
+            // every pending command is handled before the next tick runs
+            if let Some(command) = command {
+                let was_running = self.state.running_state == EngineRunningState::Running;
+                self.run_client_commands(command);
+                if !was_running {
+                    next_tick = Instant::now();
+                }
                 continue;
             }
 
             // THIS NEEDS TO STAY HERE FOR THIS TO WORK!!!
             let _send_result = self.engine_data_sender.send(self.get_current_state(None));
 
-            let instruction_execution_result = run_instruction(&mut self);
-            if let Err(e) = instruction_execution_result {
-                if e != InstructionExecutionError::EndOfLabel {
-                    self.state.running_state = EngineRunningState::Stopped;
-                    self.send_stdlog(
-                        StdLogLevel::ERROR,
-                        format!("Instruction Failed! {:?}", e).as_str(),
-                    );
-                } else {
-                    self.state.running_state = EngineRunningState::Stopped;
+            if let Some(condition) = self.state.run_until {
+                self.run_until(condition);
+                continue;
+            }
+
+            if self.options.engine.unthrottled {
+                self.run_unthrottled();
+                continue;
+            }
+
+            self.run_tick();
+
+            // ticks are scheduled from the previous deadline rather than from when the tick ended,
+            // so the time spent executing does not add up. After falling behind by more than a
+            // tick, like after a config change, the schedule starts over instead of catching up.
+            let now = Instant::now();
+            next_tick += self.options.engine.tps.interval();
+            if next_tick + self.options.engine.tps.interval() < now {
+                next_tick = now;
+            }
+        }
+    }
+
+    /// Runs the instructions owed to the program for one tick
+    fn run_tick(&mut self) {
+        let address = self.state.instruction_ptr;
+        if let Some(stall) = self
+            .state
+            .stall
+            .as_mut()
+            .filter(|stall| stall.address == address && stall.remaining > 0)
+        {
+            stall.remaining -= 1;
+            self.state.tick += 1;
+            return;
+        }
+
+        // ipt is a fraction of instructions per tick, the remainder carries over so that
+        // "1/3" runs one instruction every third tick
+        let ipt = self.options.engine.ipt;
+        self.state.instruction_budget += ipt.numerator;
+
+        match self.options.engine.timing {
+            Timing::Instructions => {
+                let instructions = self.state.instruction_budget / ipt.denominator;
+                self.state.instruction_budget %= ipt.denominator;
+
+                for _ in 0..instructions {
+                    if self.wait_for_memory() || !self.execute() {
+                        break;
+                    }
+                }
+            }
+            // the budget is in cycles, an instruction runs once the cycles it costs were saved up
+            Timing::Cycles => loop {
+                if self.wait_for_memory() {
+                    break;
+                }
+
+                let cost = self.next_cost() * ipt.denominator;
+                if self.state.instruction_budget < cost {
+                    break;
+                }
+
+                let executed = self.state.instructions;
+                self.state.instruction_budget -= cost;
+                let keep_running = self.execute();
+                if self.state.instructions == executed {
+                    // paused at a breakpoint before the instruction ran, its cycles are not spent
+                    self.state.instruction_budget += cost;
+                }
+                if !keep_running {
+                    break;
+                }
+            },
+        }
+
+        self.state.tick += 1;
+    }
+
+    /// Starts a stall if the next instruction touches the heap and has not waited for it yet.
+    /// Returns whether it has to wait.
+    fn wait_for_memory(&mut self) -> bool {
+        let address = self.state.instruction_ptr;
+        if let Some(stall) = self.state.stall.filter(|stall| stall.address == address) {
+            return stall.remaining > 0;
+        }
+
+        let simulation = &self.options.engine.heap_access_simulation;
+        let touches_memory = self
+            .ir
+            .as_ref()
+            .and_then(|ir| ir.instructions.get(address))
+            .is_some_and(heap_latency::touches_memory);
+        if !simulation.enabled || !touches_memory {
+            return false;
+        }
+
+        let ticks = self.heap_latency.delay(simulation);
+        if ticks == 0 {
+            return false;
+        }
+
+        // this tick is the first one spent waiting
+        self.state.stall = Some(Stall {
+            address,
+            remaining: ticks - 1,
+            ticks,
+        });
+        true
+    }
+
+    /// Cycles the instruction at the instruction pointer costs, 1 past the end of the program
+    fn next_cost(&self) -> u64 {
+        self.ir
+            .as_ref()
+            .and_then(|ir| ir.instructions.get(self.state.instruction_ptr))
+            .map_or(1, |instruction| {
+                cycles::cost(&self.options.engine.cycle_costs, instruction)
+            })
+    }
+
+    /// Runs ticks back to back without waiting for one frame of the UI, which gets the state once
+    /// the frame is over
+    fn run_unthrottled(&mut self) {
+        let until = Instant::now() + Duration::from_millis(1000 / FPS);
+        while self.state.running_state == EngineRunningState::Running
+            && self.state.run_until.is_none()
+            && Instant::now() < until
+        {
+            self.run_tick();
+        }
+    }
+
+    /// Runs a single instruction, stopping or pausing the engine when the program exits, fails,
+    /// hits a BRK or a watchpoint or, while running, reaches a breakpoint. Returns whether the
+    /// program can keep running.
+    fn execute(&mut self) -> bool {
+        let address = self.state.instruction_ptr;
+        if self.state.running_state == EngineRunningState::Running
+            && self.state.resume_from != Some(address)
+        {
+            if let Some(reason) = self.check_breakpoints(address) {
+                self.send_stdlog(StdLogLevel::INFO, reason.to_string().as_str());
+                self.halt(EngineRunningState::Paused, reason);
+                let _ = self.engine_data_sender.send(self.get_current_state(None));
+                return false;
+            }
+        }
+        self.state.resume_from = None;
+
+        if let Some(instruction) = self.ir.as_ref().and_then(|ir| ir.instructions.get(address)) {
+            self.tracer.begin(self.state.tick, instruction);
+            self.state.cycles += cycles::cost(&self.options.engine.cycle_costs, instruction);
+            self.state.instructions += 1;
+            self.state.stall = None;
+        }
+        self.history.begin(&self.state);
+        let result = run_instruction(self);
+        self.history.finish(&self.state);
+        if let Some(e) = self
+            .tracer
+            .finish(self.state.flags, self.state.instruction_ptr)
+        {
+            self.send_stdlog(StdLogLevel::ERROR, e.as_str());
+        }
+        let watch_hit = self
+            .debugger
+            .check_watchpoints(&self.registers, &self.memory);
+
+        let (state, reason, level, message) = match result {
+            Ok(InstructionExecutionSeccess::Ok) => match watch_hit {
+                None => return true,
+                Some(hit) => (
+                    EngineRunningState::Paused,
+                    StopReason::Watchpoint {
+                        target: hit.target,
+                        old: hit.old,
+                        new: hit.new,
+                    },
+                    StdLogLevel::INFO,
+                    format!("Watchpoint hit by the instruction at {:04}", address),
+                ),
+            },
+            Ok(InstructionExecutionSeccess::Break) => (
+                EngineRunningState::Paused,
+                StopReason::Break(address),
+                StdLogLevel::INFO,
+                format!("Hit BRK at {:04}", address),
+            ),
+            Ok(InstructionExecutionSeccess::Exit(code)) => (
+                EngineRunningState::Stopped,
+                StopReason::Exit(code),
+                StdLogLevel::INFO,
+                format!("Program exited with code {}", code),
+            ),
+            Err(InstructionExecutionError::EndOfLabel) => (
+                EngineRunningState::Stopped,
+                StopReason::EndOfProgram,
+                StdLogLevel::INFO,
+                "Program Exited With Success!".to_string(),
+            ),
+            Err(e) => (
+                EngineRunningState::Stopped,
+                StopReason::Error(e.to_string()),
+                StdLogLevel::ERROR,
+                format!("Instruction Failed! {}", e),
+            ),
+        };
+
+        self.halt(state, reason);
+        self.send_stdlog(level, message.as_str());
+        let _ = self.engine_data_sender.send(self.get_current_state(None));
+        false
+    }
+
+    /// Pauses or stops the program, remembering why. The trace is flushed so that it can be read
+    /// while the program is not running.
+    fn halt(&mut self, state: EngineRunningState, reason: StopReason) {
+        let stopping = state == EngineRunningState::Stopped
+            && self.state.running_state != EngineRunningState::Stopped;
+        if let Some((path, entries)) = self.tracer.flush().filter(|_| stopping) {
+            self.send_stdlog(
+                StdLogLevel::INFO,
+                format!("Wrote trace of {} instructions to {}", entries, path).as_str(),
+            );
+        }
+
+        self.state.resume_from =
+            (state == EngineRunningState::Paused).then_some(self.state.instruction_ptr);
+        self.state.running_state = state;
+        self.state.stop_reason = Some(reason);
+        self.state.run_until = None;
+    }
+
+    fn resume(&mut self) {
+        self.state.running_state = EngineRunningState::Running;
+        self.state.stop_reason = None;
+    }
+
+    /// The breakpoint to pause at before running the instruction at `address`, if any. A
+    /// condition that cannot be evaluated pauses as well.
+    fn check_breakpoints(&mut self, address: usize) -> Option<StopReason> {
+        let index = self.debugger.breakpoints_at(address).find(|index| {
+            let Some(condition) = self.debugger.condition(*index) else {
+                return true;
+            };
+
+            condition.eval(self).map_or_else(
+                |e| {
                     self.send_stdlog(
-                        StdLogLevel::INFO,
-                        "Program Exited With Success!".to_string().as_str(),
+                        StdLogLevel::WARN,
+                        format!("Breakpoint condition failed! {}", e).as_str(),
                     );
-                }
+                    true
+                },
+                |value| value != 0,
+            )
+        })?;
+
+        Some(StopReason::Breakpoint {
+            address,
+            condition: self.debugger.hit_breakpoint(index),
+        })
+    }
+
+    /// Takes back the last applied instruction of the history. Returns false if there is none.
+    fn step_back(&mut self) -> bool {
+        self.ir.is_some()
+            && self
+                .history
+                .undo(&mut self.registers, &mut self.memory, &mut self.state)
+    }
+
+    /// Pauses at a point of the history that was moved to
+    fn pause_in_history(&mut self, reason: StopReason) {
+        self.debugger
+            .sync_watchpoints(&self.registers, &self.memory);
+        self.halt(EngineRunningState::Paused, reason);
+    }
+
+    /// Maps breakpoints and watchpoints to the loaded program
+    fn resolve_debug_points(&mut self) {
+        let errors = self
+            .debugger
+            .resolve(self.ir.as_ref(), &self.registers, &self.memory);
+
+        // variables can only be found in a loaded program, there is nothing to warn about before
+        if self.ir.is_some() {
+            for error in errors {
+                self.send_stdlog(StdLogLevel::WARN, error.as_str());
             }
+        }
+    }
 
-            self.state.tick += 1;
-            thread::sleep(Duration::from_millis(
-                (1000 / self.options.engine.tps).try_into().unwrap(),
+    /// Runs a batch of instructions without waiting for ticks, pausing once `condition` is met
+    fn run_until(&mut self, condition: RunUntil) {
+        for _ in 0..RUN_UNTIL_BATCH {
+            if !self.execute() {
+                return;
+            }
+
+            let reached = match condition {
+                RunUntil::CallDepth(depth) => self.state.call_stack.len() <= depth,
+                RunUntil::Address(address) => self.state.instruction_ptr == address,
+            };
+
+            if reached {
+                self.halt(EngineRunningState::Paused, StopReason::Reached);
+                let _ = self.engine_data_sender.send(self.get_current_state(None));
+                return;
+            }
+        }
+    }
+
+    /// Compiles and loads a program, ready to run from its `@start` label, or `@_start` without one
+    fn load(&mut self, source: &str) -> bool {
+        let Some(program) = self.compile(source) else {
+            return false;
+        };
+
+        let loaded = translate(&program)
+            .map_err(|e| format!("IR Translation Failed! {}", e))
+            .and_then(|ir| {
+                let start = ir
+                    .label_address("start")
+                    .or_else(|| ir.label_address("_start"))
+                    .ok_or("The program has no @start label")?;
+                let memory = load_data(&ir, self.options.engine.heap_memory_size)?;
+                Ok((ir, start, memory))
+            });
+
+        let (ir, start, memory) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                self.send_stdlog(StdLogLevel::ERROR, e.as_str());
+                return false;
+            }
+        };
+
+        self.program = Some(Arc::new(program));
+        self.source = Some(source.to_string());
+        self.set_ir(Some(ir));
+        self.memory = memory;
+        self.registers = Registers::default();
+        self.state.tick = 0;
+        self.state.instruction_ptr = start;
+        self.state.instruction_budget = 0;
+        self.state.cycles = 0;
+        self.state.instructions = 0;
+        self.state.stall = None;
+        self.heap_latency
+            .reset(self.options.engine.heap_access_simulation.seed);
+        self.state.flags = Flags::default();
+        self.state.call_stack.clear();
+        self.state.run_until = None;
+        self.state.stop_reason = None;
+        self.state.resume_from = None;
+        self.history.clear();
+        self.stdout.clear();
+        self.start_trace();
+        self.debugger.reset_hits();
+        self.resolve_debug_points();
+        true
+    }
+
+    fn start_trace(&mut self) {
+        if let Some(e) = self.tracer.start(&self.options.program.output_trace) {
+            self.send_stdlog(StdLogLevel::ERROR, e.as_str());
+        }
+    }
+
+    /// The state of the loaded program, none when stopped
+    fn snapshot(&self) -> Option<Snapshot> {
+        if self.state.running_state == EngineRunningState::Stopped {
+            return None;
+        }
+
+        Some(Snapshot {
+            version: SNAPSHOT_VERSION,
+            frontend: self.frontend.name().to_string(),
+            source: self.source.clone(),
+            program: self.program.as_deref()?.clone(),
+            registers: self.registers.clone(),
+            memory: self.memory.clone(),
+            instruction_ptr: self.state.instruction_ptr,
+            tick: self.state.tick,
+            instruction_budget: self.state.instruction_budget,
+            cycles: self.state.cycles,
+            instructions: self.state.instructions,
+            stall: self.state.stall,
+            heap_latency: self.heap_latency,
+            flags: self.state.flags,
+            call_stack: self.state.call_stack.clone(),
+            stdout: self.stdout.printed(),
+        })
+    }
+
+    /// Loads the program of a snapshot and puts the machine into the saved state, paused
+    fn restore(&mut self, snapshot: Snapshot) -> Result<(), String> {
+        let frontend = self.frontends.for_name(&snapshot.frontend)?;
+        let ir =
+            translate(&snapshot.program).map_err(|e| format!("IR Translation Failed! {}", e))?;
+        if snapshot.instruction_ptr > ir.instructions.len() {
+            return Err(format!(
+                "The instruction pointer {} is outside of the program",
+                snapshot.instruction_ptr
             ));
         }
+
+        self.frontend = frontend;
+        self.program = Some(Arc::new(snapshot.program));
+        self.source = snapshot.source;
+        self.set_ir(Some(ir));
+        self.memory = snapshot.memory;
+        self.registers = snapshot.registers;
+        self.state.tick = snapshot.tick;
+        self.state.instruction_ptr = snapshot.instruction_ptr;
+        self.state.instruction_budget = snapshot.instruction_budget;
+        self.state.cycles = snapshot.cycles;
+        self.state.instructions = snapshot.instructions;
+        self.state.stall = snapshot.stall;
+        self.heap_latency = snapshot.heap_latency;
+        self.state.flags = snapshot.flags;
+        self.state.call_stack = snapshot.call_stack;
+        self.history.clear();
+        self.stdout.replay(&snapshot.stdout);
+        self.start_trace();
+        self.debugger.reset_hits();
+        self.resolve_debug_points();
+        self.halt(EngineRunningState::Paused, StopReason::Restored);
+        Ok(())
+    }
+
+    /// Loads the program in the payload paused before its first instruction when stopped.
+    /// Returns whether a program was already running or paused.
+    fn ensure_loaded(&mut self, payload: Payload) -> bool {
+        if self.state.running_state != EngineRunningState::Stopped {
+            return true;
+        }
+
+        if self.load(&payload.extract()) {
+            self.halt(EngineRunningState::Paused, StopReason::Step);
+        }
+        false
     }
 
     pub fn run_client_commands(&mut self, client_command: ClientCommands) {
         match client_command.command_type {
             ClientCommandType::Start => {
                 if self.state.running_state == EngineRunningState::Stopped {
-                    self.state.tick = 0;
-                    self.state.instruction_ptr = 0;
-                    self.state.running_state = EngineRunningState::Running;
-                    let program = parse(client_command.payload.unwrap());
+                    if !self.load(&client_command.payload.extract()) {
+                        return;
+                    }
 
-                    self.current_label = program.get_start_label().ok();
-                    self.program = Some(program);
+                    self.resume();
                     let _send_res = self
                         .engine_data_sender
                         .send(self.get_current_state(Some(ClientCommandType::Start)));
                 } else {
-                    self.state.running_state = EngineRunningState::Running;
+                    self.state.run_until = None;
+                    self.resume();
                 }
             }
 
             ClientCommandType::Pause => {
-                self.state.running_state = EngineRunningState::Paused;
+                self.halt(EngineRunningState::Paused, StopReason::User);
                 let _ = self
                     .engine_data_sender
                     .send(self.get_current_state(Some(ClientCommandType::Pause)));
             }
 
             ClientCommandType::Stop => {
-                self.state.running_state = EngineRunningState::Stopped;
+                self.halt(EngineRunningState::Stopped, StopReason::User);
                 let _ = self
                     .engine_data_sender
                     .send(self.get_current_state(Some(ClientCommandType::Stop)));
             }
 
-            ClientCommandType::ParseFile => {
+            ClientCommandType::StepInstruction => {
+                if self.ensure_loaded(client_command.payload) {
+                    self.state.running_state = EngineRunningState::Paused;
+                    if self.execute() {
+                        self.halt(EngineRunningState::Paused, StopReason::Step);
+                    }
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::StepInstruction)));
+            }
+
+            ClientCommandType::StepOver => {
+                if self.ensure_loaded(client_command.payload) {
+                    let is_call = self.ir.as_ref().is_some_and(|ir| {
+                        ir.instructions
+                            .get(self.state.instruction_ptr)
+                            .is_some_and(|instruction| {
+                                instruction.ty == InstructionType::CALL
+                                    && matches!(
+                                        instruction.operands.first(),
+                                        Some(Operand::Label { .. })
+                                    )
+                            })
+                    });
+
+                    self.state.running_state = EngineRunningState::Paused;
+                    let depth = self.state.call_stack.len();
+                    if self.execute() {
+                        if is_call && self.state.call_stack.len() > depth {
+                            self.resume();
+                            self.state.run_until = Some(RunUntil::CallDepth(depth));
+                        } else {
+                            self.halt(EngineRunningState::Paused, StopReason::Step);
+                        }
+                    }
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::StepOver)));
+            }
+
+            ClientCommandType::StepOut => {
+                if self.ensure_loaded(client_command.payload) {
+                    match self.state.call_stack.len() {
+                        0 => self.send_stdlog(
+                            StdLogLevel::WARN,
+                            "Not inside of a CALL, there is nothing to step out of",
+                        ),
+                        depth => {
+                            self.resume();
+                            self.state.run_until = Some(RunUntil::CallDepth(depth - 1));
+                        }
+                    }
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::StepOut)));
+            }
+
+            ClientCommandType::RunToCursor => {
+                let payload = client_command.payload.extract();
+                let target = payload.trim().parse::<usize>().ok().and_then(|line| {
+                    self.ir
+                        .as_ref()
+                        .and_then(|ir| ir.address_for_line(line))
+                        .map(|address| (line, address))
+                });
+
+                match target {
+                    _ if self.state.running_state == EngineRunningState::Stopped => self
+                        .send_stdlog(
+                            StdLogLevel::WARN,
+                            "Start or step into the program before running to the cursor",
+                        ),
+                    Some((line, address)) => {
+                        self.send_stdlog(
+                            StdLogLevel::INFO,
+                            format!("Running to line {} ({:04})", line, address).as_str(),
+                        );
+                        self.resume();
+                        self.state.run_until = Some(RunUntil::Address(address));
+                    }
+                    None => self.send_stdlog(
+                        StdLogLevel::WARN,
+                        format!("No code at or after line {}", payload.trim()).as_str(),
+                    ),
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::RunToCursor)));
+            }
+
+            ClientCommandType::StepBack => {
+                if self.step_back() {
+                    self.pause_in_history(StopReason::Step);
+                } else {
+                    self.send_stdlog(StdLogLevel::WARN, "There is no instruction to step back to");
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::StepBack)));
+            }
+
+            ClientCommandType::ReverseContinue => {
+                let mut reason = None;
+                let mut moved = false;
+
+                while self.step_back() {
+                    moved = true;
+                    // only changes can be seen in reverse, writes are recorded while running
+                    if let Some(hit) = self
+                        .debugger
+                        .check_watchpoints(&self.registers, &self.memory)
+                    {
+                        reason = Some(StopReason::Watchpoint {
+                            target: hit.target,
+                            old: hit.new,
+                            new: hit.old,
+                        });
+                        break;
+                    }
+
+                    reason = self.check_breakpoints(self.state.instruction_ptr);
+                    if reason.is_some() {
+                        break;
+                    }
+                }
+
+                if moved {
+                    let reason = reason.unwrap_or(StopReason::HistoryStart);
+                    self.send_stdlog(StdLogLevel::INFO, reason.to_string().as_str());
+                    self.pause_in_history(reason);
+                } else {
+                    self.send_stdlog(StdLogLevel::WARN, "There is no instruction to step back to");
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::ReverseContinue)));
+            }
+
+            ClientCommandType::SeekHistory => {
                 let payload = client_command.payload.extract();
-                let program = parse(payload);
-                self.program = Some(program);
+                match payload.trim().parse::<usize>() {
+                    Ok(_) if self.ir.is_none() => {}
+                    Ok(target) => {
+                        while self.history.position() > target && self.step_back() {}
+                        while self.history.position() < target
+                            && self.history.redo(
+                                &mut self.registers,
+                                &mut self.memory,
+                                &mut self.state,
+                            )
+                        {}
+                        self.pause_in_history(StopReason::History);
+                    }
+                    Err(_) => self.send_stdlog(
+                        StdLogLevel::ERROR,
+                        format!("Invalid history position {}", payload).as_str(),
+                    ),
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::SeekHistory)));
+            }
+
+            ClientCommandType::SetBreakpoints => {
+                match serde_json::from_str(&client_command.payload.extract()) {
+                    Ok(specs) => {
+                        for error in self.debugger.set_breakpoints(specs) {
+                            self.send_stdlog(StdLogLevel::WARN, error.as_str());
+                        }
+                        self.resolve_debug_points();
+                    }
+                    Err(e) => self.send_stdlog(
+                        StdLogLevel::ERROR,
+                        format!("Invalid breakpoints! {}", e).as_str(),
+                    ),
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::SetBreakpoints)));
+            }
+
+            ClientCommandType::SetWatchpoints => {
+                match serde_json::from_str(&client_command.payload.extract()) {
+                    Ok(specs) => {
+                        self.debugger.set_watchpoints(specs);
+                        self.resolve_debug_points();
+                    }
+                    Err(e) => self.send_stdlog(
+                        StdLogLevel::ERROR,
+                        format!("Invalid watchpoints! {}", e).as_str(),
+                    ),
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::SetWatchpoints)));
+            }
+
+            ClientCommandType::ParseFile => {
+                let Some(program) = self.compile(&client_command.payload.extract()) else {
+                    return;
+                };
+                self.program = Some(Arc::new(program));
+                self.set_ir(None);
                 let _ = self
                     .engine_data_sender
                     .send(self.get_current_state(Some(ClientCommandType::ParseFile)));
             }
 
             ClientCommandType::ParseWithoutUpdate => {
-                let payload = client_command.payload.extract();
-                let program = parse(payload);
+                let Some(program) = self.compile(&client_command.payload.extract()) else {
+                    return;
+                };
                 let mut current_state =
                     self.get_current_state(Some(ClientCommandType::ParseWithoutUpdate));
 
-                current_state.program = Some(program);
+                current_state.program = Some(Arc::new(program));
+                let _ = self.engine_data_sender.send(current_state);
+            }
+
+            ClientCommandType::TranslateToIR => {
+                let Some(program) = self.compile(&client_command.payload.extract()) else {
+                    return;
+                };
+                match translate(&program) {
+                    Ok(ir) => {
+                        self.program = Some(Arc::new(program));
+                        self.set_ir(Some(ir));
+                    }
+                    Err(e) => self.send_stdlog(
+                        StdLogLevel::ERROR,
+                        format!("IR Translation Failed! {}", e).as_str(),
+                    ),
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::TranslateToIR)));
+            }
+
+            ClientCommandType::TranslateToIRWithoutUpdate => {
+                let Some(program) = self.compile(&client_command.payload.extract()) else {
+                    return;
+                };
+                let mut current_state =
+                    self.get_current_state(Some(ClientCommandType::TranslateToIRWithoutUpdate));
+
+                match translate(&program) {
+                    Ok(ir) => {
+                        current_state.program = Some(Arc::new(program));
+                        current_state.ir_repsersentation = Arc::new(ir.to_string());
+                    }
+                    Err(e) => self.send_stdlog(
+                        StdLogLevel::ERROR,
+                        format!("IR Translation Failed! {}", e).as_str(),
+                    ),
+                }
+
+                let _ = self.engine_data_sender.send(current_state);
+            }
+
+            ClientCommandType::SelectFrontend => {
+                let path = client_command.payload.extract();
+                match self.frontends.for_path(&path) {
+                    Some(frontend) => {
+                        self.frontend = frontend;
+                        self.send_stdlog(
+                            StdLogLevel::INFO,
+                            format!("Using the {} frontend for {}", self.frontend.name(), path)
+                                .as_str(),
+                        );
+                    }
+                    None => self.send_stdlog(
+                        StdLogLevel::WARN,
+                        format!(
+                            "No frontend found for {}, keeping {}. Known extensions: {}",
+                            path,
+                            self.frontend.name(),
+                            self.frontends
+                                .extensions()
+                                .collect::<Vec<&str>>()
+                                .join(", ")
+                        )
+                        .as_str(),
+                    ),
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::SelectFrontend)));
+            }
+
+            ClientCommandType::LoadBinary => {
+                let path = client_command.payload.extract();
+                let program = std::fs::read(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|bytes| disassemble(&bytes).map_err(|e| e.to_string()));
+
+                match program {
+                    Ok(program) => {
+                        self.program = Some(Arc::new(program));
+                        self.set_ir(None);
+                        // the UI edits the disassembly, which is written in the assembly syntax
+                        self.frontend = Arc::new(AsmFrontend);
+                        self.send_stdlog(
+                            StdLogLevel::INFO,
+                            format!("Loaded binary {}", path).as_str(),
+                        );
+                    }
+                    Err(e) => self.send_stdlog(
+                        StdLogLevel::ERROR,
+                        format!("Failed to load binary {}! {}", path, e).as_str(),
+                    ),
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::LoadBinary)));
+            }
+
+            ClientCommandType::SaveBinary => {
+                let path = client_command.payload.extract();
+                match &self.program {
+                    None => self.send_stdlog(
+                        StdLogLevel::WARN,
+                        "Nothing to assemble, parse a program first",
+                    ),
+                    Some(program) => match std::fs::write(&path, assemble(program)) {
+                        Ok(()) => self.send_stdlog(
+                            StdLogLevel::INFO,
+                            format!("Wrote binary {}", path).as_str(),
+                        ),
+                        Err(e) => self.send_stdlog(
+                            StdLogLevel::ERROR,
+                            format!("Failed to write binary {}! {}", path, e).as_str(),
+                        ),
+                    },
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::SaveBinary)));
+            }
+
+            ClientCommandType::SaveSnapshot => {
+                let path = client_command.payload.extract();
+                match self.snapshot() {
+                    None => self.send_stdlog(
+                        StdLogLevel::WARN,
+                        "Nothing to snapshot, start or step into a program first",
+                    ),
+                    Some(snapshot) => match snapshot
+                        .to_json()
+                        .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()))
+                    {
+                        Ok(()) => self.send_stdlog(
+                            StdLogLevel::INFO,
+                            format!("Wrote snapshot {} at tick {}", path, snapshot.tick).as_str(),
+                        ),
+                        Err(e) => self.send_stdlog(
+                            StdLogLevel::ERROR,
+                            format!("Failed to write snapshot {}! {}", path, e).as_str(),
+                        ),
+                    },
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::SaveSnapshot)));
+            }
+
+            ClientCommandType::LoadSnapshot => {
+                let path = client_command.payload.extract();
+                let restored = std::fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| Snapshot::from_json(&json))
+                    .and_then(|snapshot| self.restore(snapshot));
+
+                if let Err(e) = restored {
+                    self.send_stdlog(
+                        StdLogLevel::ERROR,
+                        format!("Failed to restore snapshot {}! {}", path, e).as_str(),
+                    );
+                    return;
+                }
+
+                self.send_stdlog(
+                    StdLogLevel::INFO,
+                    format!(
+                        "Restored snapshot {}, paused at {:04}",
+                        path, self.state.instruction_ptr
+                    )
+                    .as_str(),
+                );
+                let mut current_state =
+                    self.get_current_state(Some(ClientCommandType::LoadSnapshot));
+                current_state.source = self.source.clone();
                 let _ = self.engine_data_sender.send(current_state);
             }
 
-            ClientCommandType::TranslateToIR => todo!(),
-            ClientCommandType::TranslateToIRWithoutUpdate => todo!(),
+            ClientCommandType::UpdateConfig => {
+                let options = toml::from_str::<RootConfig>(&client_command.payload.extract())
+                    .map_err(|e| e.to_string())
+                    .and_then(|options| {
+                        options.validate().map_err(|errors| errors.join(", "))?;
+                        Ok(options)
+                    });
+
+                match options {
+                    Ok(options) => self.update_config(options),
+                    Err(e) => self.send_stdlog(
+                        StdLogLevel::ERROR,
+                        format!("Config update rejected! {}", e).as_str(),
+                    ),
+                }
+
+                let _ = self
+                    .engine_data_sender
+                    .send(self.get_current_state(Some(ClientCommandType::UpdateConfig)));
+            }
+        }
+    }
+}
+
+/// Drives an engine without its thread, for the tests of the engine and the runner
+#[cfg(test)]
+pub(super) mod test_support {
+    use super::*;
+
+    /// Ticks run before a program that is still running is considered stuck
+    const MAX_TICKS: usize = 100_000;
+
+    pub fn engine(options: RootConfig) -> Engine {
+        Engine::new(options).0
+    }
+
+    pub fn command(engine: &mut Engine, command_type: ClientCommandType, payload: &str) {
+        engine.run_client_commands(ClientCommands {
+            command_type,
+            payload: Some(payload.to_string()),
+        });
+    }
+
+    /// Compiles `source` with the frontend for `path` and pauses before its first instruction
+    pub fn load(path: &str, source: &str) -> Engine {
+        let mut engine = engine(RootConfig::default());
+        command(&mut engine, ClientCommandType::SelectFrontend, path);
+        command(&mut engine, ClientCommandType::StepInstruction, source);
+        assert!(engine.ir.is_some(), "{} did not load", path);
+        engine
+    }
+
+    /// Runs like the engine thread does until the program pauses or stops
+    pub fn run(engine: &mut Engine) {
+        for _ in 0..MAX_TICKS {
+            if engine.state.running_state != EngineRunningState::Running {
+                return;
+            }
+
+            match engine.state.run_until {
+                Some(condition) => engine.run_until(condition),
+                None => engine.run_tick(),
+            }
+        }
+        panic!("the program still runs after {} ticks", MAX_TICKS);
+    }
+
+    pub fn printed(engine: &Engine) -> Vec<String> {
+        engine.stdout.printed()
+    }
+
+    /// Starts `source` and returns what it printed once it stopped
+    pub fn run_program(path: &str, source: &str) -> Vec<String> {
+        let mut engine = engine(RootConfig::default());
+        command(&mut engine, ClientCommandType::SelectFrontend, path);
+        command(&mut engine, ClientCommandType::Start, source);
+        run(&mut engine);
+        assert_eq!(
+            engine.state.running_state,
+            EngineRunningState::Stopped,
+            "{:?}",
+            engine.state.stop_reason
+        );
+        printed(&engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use irv::BINARY_EXTENSION;
+
+    use super::super::SNAPSHOT_EXTENSION;
+    use super::test_support::*;
+    use super::*;
+    use crate::config::Rate;
+
+    /// `f` calls `g`, each line is commented with its address
+    const NESTED_CALLS: &str = "\
+.section .program:
+@start:
+    CALL @f      // 0
+    LOAD 1 rbx   // 1
+    EXIT 0       // 2
+
+@f:
+    LOAD 5 rax   // 3
+    CALL @g      // 4
+    RET          // 5
+
+@g:
+    // bump rax
+    INC rax      // 6
+    RET          // 7
+";
+
+    fn start(options: RootConfig, source: &str) -> Engine {
+        let mut engine = engine(options);
+        command(&mut engine, ClientCommandType::SelectFrontend, "main.irv");
+        command(&mut engine, ClientCommandType::Start, source);
+        engine
+    }
+
+    /// Ticks each instruction of `source` waited before it ran, in the order they ran
+    fn waits(options: RootConfig, source: &str) -> Vec<usize> {
+        wait_until_stopped(&mut start(options, source))
+    }
+
+    fn wait_until_stopped(engine: &mut Engine) -> Vec<usize> {
+        let (mut waits, mut waited) = (Vec::new(), 0);
+        while engine.state.running_state == EngineRunningState::Running {
+            assert!(engine.state.tick < 1000, "the program still runs");
+
+            let executed = engine.state.instructions;
+            engine.run_tick();
+            if engine.state.instructions == executed {
+                waited += 1;
+            } else {
+                waits.push(waited);
+                waited = 0;
+            }
+        }
+        waits
+    }
+
+    const HEAP_ACCESSES: &str = "\
+.section .data:
+    qword q 7
+
+.section .program:
+@start:
+    LOAD 1 rax
+    LOAD q rbx
+    ADD q rax
+    LOAD 2 rcx
+    EXIT 0
+";
+
+    #[test]
+    fn heap_accesses_wait_the_configured_ticks() {
+        let mut options = RootConfig::default();
+        options.engine.ipt = Rate::new(1, 1);
+        assert_eq!(waits(options.clone(), HEAP_ACCESSES), [0, 0, 0, 0, 0]);
+
+        let simulation = &mut options.engine.heap_access_simulation;
+        simulation.enabled = true;
+        simulation.minimum_delay = 3;
+        assert_eq!(waits(options.clone(), HEAP_ACCESSES), [0, 3, 3, 0, 0]);
+
+        options.engine.heap_access_simulation.minimum_delay = 1;
+        assert_eq!(waits(options, HEAP_ACCESSES), [0, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn heap_jitter_is_seeded() {
+        let mut options = RootConfig::default();
+        options.engine.ipt = Rate::new(1, 1);
+        let simulation = &mut options.engine.heap_access_simulation;
+        simulation.enabled = true;
+        simulation.minimum_delay = 2;
+        simulation.jitter = 5;
+        simulation.seed = 42;
+
+        // 2 ticks plus 0 to 5 ticks of jitter, the same on every run
+        assert_eq!(waits(options.clone(), HEAP_ACCESSES), [0, 6, 3, 0, 0]);
+        assert_eq!(waits(options.clone(), HEAP_ACCESSES), [0, 6, 3, 0, 0]);
+
+        options.engine.heap_access_simulation.seed = 7;
+        assert_ne!(waits(options, HEAP_ACCESSES), [0, 6, 3, 0, 0]);
+    }
+
+    #[test]
+    fn snapshots_keep_the_stall_and_the_jitter() {
+        let mut options = RootConfig::default();
+        options.engine.ipt = Rate::new(1, 1);
+        let simulation = &mut options.engine.heap_access_simulation;
+        simulation.enabled = true;
+        simulation.minimum_delay = 2;
+        simulation.jitter = 5;
+        simulation.seed = 42;
+
+        // halfway through the 6 ticks the first access waits
+        let mut running = start(options.clone(), HEAP_ACCESSES);
+        for _ in 0..4 {
+            running.run_tick();
         }
+        let stall = running.state.stall.expect("the access should be waiting");
+        assert_eq!((stall.address, stall.remaining, stall.ticks), (1, 3, 6));
+
+        let json = running.snapshot().unwrap().to_json().unwrap();
+        let rest = wait_until_stopped(&mut running);
+
+        let mut restored = engine(options);
+        restored
+            .restore(Snapshot::from_json(&json).unwrap())
+            .unwrap();
+        assert_eq!(restored.state.stall, Some(stall));
+        command(&mut restored, ClientCommandType::Start, "");
+        assert_eq!(wait_until_stopped(&mut restored), rest);
+        assert_eq!(rest, [3, 3, 0, 0]);
+    }
+
+    #[test]
+    fn programs_start_at_start_or_underscore_start() {
+        let entry = |labels: [&str; 2]| {
+            let source = format!(
+                ".section .program:\n@{}:\n    LOAD 1 rax\n@{}:\n    EXIT 0\n",
+                labels[0], labels[1]
+            );
+            let engine = start(RootConfig::default(), &source);
+            engine.ir.is_some().then_some(engine.state.instruction_ptr)
+        };
+
+        assert_eq!(entry(["restart", "start"]), Some(1));
+        assert_eq!(entry(["restart", "_start"]), Some(1));
+        assert_eq!(entry(["start", "_start"]), Some(0));
+        assert_eq!(entry(["_start", "start"]), Some(1));
+        assert_eq!(entry(["restart", "start_up"]), None);
+    }
+
+    #[test]
+    fn saving_responds_on_every_path() {
+        let (mut engine, _commands, states, logs) = Engine::new(RootConfig::default());
+        let dir = std::env::temp_dir().join(format!("cpuv-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let save = |engine: &mut Engine, file: &str| {
+            for (command_type, extension) in [
+                (ClientCommandType::SaveBinary, BINARY_EXTENSION),
+                (ClientCommandType::SaveSnapshot, SNAPSHOT_EXTENSION),
+            ] {
+                let path = dir.join(file).with_extension(extension);
+                command(engine, command_type, &path.display().to_string());
+            }
+        };
+
+        // nothing loaded, a directory that does not exist, then written
+        save(&mut engine, "program");
+        command(&mut engine, ClientCommandType::SelectFrontend, "main.irv");
+        command(
+            &mut engine,
+            ClientCommandType::StepInstruction,
+            NESTED_CALLS,
+        );
+        save(&mut engine, "missing/program");
+        save(&mut engine, "program");
+        let written = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let responses = states
+            .drain()
+            .into_iter()
+            .filter_map(|state| state.responding_to)
+            .filter(|command| {
+                matches!(
+                    command,
+                    ClientCommandType::SaveBinary | ClientCommandType::SaveSnapshot
+                )
+            })
+            .count();
+        assert_eq!(responses, 6);
+        assert_eq!(written, 2);
+
+        let levels = logs
+            .try_iter()
+            .filter(|log| log.message.contains("program"))
+            .map(|log| log.log_level)
+            .collect::<Vec<StdLogLevel>>();
+        assert_eq!(
+            levels,
+            [
+                StdLogLevel::WARN,
+                StdLogLevel::WARN,
+                StdLogLevel::ERROR,
+                StdLogLevel::ERROR,
+                StdLogLevel::INFO,
+                StdLogLevel::INFO
+            ]
+        );
+    }
+
+    fn paused_at(engine: &Engine, address: usize, depth: usize) {
+        assert_eq!(engine.state.running_state, EngineRunningState::Paused);
+        assert_eq!(
+            (engine.state.instruction_ptr, engine.state.call_stack.len()),
+            (address, depth),
+            "{:?}",
+            engine.state.stop_reason
+        );
+    }
+
+    #[test]
+    fn step_over_runs_calls_to_their_return() {
+        let mut engine = load("main.irv", NESTED_CALLS);
+        paused_at(&engine, 0, 0);
+
+        command(&mut engine, ClientCommandType::StepOver, "");
+        run(&mut engine);
+        paused_at(&engine, 1, 0);
+        assert_eq!(engine.state.stop_reason, Some(StopReason::Reached));
+        assert_eq!(engine.registers.get("rax"), Some(6));
+
+        // anything but a CALL is a single step
+        command(&mut engine, ClientCommandType::StepOver, "");
+        paused_at(&engine, 2, 0);
+        assert_eq!(engine.state.stop_reason, Some(StopReason::Step));
+    }
+
+    #[test]
+    fn step_out_runs_until_the_label_returns() {
+        let mut engine = load("main.irv", NESTED_CALLS);
+        command(&mut engine, ClientCommandType::StepInstruction, "");
+        command(&mut engine, ClientCommandType::StepInstruction, "");
+        paused_at(&engine, 4, 1);
+
+        command(&mut engine, ClientCommandType::StepOut, "");
+        run(&mut engine);
+        paused_at(&engine, 1, 0);
+        assert_eq!(engine.state.stop_reason, Some(StopReason::Reached));
+        assert_eq!(engine.registers.get("rax"), Some(6));
+
+        // there is nothing to step out of at the top
+        command(&mut engine, ClientCommandType::StepOut, "");
+        paused_at(&engine, 1, 0);
+    }
+
+    #[test]
+    fn run_to_cursor_stops_before_the_line() {
+        let mut engine = load("main.irv", NESTED_CALLS);
+        command(&mut engine, ClientCommandType::RunToCursor, "14");
+        run(&mut engine);
+        paused_at(&engine, 6, 2);
+        assert_eq!(engine.state.stop_reason, Some(StopReason::Reached));
+        assert_eq!(engine.registers.get("rax"), Some(5));
+
+        // the comment above INC runs to the INC as well
+        let mut engine = load("main.irv", NESTED_CALLS);
+        command(&mut engine, ClientCommandType::RunToCursor, "13");
+        run(&mut engine);
+        paused_at(&engine, 6, 2);
+
+        // past the last line there is nothing to run to, the engine stays where it is
+        command(&mut engine, ClientCommandType::RunToCursor, "40");
+        paused_at(&engine, 6, 2);
+        assert_eq!(engine.state.run_until, None);
+    }
+
+    #[test]
+    fn tiny_programs_compute_with_negative_values() {
+        let printed = run_program(
+            "main.tiny",
+            "var a = -5;\n\
+             print(a);\n\
+             if (a < 0) { print(\"negative\"); }\n\
+             print(-a * 3);\n\
+             print(a / 2);\n\
+             while (a < -2) { a = a + 1; }\n\
+             print(a);",
+        );
+        assert_eq!(printed, ["-5", "negative", "15", "-2", "-2"]);
+    }
+
+    #[test]
+    fn tiny_programs_nest_calls() {
+        let printed = run_program(
+            "main.tiny",
+            "fn sub(a, b) { return a - b; }\n\
+             fn twice(x) { return sub(x, -x); }\n\
+             print(sub(10, sub(3, 1)));\n\
+             print(twice(4) + sub(1, 2));",
+        );
+        assert_eq!(printed, ["8", "7"]);
     }
 }
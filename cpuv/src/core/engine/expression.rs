@@ -0,0 +1,406 @@
+//! Conditions of breakpoints, like `rax > 10 && zf`.
+//!
+//! Values are integers, comparisons and logical operators give 1 or 0 and anything other than 0
+//! counts as true. Names can be registers, variables of the data section, the flags `zf` and `nf`,
+//! `ip`, `tick`, `cycles` and `depth` (the call depth). `mem[address]` reads one byte of the heap.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use irv::{parse_immediate, Operand};
+
+use super::runner::peek;
+use super::Engine;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(i128),
+    /// A register, variable or one of the engine values, resolved when evaluated
+    Name(String),
+    /// The byte of the heap at an address
+    Memory(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Negate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    /// Binding strength, operators with a higher one are applied first
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Equal
+            | BinaryOp::NotEqual
+            | BinaryOp::Less
+            | BinaryOp::LessEqual
+            | BinaryOp::Greater
+            | BinaryOp::GreaterEqual => 3,
+            BinaryOp::Add | BinaryOp::Sub => 4,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i128),
+    Name(String),
+    Op(BinaryOp),
+    Not,
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.expression(0)?;
+
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {:?} after the condition", token)),
+        }
+    }
+
+    pub fn eval(&self, engine: &Engine) -> Result<i128, String> {
+        Ok(match self {
+            Expr::Number(value) => *value,
+            Expr::Name(name) => lookup(engine, name)?,
+            Expr::Memory(address) => {
+                let address = address.eval(engine)?;
+                usize::try_from(address)
+                    .ok()
+                    .and_then(|address| engine.memory.get(address))
+                    .map(|byte| *byte as i128)
+                    .ok_or_else(|| format!("mem[{}] is outside of the heap", address))?
+            }
+            Expr::Unary(UnaryOp::Not, value) => (value.eval(engine)? == 0) as i128,
+            Expr::Unary(UnaryOp::Negate, value) => value.eval(engine)?.wrapping_neg(),
+            // both sides of && and || are only evaluated when needed, like in Rust
+            Expr::Binary(BinaryOp::And, left, right) => {
+                (left.eval(engine)? != 0 && right.eval(engine)? != 0) as i128
+            }
+            Expr::Binary(BinaryOp::Or, left, right) => {
+                (left.eval(engine)? != 0 || right.eval(engine)? != 0) as i128
+            }
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(engine)?, right.eval(engine)?);
+                match op {
+                    BinaryOp::Equal => (left == right) as i128,
+                    BinaryOp::NotEqual => (left != right) as i128,
+                    BinaryOp::Less => (left < right) as i128,
+                    BinaryOp::LessEqual => (left <= right) as i128,
+                    BinaryOp::Greater => (left > right) as i128,
+                    BinaryOp::GreaterEqual => (left >= right) as i128,
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                    BinaryOp::Mul => left.wrapping_mul(right),
+                    BinaryOp::Div | BinaryOp::Rem if right == 0 => {
+                        return Err("Division by zero".to_string())
+                    }
+                    BinaryOp::Div => left.wrapping_div(right),
+                    BinaryOp::Rem => left.wrapping_rem(right),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            }
+        })
+    }
+}
+
+fn lookup(engine: &Engine, name: &str) -> Result<i128, String> {
+    match name {
+        "zf" => return Ok(engine.state.flags.zero as i128),
+        "nf" => return Ok(engine.state.flags.negative as i128),
+        "ip" => return Ok(engine.state.instruction_ptr as i128),
+        "tick" => return Ok(engine.state.tick as i128),
+        "cycles" => return Ok(engine.state.cycles as i128),
+        "depth" => return Ok(engine.state.call_stack.len() as i128),
+        _ => {}
+    }
+
+    if let Some(value) = engine.registers.get(name) {
+        return Ok(value);
+    }
+
+    let slot = engine
+        .ir
+        .as_ref()
+        .and_then(|ir| ir.data.iter().find(|slot| slot.name == name))
+        .ok_or_else(|| format!("Unknown name {}", name))?;
+    let variable = Operand::Variable {
+        name: slot.name.clone(),
+        address: slot.address,
+        ty: slot.ty.clone(),
+    };
+    peek(engine, &variable, engine.state.instruction_ptr).map_err(|e| e.to_string())
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            tokens.push(Token::Number(number(&mut chars)?));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                name.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Name(name));
+            continue;
+        }
+
+        chars.next();
+        let mut followed_by = |next: char| chars.next_if_eq(&next).is_some();
+        tokens.push(match c {
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '[' => Token::OpenBracket,
+            ']' => Token::CloseBracket,
+            '+' => Token::Op(BinaryOp::Add),
+            '-' => Token::Op(BinaryOp::Sub),
+            '*' => Token::Op(BinaryOp::Mul),
+            '/' => Token::Op(BinaryOp::Div),
+            '%' => Token::Op(BinaryOp::Rem),
+            '|' if followed_by('|') => Token::Op(BinaryOp::Or),
+            '&' if followed_by('&') => Token::Op(BinaryOp::And),
+            '=' if followed_by('=') => Token::Op(BinaryOp::Equal),
+            '!' if followed_by('=') => Token::Op(BinaryOp::NotEqual),
+            '!' => Token::Not,
+            '<' if followed_by('=') => Token::Op(BinaryOp::LessEqual),
+            '<' => Token::Op(BinaryOp::Less),
+            '>' if followed_by('=') => Token::Op(BinaryOp::GreaterEqual),
+            '>' => Token::Op(BinaryOp::Greater),
+            _ => return Err(format!("Unexpected character '{}'", c)),
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Decimal or `0x` hexadecimal number, `_` can be used as a separator
+fn number(chars: &mut Peekable<Chars>) -> Result<i128, String> {
+    let mut digits = String::new();
+    while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+        digits.push(c);
+        chars.next();
+    }
+
+    parse_immediate(&digits).ok_or_else(|| format!("Invalid number {}", digits))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("Expected {:?}, found {:?}", expected, token)),
+            None => Err(format!("Expected {:?} at the end", expected)),
+        }
+    }
+
+    /// Precedence climbing, only operators binding stronger than `min_precedence` are consumed
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos).cloned() {
+            if op.precedence() <= min_precedence {
+                break;
+            }
+
+            self.pos += 1;
+            let right = self.expression(op.precedence())?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?))),
+            Some(Token::Op(BinaryOp::Sub)) => {
+                Ok(Expr::Unary(UnaryOp::Negate, Box::new(self.unary()?)))
+            }
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Name(name)) if name == "mem" => {
+                self.expect(Token::OpenBracket)?;
+                let address = self.expression(0)?;
+                self.expect(Token::CloseBracket)?;
+                Ok(Expr::Memory(Box::new(address)))
+            }
+            Some(Token::Name(name)) => Ok(Expr::Name(name)),
+            Some(Token::Open) => {
+                let expr = self.expression(0)?;
+                self.expect(Token::Close)?;
+                Ok(expr)
+            }
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("The condition is incomplete".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::engine::test_support::load;
+    use super::*;
+
+    fn engine() -> Engine {
+        let mut engine = load(
+            "main.irv",
+            ".section .data:\n    byte small 200\n    sword negative -3\n    str8 name \"irv\"\n\n\
+             .section .program:\n@start:\n    EXIT 0\n",
+        );
+        engine.registers.set("rax", 5);
+        engine
+    }
+
+    fn eval(source: &str) -> Result<i128, String> {
+        Expr::parse(source)?.eval(&engine())
+    }
+
+    fn number(value: i128) -> Box<Expr> {
+        Box::new(Expr::Number(value))
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(
+            Expr::parse("1 + 2 * 3 - 4"),
+            Ok(Expr::Binary(
+                BinaryOp::Sub,
+                Box::new(Expr::Binary(
+                    BinaryOp::Add,
+                    number(1),
+                    Box::new(Expr::Binary(BinaryOp::Mul, number(2), number(3)))
+                )),
+                number(4)
+            ))
+        );
+
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("-2 * 3"), Ok(-6));
+        assert_eq!(eval("!0 + 1"), Ok(2));
+        assert_eq!(eval("1 || 0 && 0"), Ok(1));
+        assert_eq!(eval("rax > 4 && rax <= 5"), Ok(1));
+        assert_eq!(eval("0x10 % 5 == 1"), Ok(1));
+    }
+
+    #[test]
+    fn operators_of_equal_precedence_are_left_associative() {
+        assert_eq!(eval("10 - 4 - 3"), Ok(3));
+        assert_eq!(eval("64 / 4 / 2"), Ok(8));
+        assert_eq!(eval("2 * 3 % 4"), Ok(2));
+        assert_eq!(eval("3 > 2 == 1"), Ok(1));
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(eval("0 && 1 / 0"), Ok(0));
+        assert_eq!(eval("1 || missing"), Ok(1));
+        assert_eq!(eval("1 && 1 / 0"), Err("Division by zero".to_string()));
+        assert_eq!(
+            eval("0 || missing"),
+            Err("Unknown name missing".to_string())
+        );
+    }
+
+    #[test]
+    fn names_read_registers_variables_and_the_engine() {
+        assert_eq!(eval("rax"), Ok(5));
+        assert_eq!(eval("srbx"), Ok(0));
+        assert_eq!(eval("small"), Ok(200));
+        assert_eq!(eval("negative"), Ok(-3));
+        assert_eq!(eval("ip + depth + zf + nf + cycles"), Ok(0));
+
+        assert_eq!(eval("rzz"), Err("Unknown name rzz".to_string()));
+        assert_eq!(
+            eval("name"),
+            Err("0000: String variable name cannot be used as a number".to_string())
+        );
+    }
+
+    #[test]
+    fn memory_reads_single_bytes_of_the_heap() {
+        assert_eq!(eval("mem[0]"), Ok(200));
+        assert_eq!(eval("mem[1] + mem[1 + 1] * 256"), Ok(0xfffd));
+        assert_eq!(eval("mem[2047]"), Ok(0));
+
+        assert_eq!(
+            eval("mem[2048]"),
+            Err("mem[2048] is outside of the heap".to_string())
+        );
+        assert_eq!(
+            eval("mem[-1]"),
+            Err("mem[-1] is outside of the heap".to_string())
+        );
+        assert_eq!(
+            eval("mem[18446744073709551615]"),
+            Err("mem[18446744073709551615] is outside of the heap".to_string())
+        );
+    }
+
+    #[test]
+    fn malformed_conditions_are_errors() {
+        let error = |source: &str| Expr::parse(source).unwrap_err();
+
+        assert_eq!(error("1 2"), "Unexpected Number(2) after the condition");
+        assert_eq!(error("(1 + 2"), "Expected Close at the end");
+        assert_eq!(error("1 + 2)"), "Unexpected Close after the condition");
+        assert_eq!(error("mem[1"), "Expected CloseBracket at the end");
+        assert_eq!(error("mem(1)"), "Expected OpenBracket, found Open");
+        assert_eq!(error("]"), "Unexpected CloseBracket");
+        assert_eq!(error("1 +"), "The condition is incomplete");
+        assert_eq!(error(""), "The condition is incomplete");
+        assert_eq!(error("1 & 2"), "Unexpected character '&'");
+        assert_eq!(error("12ab"), "Invalid number 12ab");
+    }
+}
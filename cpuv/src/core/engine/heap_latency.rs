@@ -0,0 +1,68 @@
+//! Simulated latency of the heap, set by `engine.heap-access-simulation`.
+//!
+//! An instruction with a variable operand waits `minimum-delay` ticks, plus up to `jitter` more,
+//! before it runs. Only ticks wait, steps and runs to a stop condition don't.
+
+use serde::{Deserialize, Serialize};
+
+use irv::{IrInstruction, Operand};
+
+use crate::config::HeapAccessSimulationConfig;
+
+/// An instruction waiting for the heap
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stall {
+    /// Address of the waiting instruction
+    pub address: usize,
+    /// Ticks left to wait, the instruction runs on the next tick once this is 0
+    pub remaining: u32,
+    /// Ticks the instruction waits in total
+    pub ticks: u32,
+}
+
+pub fn touches_memory(instruction: &IrInstruction) -> bool {
+    instruction
+        .operands
+        .iter()
+        .any(|operand| matches!(operand, Operand::Variable { .. }))
+}
+
+/// Picks how long each access waits. The jitter comes from a xorshift generator, so a program
+/// waits the same ticks on every run with the same seed. Its state is kept in snapshots.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct HeapLatency {
+    state: u64,
+}
+
+impl HeapLatency {
+    /// Starts the jitter over, for a program that was just loaded
+    pub fn reset(&mut self, seed: u64) {
+        // xorshift never leaves 0, any other fixed value works
+        self.state = if seed == 0 {
+            0x9e37_79b9_7f4a_7c15
+        } else {
+            seed
+        };
+    }
+
+    pub fn delay(&mut self, config: &HeapAccessSimulationConfig) -> u32 {
+        if config.jitter == 0 {
+            return config.minimum_delay;
+        }
+
+        let jitter = self.next() % (u64::from(config.jitter) + 1);
+        config.minimum_delay.saturating_add(jitter as u32)
+    }
+
+    fn next(&mut self) -> u64 {
+        if self.state == 0 {
+            self.reset(0);
+        }
+
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
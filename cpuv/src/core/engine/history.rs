@@ -0,0 +1,388 @@
+use std::collections::VecDeque;
+
+use irv::Registers;
+
+use super::{EngineState, Flags};
+
+/// What a single instruction changed, with the values from before and after it ran so that it can
+/// be undone and redone
+#[derive(Debug, Clone, Default)]
+struct Delta {
+    instruction_ptr: (usize, usize),
+    flags: (Flags, Flags),
+    call: CallChange,
+    registers: Vec<RegisterChange>,
+    memory: Vec<MemoryChange>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum CallChange {
+    #[default]
+    None,
+    /// A CALL pushed this return address
+    Push(usize),
+    /// A RET popped this return address
+    Pop(usize),
+}
+
+#[derive(Debug, Clone)]
+struct RegisterChange {
+    name: String,
+    old: i128,
+    new: i128,
+}
+
+#[derive(Debug, Clone)]
+struct MemoryChange {
+    address: usize,
+    old: Vec<u8>,
+    new: Vec<u8>,
+}
+
+/// Ring buffer of the last executed instructions.
+///
+/// Deltas before `position` are applied to the current state, the ones after it were stepped back
+/// over and can be redone by seeking forward. Running an instruction drops those.
+///
+/// Output of the program, like printf, is not part of the history and is not taken back.
+#[derive(Debug, Default)]
+pub struct History {
+    deltas: VecDeque<Delta>,
+    position: usize,
+    capacity: usize,
+    /// The instruction that is running, pushed once it finished
+    recording: Option<Delta>,
+    /// Call depth before the recorded instruction ran
+    call_depth: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    /// Drops the oldest applied deltas if there are more than `capacity`, then the ones that were
+    /// stepped back over. Dropping one of those from the front would leave a gap in front of the
+    /// rest, which could not be redone.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.deltas.len() > capacity {
+            if self.position > 0 {
+                self.deltas.pop_front();
+                self.position -= 1;
+            } else {
+                self.deltas.pop_back();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.position = 0;
+        self.recording = None;
+    }
+
+    /// Number of recorded instructions, including the ones stepped back over
+    pub fn recorded(&self) -> usize {
+        self.deltas.len()
+    }
+
+    /// Number of recorded instructions that are applied to the current state
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Starts recording the instruction that is about to run
+    pub fn begin(&mut self, state: &EngineState) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.call_depth = state.call_stack.len();
+        self.recording = Some(Delta {
+            instruction_ptr: (state.instruction_ptr, state.instruction_ptr),
+            flags: (state.flags, state.flags),
+            call: match state.call_stack.last() {
+                Some(address) => CallChange::Pop(*address),
+                None => CallChange::None,
+            },
+            ..Default::default()
+        });
+    }
+
+    pub fn record_register(&mut self, name: &str, old: i128, new: i128) {
+        if let Some(delta) = &mut self.recording {
+            delta.registers.push(RegisterChange {
+                name: name.to_string(),
+                old,
+                new,
+            });
+        }
+    }
+
+    pub fn record_memory(&mut self, address: usize, old: &[u8], new: &[u8]) {
+        if let Some(delta) = &mut self.recording {
+            delta.memory.push(MemoryChange {
+                address,
+                old: old.to_vec(),
+                new: new.to_vec(),
+            });
+        }
+    }
+
+    /// Finishes recording the instruction that ran since [`History::begin`]
+    pub fn finish(&mut self, state: &EngineState) {
+        let Some(mut delta) = self.recording.take() else {
+            return;
+        };
+
+        delta.instruction_ptr.1 = state.instruction_ptr;
+        delta.flags.1 = state.flags;
+        // `begin` kept the top of the call stack in case it is popped
+        delta.call = match state.call_stack.len() {
+            depth if depth > self.call_depth => CallChange::Push(state.call_stack[depth - 1]),
+            depth if depth < self.call_depth => delta.call,
+            _ => CallChange::None,
+        };
+
+        self.deltas.truncate(self.position);
+        self.deltas.push_back(delta);
+        if self.deltas.len() > self.capacity {
+            self.deltas.pop_front();
+        }
+        self.position = self.deltas.len();
+    }
+
+    /// Takes back the last applied instruction. Returns false if there is none.
+    pub fn undo(
+        &mut self,
+        registers: &mut Registers,
+        memory: &mut [u8],
+        state: &mut EngineState,
+    ) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+
+        self.position -= 1;
+        let delta = &self.deltas[self.position];
+
+        for change in delta.registers.iter().rev() {
+            registers.set(&change.name, change.old);
+        }
+        for change in delta.memory.iter().rev() {
+            memory[change.address..change.address + change.old.len()].copy_from_slice(&change.old);
+        }
+        match delta.call {
+            CallChange::Push(_) => {
+                state.call_stack.pop();
+            }
+            CallChange::Pop(address) => state.call_stack.push(address),
+            CallChange::None => {}
+        }
+        state.instruction_ptr = delta.instruction_ptr.0;
+        state.flags = delta.flags.0;
+        true
+    }
+
+    /// Applies the next instruction that was stepped back over again. Returns false if there is
+    /// none.
+    pub fn redo(
+        &mut self,
+        registers: &mut Registers,
+        memory: &mut [u8],
+        state: &mut EngineState,
+    ) -> bool {
+        let Some(delta) = self.deltas.get(self.position) else {
+            return false;
+        };
+        self.position += 1;
+
+        for change in &delta.registers {
+            registers.set(&change.name, change.new);
+        }
+        for change in &delta.memory {
+            memory[change.address..change.address + change.new.len()].copy_from_slice(&change.new);
+        }
+        match delta.call {
+            CallChange::Push(address) => state.call_stack.push(address),
+            CallChange::Pop(_) => {
+                state.call_stack.pop();
+            }
+            CallChange::None => {}
+        }
+        state.instruction_ptr = delta.instruction_ptr.1;
+        state.flags = delta.flags.1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::engine::test_support::{command, engine, load};
+    use super::super::{ClientCommandType, Engine};
+    use crate::RootConfig;
+
+    const PROGRAM: &str = "\
+.section .data:
+    word w 1
+
+.section .program:
+@start:
+    LOAD 7 rax
+    CALL @f
+    CMP rax 8
+    EXIT 0
+
+@f:
+    ADD w 300
+    INC rax
+    RET
+";
+
+    #[derive(Debug, PartialEq)]
+    struct Machine {
+        registers: irv::Registers,
+        memory: Vec<u8>,
+        instruction_ptr: usize,
+        flags: super::Flags,
+        call_stack: Vec<usize>,
+    }
+
+    fn machine(engine: &Engine) -> Machine {
+        Machine {
+            registers: engine.registers.clone(),
+            memory: engine.memory.clone(),
+            instruction_ptr: engine.state.instruction_ptr,
+            flags: engine.state.flags,
+            call_stack: engine.state.call_stack.clone(),
+        }
+    }
+
+    fn step(engine: &mut Engine, count: usize) -> Vec<Machine> {
+        (0..count)
+            .map(|_| {
+                command(engine, ClientCommandType::StepInstruction, "");
+                machine(engine)
+            })
+            .collect()
+    }
+
+    fn back(engine: &mut Engine, count: usize) {
+        for _ in 0..count {
+            command(engine, ClientCommandType::StepBack, "");
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_restore_every_change() {
+        let mut engine = load("main.irv", PROGRAM);
+        let mut states = vec![machine(&engine)];
+        // LOAD, CALL, ADD, INC, RET, CMP
+        states.extend(step(&mut engine, 6));
+        assert_eq!(states[2].call_stack, [2]);
+        assert_eq!(states[3].memory[..2], [0x2d, 0x01]);
+        assert!(states[6].flags.zero);
+        assert_eq!(engine.history.recorded(), 6);
+
+        for position in (0..6).rev() {
+            back(&mut engine, 1);
+            assert_eq!(engine.history.position(), position);
+            assert_eq!(machine(&engine), states[position], "undone to {}", position);
+        }
+
+        for (position, state) in states.iter().enumerate().skip(1) {
+            command(
+                &mut engine,
+                ClientCommandType::SeekHistory,
+                &position.to_string(),
+            );
+            assert_eq!(machine(&engine), *state, "redone to {}", position);
+        }
+    }
+
+    #[test]
+    fn running_after_an_undo_drops_the_redo_tail() {
+        let mut engine = load("main.irv", PROGRAM);
+        step(&mut engine, 4);
+        back(&mut engine, 2);
+        assert_eq!(
+            (engine.history.position(), engine.history.recorded()),
+            (2, 4)
+        );
+
+        let states = step(&mut engine, 1);
+        assert_eq!(
+            (engine.history.position(), engine.history.recorded()),
+            (3, 3)
+        );
+
+        back(&mut engine, 1);
+        command(&mut engine, ClientCommandType::SeekHistory, "4");
+        assert_eq!(engine.history.position(), 3);
+        assert_eq!(machine(&engine), states[0]);
+    }
+
+    #[test]
+    fn capacity_keeps_the_latest_instructions() {
+        let mut engine = load("main.irv", PROGRAM);
+        engine.history.set_capacity(3);
+        let states = step(&mut engine, 5);
+        assert_eq!(
+            (engine.history.position(), engine.history.recorded()),
+            (3, 3)
+        );
+
+        // the evicted instructions cannot be stepped back over
+        back(&mut engine, 4);
+        assert_eq!(engine.history.position(), 0);
+        assert_eq!(machine(&engine), states[1]);
+
+        command(&mut engine, ClientCommandType::SeekHistory, "3");
+        assert_eq!(machine(&engine), states[4]);
+    }
+
+    #[test]
+    fn shrinking_the_capacity_keeps_the_position_consistent() {
+        let mut engine = load("main.irv", PROGRAM);
+        let states = step(&mut engine, 5);
+        back(&mut engine, 2);
+
+        // 3 applied and 2 undone: the oldest 2 applied ones go first
+        engine.history.set_capacity(3);
+        assert_eq!(
+            (engine.history.position(), engine.history.recorded()),
+            (1, 3)
+        );
+        command(&mut engine, ClientCommandType::SeekHistory, "3");
+        assert_eq!(machine(&engine), states[4]);
+
+        // with nothing applied left, the redo tail is dropped from the back
+        back(&mut engine, 3);
+        engine.history.set_capacity(1);
+        assert_eq!(
+            (engine.history.position(), engine.history.recorded()),
+            (0, 1)
+        );
+        command(&mut engine, ClientCommandType::SeekHistory, "1");
+        assert_eq!(machine(&engine), states[2]);
+    }
+
+    #[test]
+    fn capacity_zero_records_nothing() {
+        let mut options = RootConfig::default();
+        options.engine.history_size = 0;
+        let mut engine = engine(options);
+        command(&mut engine, ClientCommandType::SelectFrontend, "main.irv");
+        command(&mut engine, ClientCommandType::StepInstruction, PROGRAM);
+
+        let states = step(&mut engine, 3);
+        assert_eq!(engine.history.recorded(), 0);
+
+        back(&mut engine, 1);
+        assert_eq!(machine(&engine), states[2]);
+    }
+}
@@ -0,0 +1,265 @@
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use super::{StdLogLevel, StdLogMessage};
+use crate::config::{LogLevel, LogsConfig};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Sends system logs to the UI, and optionally to a rotating log file and stderr.
+pub struct Logger {
+    level: LogLevel,
+    mirror_stderr: bool,
+    file: Option<RefCell<LogFile>>,
+    sender: mpsc::Sender<StdLogMessage>,
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl Logger {
+    pub fn new(level: LogLevel, config: &LogsConfig, sender: mpsc::Sender<StdLogMessage>) -> Self {
+        let mut logger = Self {
+            level,
+            mirror_stderr: config.mirror_stderr,
+            file: None,
+            sender,
+        };
+        logger.reconfigure(level, config);
+
+        logger
+    }
+
+    /// Applies a new config, reopening the log file only if its path changed
+    pub fn reconfigure(&mut self, level: LogLevel, config: &LogsConfig) {
+        self.level = level;
+        self.mirror_stderr = config.mirror_stderr;
+
+        if !config.should_write {
+            self.file = None;
+            return;
+        }
+
+        if let Some(file) = &self.file {
+            let mut file = file.borrow_mut();
+            if file.path == Path::new(&config.path) {
+                file.max_size = config.max_size;
+                file.max_files = config.max_files;
+                return;
+            }
+        }
+
+        match LogFile::open(config) {
+            Ok(file) => self.file = Some(RefCell::new(file)),
+            Err(e) => {
+                self.file = None;
+                self.log(
+                    StdLogLevel::ERROR,
+                    format!("Could not open log file {}! {}", config.path, e).as_str(),
+                );
+            }
+        }
+    }
+
+    pub fn log(&self, log_level: StdLogLevel, message: &str) {
+        let level = match log_level {
+            StdLogLevel::INFO => LogLevel::Info,
+            StdLogLevel::WARN => LogLevel::Warn,
+            StdLogLevel::ERROR => LogLevel::Error,
+            StdLogLevel::UserPrint => {
+                let _ = self.sender.send(StdLogMessage {
+                    message: message.to_string(),
+                    log_level,
+                });
+                return;
+            }
+        };
+
+        if level < self.level {
+            return;
+        }
+
+        let message = format!(
+            "[{:?}] {:?} - {}",
+            log_level,
+            chrono::Local::now().format(TIME_FORMAT).to_string(),
+            message
+        );
+
+        if self.mirror_stderr {
+            eprintln!("{}", message);
+        }
+
+        if let Some(file) = &self.file {
+            if let Err(e) = file.borrow_mut().append(&message) {
+                eprintln!("Failed to write to log file! {}", e);
+            }
+        }
+
+        let _ = self.sender.send(StdLogMessage { message, log_level });
+    }
+}
+
+impl LogFile {
+    fn open(config: &LogsConfig) -> std::io::Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size: config.max_size,
+            max_files: config.max_files,
+        })
+    }
+
+    fn append(&mut self, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.max_size > 0 && self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Shifts `logs.txt` to `logs.txt.1`, `logs.txt.1` to `logs.txt.2` and so on, dropping the
+    /// oldest file once `max_files` rotated files exist
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let rotated = |n: usize| -> PathBuf {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            name.into()
+        };
+
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+            self.size = 0;
+            return Ok(());
+        }
+
+        let oldest = rotated(self.max_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+
+        for n in (1..self.max_files).rev() {
+            if Path::new(&rotated(n)).exists() {
+                fs::rename(rotated(n), rotated(n + 1))?;
+            }
+        }
+
+        fs::rename(&self.path, rotated(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn log_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cpuv-logs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn open(dir: &Path, max_files: usize) -> LogFile {
+        LogFile::open(&LogsConfig {
+            should_write: true,
+            path: dir.join("logs.txt").display().to_string(),
+            // two lines of `line N\n`
+            max_size: 14,
+            max_files,
+            mirror_stderr: false,
+        })
+        .unwrap()
+    }
+
+    fn files(dir: &Path) -> Vec<(String, String)> {
+        let mut files = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                (name, fs::read_to_string(&path).unwrap())
+            })
+            .collect::<Vec<(String, String)>>();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn rotation_keeps_max_files_old_logs() {
+        let dir = log_dir("rotate");
+        let mut file = open(&dir, 2);
+        for line in 0..9 {
+            file.append(&format!("line {}", line)).unwrap();
+        }
+        let files = files(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            files,
+            [
+                ("logs.txt".to_string(), "line 8\n".to_string()),
+                ("logs.txt.1".to_string(), "line 6\nline 7\n".to_string()),
+                ("logs.txt.2".to_string(), "line 4\nline 5\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rotation_continues_an_existing_log() {
+        let dir = log_dir("reopen");
+        open(&dir, 1).append("line 0").unwrap();
+        let mut file = open(&dir, 1);
+        file.append("line 1").unwrap();
+        file.append("line 2").unwrap();
+        let files = files(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            files,
+            [
+                ("logs.txt".to_string(), "line 2\n".to_string()),
+                ("logs.txt.1".to_string(), "line 0\nline 1\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_rotated_files_truncates_the_log() {
+        let dir = log_dir("truncate");
+        let mut file = open(&dir, 0);
+        for line in 0..5 {
+            file.append(&format!("line {}", line)).unwrap();
+        }
+        let files = files(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(files, [("logs.txt".to_string(), "line 4\n".to_string())]);
+    }
+}
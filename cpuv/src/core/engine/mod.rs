@@ -1,4 +1,19 @@
+pub mod cycles;
+pub mod debugger;
+#[allow(clippy::module_inception)]
 pub mod engine;
+pub mod expression;
+pub mod heap_latency;
+pub mod history;
+pub mod logger;
+pub mod parsing_results;
+pub mod publisher;
 pub mod runner;
+pub mod snapshot;
+pub mod stdout;
+pub mod trace;
+pub mod trace_diff;
 
+pub use debugger::{BreakpointSpec, DebugPointStatus, WatchKind, WatchpointSpec};
 pub use engine::*;
+pub use snapshot::SNAPSHOT_EXTENSION;
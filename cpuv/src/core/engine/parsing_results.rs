@@ -0,0 +1,207 @@
+use std::fs;
+use std::path::Path;
+
+use irv::{InstructionValue, Program};
+
+use crate::config::{ParsingResultsConfig, ParsingResultsFormat};
+
+/// Serializes the parsed program to `config.path`, creating any missing parent directories.
+pub fn write_parsing_results(
+    config: &ParsingResultsConfig,
+    program: &Program,
+) -> Result<(), String> {
+    let contents = match config.format {
+        ParsingResultsFormat::Json => {
+            serde_json::to_string_pretty(program).map_err(|e| e.to_string())?
+        }
+        ParsingResultsFormat::Ron => {
+            ron::ser::to_string_pretty(program, Default::default()).map_err(|e| e.to_string())?
+        }
+        ParsingResultsFormat::Tree => tree(program),
+    };
+
+    let path = Path::new(&config.path);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Could not create {}: {}", parent.display(), e))?;
+    }
+
+    fs::write(path, contents).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+/// Renders the program as an indented tree, meant to be read rather than parsed
+pub fn tree(program: &Program) -> String {
+    let leaf = |text: String| Node {
+        text,
+        children: Vec::new(),
+    };
+
+    let externs = program
+        .extern_functions
+        .iter()
+        .flatten()
+        .map(|function| leaf(function.clone()));
+
+    let data = program.static_variables.iter().map(|var| {
+        leaf(format!(
+            "{} {} = {}",
+            format!("{:?}", var.ty).to_lowercase(),
+            var.name,
+            var.inital_value
+        ))
+    });
+
+    let labels = program.labels.iter().flatten().map(|label| Node {
+        text: label.label_name.clone(),
+        children: label
+            .instructions
+            .iter()
+            .flatten()
+            .map(|instruction| {
+                leaf(match &instruction.val {
+                    InstructionValue::SingleValue(val) => format!("{:?} {}", instruction.ty, val)
+                        .trim_end()
+                        .to_string(),
+                    InstructionValue::MultipleValue((left, right)) => {
+                        format!("{:?} {}, {}", instruction.ty, left, right)
+                    }
+                })
+            })
+            .collect(),
+    });
+
+    let root = Node {
+        text: "Program".to_string(),
+        children: vec![
+            Node {
+                text: "Extern Functions".to_string(),
+                children: externs.collect(),
+            },
+            Node {
+                text: "Static Variables".to_string(),
+                children: data.collect(),
+            },
+            Node {
+                text: "Labels".to_string(),
+                children: labels.collect(),
+            },
+        ],
+    };
+
+    let mut out = format!("{}\n", root.text);
+    root.render_children("", &mut out);
+    out
+}
+
+struct Node {
+    text: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn render_children(&self, indent: &str, out: &mut String) {
+        for (idx, child) in self.children.iter().enumerate() {
+            let last = idx == self.children.len() - 1;
+            let (branch, continuation) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+
+            out.push_str(&format!("{}{}{}\n", indent, branch, child.text));
+            child.render_children(&format!("{}{}", indent, continuation), out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+
+    use irv::try_parse;
+
+    use super::*;
+
+    fn program() -> Program {
+        try_parse(
+            ".section .extern\n\
+             \x20   printf\n\
+             .section .data:\n\
+             \x20   word count 3\n\
+             .section .program:\n\
+             @start:\n\
+             \x20   LOAD count rax\n\
+             \x20   CALL printf rax\n\
+             @end:\n\
+             \x20   EXIT 0\n",
+        )
+        .unwrap()
+    }
+
+    fn results_dir(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "cpuv-parsing-results-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn write(dir: &Path, file: &str, format: ParsingResultsFormat) -> Result<String, String> {
+        let path = dir.join(file);
+        let config = ParsingResultsConfig {
+            should_write: true,
+            path: path.display().to_string(),
+            format,
+        };
+        write_parsing_results(&config, &program())?;
+        Ok(fs::read_to_string(path).unwrap())
+    }
+
+    #[test]
+    fn programs_are_rendered_as_a_tree() {
+        assert_eq!(
+            tree(&program()),
+            "Program\n\
+             ├── Extern Functions\n\
+             │   └── printf\n\
+             ├── Static Variables\n\
+             │   └── word count = 3\n\
+             └── Labels\n\
+             \x20   ├── @start:\n\
+             \x20   │   ├── LOAD count rax\n\
+             \x20   │   └── CALL printf rax\n\
+             \x20   └── @end:\n\
+             \x20       └── EXIT 0\n"
+        );
+    }
+
+    #[test]
+    fn every_format_is_written_into_new_directories() {
+        let dir = results_dir("formats");
+        let _ = fs::remove_dir_all(&dir);
+        let json = write(&dir, "a/parsed.json", ParsingResultsFormat::Json);
+        let ron = write(&dir, "b/c/parsed.ron", ParsingResultsFormat::Ron);
+        let tree = write(&dir, "parsed.ptree", ParsingResultsFormat::Tree);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let json = serde_json::from_str::<Program>(&json.unwrap()).unwrap();
+        assert_eq!(json, program());
+        let ron = ron::from_str::<Program>(&ron.unwrap()).unwrap();
+        assert_eq!(ron, program());
+        assert_eq!(tree.unwrap(), super::tree(&program()));
+    }
+
+    #[test]
+    fn write_errors_name_the_path() {
+        let dir = results_dir("errors");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), "").unwrap();
+        let error = write(&dir, "file/parsed.ptree", ParsingResultsFormat::Tree);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let error = error.unwrap_err();
+        assert!(error.starts_with("Could not create "), "{}", error);
+        assert!(error.contains("file"), "{}", error);
+    }
+}
@@ -0,0 +1,176 @@
+//! Hands engine states to the UI without letting them pile up.
+//!
+//! States sent on every tick replace the previous one if the UI has not taken it yet, so the UI
+//! always sees the latest state no matter how fast the engine ticks. Responses to client commands
+//! are kept in order and never dropped, the UI reacts to some of them (like replacing the code after
+//! LoadBinary).
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use super::EngineData;
+
+/// Entries kept for a UI that stopped taking states, before states between responses are dropped
+const MAX_PENDING: usize = 64;
+
+pub fn channel() -> (StatePublisher, StateReceiver) {
+    let pending = Arc::new(Mutex::new(VecDeque::new()));
+    (
+        StatePublisher {
+            pending: Arc::clone(&pending),
+        },
+        StateReceiver { pending },
+    )
+}
+
+/// The UI is gone, states are no longer taken
+#[derive(Debug)]
+pub struct Disconnected;
+
+pub struct StatePublisher {
+    pending: Arc<Mutex<VecDeque<EngineData>>>,
+}
+
+impl StatePublisher {
+    /// Queues a state, failing like a channel would once the receiver is gone
+    pub fn send(&self, data: EngineData) -> Result<(), Disconnected> {
+        if Arc::strong_count(&self.pending) == 1 {
+            return Err(Disconnected);
+        }
+
+        let mut pending = self.pending.lock().map_err(|_| Disconnected)?;
+
+        let coalesce = data.responding_to.is_none()
+            && pending
+                .back()
+                .is_some_and(|previous| previous.responding_to.is_none());
+        if coalesce {
+            pending.pop_back();
+        }
+
+        pending.push_back(data);
+        if pending.len() > MAX_PENDING {
+            let stale = pending
+                .range(..pending.len() - 1)
+                .position(|data| data.responding_to.is_none());
+            if let Some(index) = stale {
+                pending.remove(index);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct StateReceiver {
+    pending: Arc<Mutex<VecDeque<EngineData>>>,
+}
+
+impl StateReceiver {
+    /// Takes every pending state, oldest first. The last one is the current state of the engine.
+    pub fn drain(&self) -> Vec<EngineData> {
+        self.pending
+            .lock()
+            .map(|mut pending| pending.drain(..).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ClientCommandType;
+    use super::*;
+
+    fn state(tick: usize) -> EngineData {
+        EngineData {
+            tick,
+            ..Default::default()
+        }
+    }
+
+    fn response(tick: usize, command: ClientCommandType) -> EngineData {
+        EngineData {
+            tick,
+            responding_to: Some(command),
+            ..Default::default()
+        }
+    }
+
+    fn ticks(data: &[EngineData]) -> Vec<usize> {
+        data.iter().map(|data| data.tick).collect()
+    }
+
+    #[test]
+    fn states_collapse_to_the_latest() {
+        let (publisher, receiver) = channel();
+        for tick in 0..10 {
+            publisher.send(state(tick)).unwrap();
+        }
+        assert_eq!(ticks(&receiver.drain()), [9]);
+        assert!(receiver.drain().is_empty());
+    }
+
+    #[test]
+    fn responses_are_kept_in_order() {
+        let (publisher, receiver) = channel();
+        publisher.send(state(0)).unwrap();
+        publisher.send(state(1)).unwrap();
+        publisher
+            .send(response(2, ClientCommandType::Pause))
+            .unwrap();
+        publisher
+            .send(response(3, ClientCommandType::StepInstruction))
+            .unwrap();
+        publisher.send(state(4)).unwrap();
+        publisher.send(state(5)).unwrap();
+        publisher
+            .send(response(6, ClientCommandType::Pause))
+            .unwrap();
+        publisher.send(state(7)).unwrap();
+
+        let drained = receiver.drain();
+        assert_eq!(ticks(&drained), [1, 2, 3, 5, 6, 7]);
+        let commands = drained
+            .iter()
+            .filter_map(|data| data.responding_to.clone())
+            .collect::<Vec<ClientCommandType>>();
+        assert_eq!(
+            commands,
+            [
+                ClientCommandType::Pause,
+                ClientCommandType::StepInstruction,
+                ClientCommandType::Pause
+            ]
+        );
+    }
+
+    #[test]
+    fn responses_are_never_dropped() {
+        let (publisher, receiver) = channel();
+        for tick in 0..MAX_PENDING * 2 {
+            publisher.send(state(tick * 2)).unwrap();
+            publisher
+                .send(response(tick * 2 + 1, ClientCommandType::Pause))
+                .unwrap();
+        }
+        publisher.send(state(MAX_PENDING * 4)).unwrap();
+
+        let drained = receiver.drain();
+        let responses = drained
+            .iter()
+            .filter(|data| data.responding_to.is_some())
+            .map(|data| data.tick)
+            .collect::<Vec<usize>>();
+        let expected = (0..MAX_PENDING * 2)
+            .map(|tick| tick * 2 + 1)
+            .collect::<Vec<usize>>();
+        assert_eq!(responses, expected);
+        assert_eq!(drained.last().unwrap().tick, MAX_PENDING * 4);
+    }
+
+    #[test]
+    fn sending_fails_once_the_receiver_is_gone() {
+        let (publisher, receiver) = channel();
+        drop(receiver);
+        assert!(publisher.send(state(0)).is_err());
+    }
+}
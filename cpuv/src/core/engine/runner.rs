@@ -1,45 +1,729 @@
-#![allow(warnings)]
+use std::fmt;
 
-use super::Engine;
+use irv::{parse_immediate, DataType, InstructionType, IrInstruction, IrProgram, Operand};
 
-#[derive(Debug)]
+use super::trace::AccessKind;
+use super::{Engine, StdLogLevel};
+
+/// Deepest the call stack can get before the program is stopped
+pub const MAX_CALL_DEPTH: usize = 1024;
+
+#[derive(Debug, PartialEq)]
 pub enum InstructionExecutionSeccess {
     Ok,
-    WaitingForNextTick,
+    /// A BRK instruction was run
+    Break,
+    /// The program ran EXIT with this exit code
+    Exit(i128),
 }
 
 #[derive(Debug, PartialEq)]
 pub enum InstructionExecutionError {
+    /// Execution ran past the last instruction
     EndOfLabel,
+    NoProgram,
+    InvalidOperands {
+        address: usize,
+        message: String,
+    },
+    DivisionByZero {
+        address: usize,
+    },
+    CallStackOverflow {
+        address: usize,
+    },
+    ReturnWithoutCall {
+        address: usize,
+    },
+    MemoryOutOfBounds {
+        address: usize,
+        memory_address: usize,
+    },
 }
 
+impl fmt::Display for InstructionExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EndOfLabel => write!(f, "Ran past the last instruction"),
+            Self::NoProgram => write!(f, "No program is loaded"),
+            Self::InvalidOperands { address, message } => write!(f, "{:04}: {}", address, message),
+            Self::DivisionByZero { address } => write!(f, "{:04}: Division by zero", address),
+            Self::CallStackOverflow { address } => write!(
+                f,
+                "{:04}: Call stack overflow, more than {} nested CALLs",
+                address, MAX_CALL_DEPTH
+            ),
+            Self::ReturnWithoutCall { address } => {
+                write!(f, "{:04}: RET without a CALL to return to", address)
+            }
+            Self::MemoryOutOfBounds {
+                address,
+                memory_address,
+            } => write!(
+                f,
+                "{:04}: Memory address {:#06x} is outside of the heap",
+                address, memory_address
+            ),
+        }
+    }
+}
+
+type ExecutionResult<T> = Result<T, InstructionExecutionError>;
+
+/// Runs the instruction at the instruction pointer and moves the pointer to the next one
 pub fn run_instruction(
     engine: &mut Engine,
 ) -> Result<InstructionExecutionSeccess, InstructionExecutionError> {
-    if engine.state.tick % engine.options.engine.ipt != 0 {
-        return Ok(InstructionExecutionSeccess::WaitingForNextTick);
+    let Some(ir) = &engine.ir else {
+        return Err(InstructionExecutionError::NoProgram);
+    };
+
+    let Some(instruction) = ir.instructions.get(engine.state.instruction_ptr).cloned() else {
+        return Err(InstructionExecutionError::EndOfLabel);
+    };
+
+    let address = instruction.address;
+    let mut next = address + 1;
+    let mut result = InstructionExecutionSeccess::Ok;
+
+    match instruction.ty {
+        InstructionType::LOAD => {
+            let value = read(engine, operand(&instruction, 0, 2)?, address)?;
+            write(engine, operand(&instruction, 1, 2)?, value, address)?;
+        }
+
+        // like LOAD, but the source is cleared
+        InstructionType::MOVE => {
+            let (from, to) = (operand(&instruction, 0, 2)?, operand(&instruction, 1, 2)?);
+            let value = read(engine, from, address)?;
+            write(engine, to, value, address)?;
+            write(engine, from, 0, address)?;
+        }
+
+        InstructionType::INC | InstructionType::DEC => {
+            let target = operand(&instruction, 0, 1)?;
+            let value = read(engine, target, address)?;
+            let step = if instruction.ty == InstructionType::INC {
+                1
+            } else {
+                -1
+            };
+            write(engine, target, value.wrapping_add(step), address)?;
+        }
+
+        InstructionType::ADD
+        | InstructionType::SUB
+        | InstructionType::MUL
+        | InstructionType::DIV => {
+            let target = operand(&instruction, 0, 2)?;
+            let left = read(engine, target, address)?;
+            let right = read(engine, operand(&instruction, 1, 2)?, address)?;
+
+            let value = match instruction.ty {
+                InstructionType::ADD => left.wrapping_add(right),
+                InstructionType::SUB => left.wrapping_sub(right),
+                InstructionType::MUL => left.wrapping_mul(right),
+                _ if right == 0 => {
+                    return Err(InstructionExecutionError::DivisionByZero { address })
+                }
+                _ => left.wrapping_div(right),
+            };
+            write(engine, target, value, address)?;
+        }
+
+        InstructionType::CMP => {
+            let left = read(engine, operand(&instruction, 0, 2)?, address)?;
+            let right = read(engine, operand(&instruction, 1, 2)?, address)?;
+            engine.state.flags.zero = left == right;
+            engine.state.flags.negative = left < right;
+        }
+
+        InstructionType::JMP
+        | InstructionType::JEQ
+        | InstructionType::JLT
+        | InstructionType::JGT => {
+            let target = jump_target(&instruction)?;
+            let flags = engine.state.flags;
+            let taken = match instruction.ty {
+                InstructionType::JEQ => flags.zero,
+                InstructionType::JLT => flags.negative,
+                InstructionType::JGT => !flags.zero && !flags.negative,
+                _ => true,
+            };
+
+            if taken {
+                next = target;
+            }
+        }
+
+        InstructionType::CALL => match operand(&instruction, 0, 1)? {
+            Operand::Function(name) => {
+                let args = instruction.operands[1..]
+                    .iter()
+                    .map(|arg| format_argument(engine, arg, address))
+                    .collect::<ExecutionResult<Vec<String>>>()?;
+                call_external_function(engine, name.clone(), args);
+            }
+            Operand::Label { .. } => {
+                if engine.state.call_stack.len() >= MAX_CALL_DEPTH {
+                    return Err(InstructionExecutionError::CallStackOverflow { address });
+                }
+                engine.state.call_stack.push(next);
+                next = jump_target(&instruction)?;
+            }
+            other => {
+                return Err(InstructionExecutionError::InvalidOperands {
+                    address,
+                    message: format!("Cannot CALL {}", other),
+                })
+            }
+        },
+
+        InstructionType::RET => {
+            next = engine
+                .state
+                .call_stack
+                .pop()
+                .ok_or(InstructionExecutionError::ReturnWithoutCall { address })?;
+        }
+
+        InstructionType::NOP => {}
+
+        InstructionType::BRK => result = InstructionExecutionSeccess::Break,
+
+        InstructionType::EXIT => {
+            let code = match instruction.operands.first() {
+                Some(code) => read(engine, code, address)?,
+                None => 0,
+            };
+            result = InstructionExecutionSeccess::Exit(code);
+        }
     }
 
-    let instructions = &engine
-        .current_label
-        .as_ref()
-        .unwrap()
-        .instructions
-        .as_ref()
-        .unwrap();
+    engine.state.instruction_ptr = next;
+    Ok(result)
+}
 
-    if instructions.len() <= engine.state.instruction_ptr {
-        return Err(InstructionExecutionError::EndOfLabel);
+pub fn call_external_function(engine: &Engine, function_name: String, args: Vec<String>) {
+    match function_name.as_str() {
+        "printf" => engine.print(&args.join(" ")),
+        _ => engine.send_stdlog(
+            StdLogLevel::WARN,
+            format!(
+                "Extern function {} is not implemented, skipping",
+                function_name
+            )
+            .as_str(),
+        ),
+    }
+}
+
+/// Creates the heap with every variable of the data section set to its initial value
+pub fn load_data(ir: &IrProgram, heap_size: usize) -> Result<Vec<u8>, String> {
+    if ir.data_size() > heap_size {
+        return Err(format!(
+            "The data section needs {} bytes but engine.heap-memory-size is only {}",
+            ir.data_size(),
+            heap_size
+        ));
     }
 
-    let instruction = &instructions[engine.state.instruction_ptr];
-    println!("{:?}", instruction);
+    let mut memory = vec![0; heap_size];
+    for slot in &ir.data {
+        let invalid = || {
+            format!(
+                "Variable \"{}\" has an invalid initial value {}",
+                slot.name, slot.initial_value
+            )
+        };
+        let range = slot.address..slot.address + slot.ty.size();
 
-    match instruction.ty {
-        _ => todo!(),
+        if is_string(&slot.ty) {
+            let value = slot.initial_value.trim().trim_matches('"').as_bytes();
+            let len = value.len().min(slot.ty.size());
+            memory[range][..len].copy_from_slice(&value[..len]);
+            continue;
+        }
+
+        let value = match slot.ty {
+            DataType::Real4 | DataType::Real8 => {
+                let value = slot.initial_value.parse::<f64>().map_err(|_| invalid())?;
+                let bytes = match slot.ty {
+                    DataType::Real4 => (value as f32).to_le_bytes().to_vec(),
+                    _ => value.to_le_bytes().to_vec(),
+                };
+                memory[range].copy_from_slice(&bytes);
+                continue;
+            }
+            // values too big for a signed integer are wrapped like any other overflowing value
+            _ => parse_immediate(&slot.initial_value)
+                .or_else(|| {
+                    let digits = slot.initial_value.replace('_', "");
+                    digits.parse::<u128>().ok().map(|value| value as i128)
+                })
+                .ok_or_else(invalid)?,
+        };
+
+        memory[range].copy_from_slice(&value.to_le_bytes()[..slot.ty.size()]);
+    }
+
+    Ok(memory)
+}
+
+fn operand(instruction: &IrInstruction, index: usize, count: usize) -> ExecutionResult<&Operand> {
+    instruction
+        .operands
+        .get(index)
+        .ok_or_else(|| InstructionExecutionError::InvalidOperands {
+            address: instruction.address,
+            message: format!(
+                "{:?} expects {} operand(s), found {}",
+                instruction.ty,
+                count,
+                instruction.operands.len()
+            ),
+        })
+}
+
+fn jump_target(instruction: &IrInstruction) -> ExecutionResult<usize> {
+    match instruction.operands.first() {
+        Some(Operand::Label { address, .. }) => Ok(*address),
+        _ => Err(InstructionExecutionError::InvalidOperands {
+            address: instruction.address,
+            message: format!("{:?} expects a @label", instruction.ty),
+        }),
+    }
+}
+
+pub(super) fn read(engine: &Engine, operand: &Operand, address: usize) -> ExecutionResult<i128> {
+    match operand {
+        Operand::Variable {
+            name,
+            address: memory_address,
+            ty,
+        } => number(
+            name,
+            ty,
+            memory(engine, *memory_address, ty, address)?,
+            address,
+        ),
+        _ => peek(engine, operand, address),
+    }
+}
+
+/// Like [`read`], but without recording the access in the trace, for values the debugger looks at
+/// that the program itself did not read
+pub(super) fn peek(engine: &Engine, operand: &Operand, address: usize) -> ExecutionResult<i128> {
+    match operand {
+        Operand::Register(name) => Ok(engine.registers.get(name).unwrap_or_default()),
+        Operand::Immediate(value) => Ok(*value),
+        Operand::Variable {
+            name,
+            address: memory_address,
+            ty,
+        } => {
+            let bytes = memory_address
+                .checked_add(ty.size())
+                .and_then(|end| engine.memory.get(*memory_address..end))
+                .ok_or(InstructionExecutionError::MemoryOutOfBounds {
+                    address,
+                    memory_address: *memory_address,
+                })?;
+            number(name, ty, bytes, address)
+        }
+        Operand::Label { name, .. } => Err(InstructionExecutionError::InvalidOperands {
+            address,
+            message: format!("Label @{} cannot be used as a value", name),
+        }),
+        Operand::Function(name) => Err(InstructionExecutionError::InvalidOperands {
+            address,
+            message: format!("Function {} cannot be used as a value", name),
+        }),
     }
-    engine.state.instruction_ptr += 1;
-    return Ok(InstructionExecutionSeccess::Ok);
 }
 
-pub fn call_external_function(function_name: String, args: Vec<String>) {}
+/// The number held by the bytes of a variable of type `ty`
+pub(super) fn number(
+    name: &str,
+    ty: &DataType,
+    bytes: &[u8],
+    address: usize,
+) -> ExecutionResult<i128> {
+    let mut buffer = [0; 16];
+    buffer[..bytes.len()].copy_from_slice(bytes);
+
+    Ok(match ty {
+        DataType::Byte | DataType::Word | DataType::DWord | DataType::TByte => {
+            i128::from_le_bytes(buffer)
+        }
+        DataType::SByte => bytes[0] as i8 as i128,
+        DataType::SWord => i16::from_le_bytes([bytes[0], bytes[1]]) as i128,
+        DataType::SDWord => i32::from_le_bytes(buffer[..4].try_into().unwrap()) as i128,
+        // qwords hold the values of Tiny variables, which can be negative
+        DataType::QWord => i64::from_le_bytes(buffer[..8].try_into().unwrap()) as i128,
+        DataType::Real4 => f32::from_le_bytes(buffer[..4].try_into().unwrap()) as i128,
+        DataType::Real8 => f64::from_le_bytes(buffer[..8].try_into().unwrap()) as i128,
+        _ => {
+            return Err(InstructionExecutionError::InvalidOperands {
+                address,
+                message: format!("String variable {} cannot be used as a number", name),
+            })
+        }
+    })
+}
+
+fn write(
+    engine: &mut Engine,
+    operand: &Operand,
+    value: i128,
+    address: usize,
+) -> ExecutionResult<()> {
+    match operand {
+        Operand::Register(name) => {
+            let old = engine.registers.get(name).unwrap_or_default();
+            engine.registers.set(name, value);
+            let new = engine.registers.get(name).unwrap_or_default();
+            engine.history.record_register(name, old, new);
+            engine.tracer.record_register(name, old, new);
+            engine.debugger.record_register_write(name);
+            Ok(())
+        }
+        Operand::Variable {
+            name,
+            address: memory_address,
+            ty,
+        } => {
+            let bytes = match ty {
+                DataType::Real4 => (value as f32).to_le_bytes().to_vec(),
+                DataType::Real8 => (value as f64).to_le_bytes().to_vec(),
+                _ if is_string(ty) => {
+                    return Err(InstructionExecutionError::InvalidOperands {
+                        address,
+                        message: format!("Cannot store a number in string variable {}", name),
+                    })
+                }
+                _ => value.to_le_bytes()[..ty.size()].to_vec(),
+            };
+
+            let range = *memory_address..*memory_address + ty.size();
+            match engine.memory.get_mut(range.clone()) {
+                Some(memory) => {
+                    engine
+                        .history
+                        .record_memory(*memory_address, memory, &bytes);
+                    memory.copy_from_slice(&bytes);
+                    engine.debugger.record_memory_write(range);
+                    engine
+                        .tracer
+                        .record_memory(AccessKind::Write, *memory_address, &bytes);
+                    Ok(())
+                }
+                None => Err(InstructionExecutionError::MemoryOutOfBounds {
+                    address,
+                    memory_address: *memory_address,
+                }),
+            }
+        }
+        other => Err(InstructionExecutionError::InvalidOperands {
+            address,
+            message: format!("Cannot write to {}", other),
+        }),
+    }
+}
+
+/// Arguments of extern functions, strings are passed as text and everything else as a number
+fn format_argument(engine: &Engine, operand: &Operand, address: usize) -> ExecutionResult<String> {
+    match operand {
+        Operand::Variable {
+            address: memory_address,
+            ty,
+            ..
+        } if is_string(ty) => {
+            let bytes = memory(engine, *memory_address, ty, address)?;
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+        }
+        _ => read(engine, operand, address).map(|value| value.to_string()),
+    }
+}
+
+fn memory<'a>(
+    engine: &'a Engine,
+    memory_address: usize,
+    ty: &DataType,
+    address: usize,
+) -> ExecutionResult<&'a [u8]> {
+    let bytes = engine
+        .memory
+        .get(memory_address..memory_address + ty.size())
+        .ok_or(InstructionExecutionError::MemoryOutOfBounds {
+            address,
+            memory_address,
+        })?;
+    engine
+        .tracer
+        .record_memory(AccessKind::Read, memory_address, bytes);
+    Ok(bytes)
+}
+
+pub(super) fn is_string(ty: &DataType) -> bool {
+    matches!(
+        ty,
+        DataType::Str4
+            | DataType::Str8
+            | DataType::Str16
+            | DataType::Str32
+            | DataType::Str64
+            | DataType::Str128
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::engine::test_support::{load, printed};
+    use super::*;
+
+    fn program(data: &str, code: &str) -> Engine {
+        load(
+            "main.irv",
+            &format!(
+                ".section .extern\n    printf\n\n.section .data:\n{}\n\n.section .program:\n@start:\n{}\n",
+                data, code
+            ),
+        )
+    }
+
+    /// Runs `count` instructions, all of which have to succeed
+    fn step(engine: &mut Engine, count: usize) {
+        for _ in 0..count {
+            let address = engine.state.instruction_ptr;
+            if let Err(e) = run_instruction(engine) {
+                panic!("instruction {} failed! {}", address, e);
+            }
+        }
+    }
+
+    fn register(engine: &Engine, name: &str) -> i128 {
+        engine.registers.get(name).unwrap()
+    }
+
+    fn variable(engine: &Engine, name: &str) -> i128 {
+        let ir = engine.ir.as_ref().unwrap();
+        let slot = ir.data.iter().find(|slot| slot.name == name).unwrap();
+        let operand = Operand::Variable {
+            name: slot.name.clone(),
+            address: slot.address,
+            ty: slot.ty.clone(),
+        };
+        read(engine, &operand, 0).unwrap()
+    }
+
+    #[test]
+    fn arithmetic_wraps_to_the_register_width() {
+        let mut engine = program(
+            "",
+            "    LOAD 18446744073709551615 rax
+    INC rax
+    DEC rbx
+    LOAD 9223372036854775807 srax
+    ADD srax 1
+    LOAD 255 al
+    ADD al 2
+    LOAD 4294967296 ecx
+    LOAD -128 sal
+    SUB sal 1
+    LOAD 4611686018427387904 rdx
+    MUL rdx 4
+    LOAD -7 srbx
+    DIV srbx 2",
+        );
+        step(&mut engine, 14);
+
+        assert_eq!(register(&engine, "rax"), 0);
+        assert_eq!(register(&engine, "rbx"), u64::MAX as i128);
+        assert_eq!(register(&engine, "srax"), i64::MIN as i128);
+        assert_eq!(register(&engine, "al"), 1);
+        assert_eq!(register(&engine, "ecx"), 0);
+        assert_eq!(register(&engine, "sal"), 127);
+        assert_eq!(register(&engine, "rdx"), 0);
+        assert_eq!(register(&engine, "srbx"), -3);
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        let mut engine = program("", "    LOAD 10 rax\n    DIV rax rbx");
+        step(&mut engine, 1);
+
+        assert_eq!(
+            run_instruction(&mut engine),
+            Err(InstructionExecutionError::DivisionByZero { address: 1 })
+        );
+        assert_eq!(register(&engine, "rax"), 10);
+        assert_eq!(engine.state.instruction_ptr, 1);
+    }
+
+    #[test]
+    fn compares_set_the_flags_jumps_read() {
+        // each CMP is followed by the three conditional jumps, rax counts the jumps that were taken
+        let cases = [
+            ("3", "3", [true, false, false]),
+            ("2", "3", [false, true, false]),
+            ("4", "3", [false, false, true]),
+            ("-1", "3", [false, true, false]),
+        ];
+
+        for (left, right, expected) in cases {
+            for (jump, taken) in ["JEQ", "JLT", "JGT"].iter().zip(expected) {
+                let mut engine = program(
+                    "",
+                    &format!(
+                        "    LOAD {} srax\n    CMP srax {}\n    {} @taken\n    EXIT 0\n@taken:\n    EXIT 1",
+                        left, right, jump
+                    ),
+                );
+                step(&mut engine, 3);
+
+                let flags = engine.state.flags;
+                assert_eq!(flags.zero, left == right, "CMP {} {}", left, right);
+                assert_eq!(
+                    engine.state.instruction_ptr,
+                    if taken { 4 } else { 3 },
+                    "CMP {} {} then {}",
+                    left,
+                    right,
+                    jump
+                );
+            }
+        }
+
+        let mut engine = program("", "    JMP @end\n    NOP\n@end:\n    EXIT 0");
+        step(&mut engine, 1);
+        assert_eq!(engine.state.instruction_ptr, 2);
+    }
+
+    #[test]
+    fn calls_return_after_the_call() {
+        let mut engine = program(
+            "",
+            "    CALL @double\n    CALL @double\n    EXIT 0\n@double:\n    ADD rax rax\n    INC rax\n    RET",
+        );
+
+        step(&mut engine, 1);
+        assert_eq!(engine.state.instruction_ptr, 3);
+        assert_eq!(engine.state.call_stack, [1]);
+
+        step(&mut engine, 3);
+        assert_eq!(engine.state.instruction_ptr, 1);
+        assert!(engine.state.call_stack.is_empty());
+
+        step(&mut engine, 4);
+        assert_eq!(engine.state.instruction_ptr, 2);
+        assert_eq!(register(&engine, "rax"), 3);
+
+        assert_eq!(
+            run_instruction(&mut program("", "    RET")),
+            Err(InstructionExecutionError::ReturnWithoutCall { address: 0 })
+        );
+    }
+
+    #[test]
+    fn calls_overflow_the_stack() {
+        let mut engine = program("", "    CALL @start");
+        step(&mut engine, MAX_CALL_DEPTH);
+        assert_eq!(
+            run_instruction(&mut engine),
+            Err(InstructionExecutionError::CallStackOverflow { address: 0 })
+        );
+    }
+
+    #[test]
+    fn variables_are_read_and_written_with_their_type() {
+        let mut engine = program(
+            "    byte b 1
+    sbyte sb -2
+    word w 3
+    sword sw -4
+    dword dw 5
+    sdword sdw -6
+    qword q -7
+    tbyte tb 8
+    real4 r4 1.75
+    real8 r8 -2.5",
+            "    LOAD 300 b
+    LOAD 255 sb
+    LOAD 70000 w
+    LOAD 40000 sw
+    LOAD 4294967297 dw
+    LOAD 2147483648 sdw
+    LOAD -9223372036854775809 q
+    LOAD 1208925819614629174706175 tb
+    LOAD 3 r4
+    LOAD -4 r8",
+        );
+
+        let initial = [
+            ("b", 1),
+            ("sb", -2),
+            ("w", 3),
+            ("sw", -4),
+            ("dw", 5),
+            ("sdw", -6),
+            ("q", -7),
+            ("tb", 8),
+            ("r4", 1),
+            ("r8", -2),
+        ];
+        for (name, value) in initial {
+            assert_eq!(variable(&engine, name), value, "initial {}", name);
+        }
+
+        step(&mut engine, 10);
+        let written = [
+            ("b", 44),
+            ("sb", -1),
+            ("w", 4464),
+            ("sw", -25536),
+            ("dw", 1),
+            ("sdw", -2147483648),
+            ("q", i64::MAX as i128),
+            ("tb", (1 << 80) - 1),
+            ("r4", 3),
+            ("r8", -4),
+        ];
+        for (name, value) in written {
+            assert_eq!(variable(&engine, name), value, "written {}", name);
+        }
+
+        // the neighbours of a variable are left alone
+        let ir = engine.ir.as_ref().unwrap();
+        assert_eq!(ir.data_size(), 1 + 1 + 2 + 2 + 4 + 4 + 8 + 10 + 4 + 8);
+        assert!(engine.memory[ir.data_size()..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn strings_are_printed_but_not_used_as_numbers() {
+        let mut engine = program(
+            "    str4 short \"abcdef\"\n    str8 name \"irv\"",
+            "    CALL printf name short rax\n    LOAD name rax\n    LOAD 1 name",
+        );
+        step(&mut engine, 1);
+        assert_eq!(printed(&engine), ["irv abcd 0"]);
+
+        for address in [1, 2] {
+            engine.state.instruction_ptr = address;
+            assert!(matches!(
+                run_instruction(&mut engine),
+                Err(InstructionExecutionError::InvalidOperands { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn move_clears_the_source() {
+        let mut engine = program("    qword q 9", "    MOVE q rax\n    MOVE rax rbx");
+        step(&mut engine, 1);
+        assert_eq!((variable(&engine, "q"), register(&engine, "rax")), (0, 9));
+        step(&mut engine, 1);
+        assert_eq!((register(&engine, "rax"), register(&engine, "rbx")), (0, 9));
+    }
+}
@@ -0,0 +1,185 @@
+//! Snapshots of the complete machine state, written as versioned JSON.
+//!
+//! The IR is not stored, it is translated again from the program when the snapshot is restored.
+//! The history is not stored either, a restored program can only be stepped back to the point it
+//! was restored at.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use irv::{Program, Registers};
+
+use super::heap_latency::{HeapLatency, Stall};
+use super::Flags;
+
+pub const SNAPSHOT_EXTENSION: &str = "cpuvs";
+
+/// Bumped whenever a field is added, removed or changes its meaning. Snapshots of other versions
+/// are rejected instead of being restored into a different state.
+pub const SNAPSHOT_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Name of the frontend the program was compiled with
+    pub frontend: String,
+    /// Source the program was compiled from, none if it was loaded from a binary
+    pub source: Option<String>,
+    pub program: Program,
+    pub registers: Registers,
+    #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+    pub memory: Vec<u8>,
+    pub instruction_ptr: usize,
+    pub tick: usize,
+    pub instruction_budget: u64,
+    pub cycles: u64,
+    pub instructions: u64,
+    /// The heap access the next instruction is waiting for, if any
+    pub stall: Option<Stall>,
+    /// The jitter generator, so that the following accesses wait the same ticks
+    pub heap_latency: HeapLatency,
+    pub flags: Flags,
+    pub call_stack: Vec<usize>,
+    /// Every line the program printed so far
+    pub stdout: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    /// Parses a snapshot, checking the version before anything else so that an older or newer
+    /// snapshot is reported as such rather than as a missing field
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+
+        let Version { version } =
+            serde_json::from_str(json).map_err(|e| format!("Not a snapshot, {}", e))?;
+        if version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "Snapshot version {} is not supported, expected version {}",
+                version, SNAPSHOT_VERSION
+            ));
+        }
+
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+}
+
+/// The heap as one hex string, a JSON array would take a line per byte
+fn to_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>()
+        .serialize(serializer)
+}
+
+fn from_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    if hex.len() % 2 != 0 {
+        return Err(serde::de::Error::custom(
+            "memory has an odd number of hex digits",
+        ));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            // from_str_radix takes a sign as well
+            hex.get(i..i + 2)
+                .filter(|byte| byte.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| serde::de::Error::custom(format!("invalid hex byte at {}", i)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let mut registers = Registers::default();
+        registers.set("srax", -5);
+
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            frontend: "irv".to_string(),
+            source: Some("@start:\n    EXIT 0\n".to_string()),
+            program: Program::new(),
+            registers,
+            memory: vec![0x00, 0x7f, 0xff, 0x10],
+            instruction_ptr: 3,
+            tick: 12,
+            instruction_budget: 1,
+            cycles: 40,
+            instructions: 9,
+            stall: Some(Stall {
+                address: 3,
+                remaining: 2,
+                ticks: 4,
+            }),
+            heap_latency: HeapLatency::default(),
+            flags: Flags {
+                zero: true,
+                negative: false,
+            },
+            call_stack: vec![1, 7],
+            stdout: vec!["-5".to_string(), "done".to_string()],
+        }
+    }
+
+    #[test]
+    fn snapshots_round_trip_through_json() {
+        let json = snapshot().to_json().unwrap();
+        assert!(json.contains("\"memory\": \"007fff10\""), "{}", json);
+
+        let restored = Snapshot::from_json(&json).unwrap();
+        assert_eq!(restored.to_json().unwrap(), json);
+        assert_eq!(restored.memory, [0x00, 0x7f, 0xff, 0x10]);
+        assert_eq!(restored.registers.get("srax"), Some(-5));
+        assert_eq!(restored.stall, snapshot().stall);
+        assert_eq!(restored.call_stack, [1, 7]);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut old = snapshot();
+        old.version = SNAPSHOT_VERSION - 1;
+
+        assert_eq!(
+            Snapshot::from_json(&old.to_json().unwrap()).unwrap_err(),
+            format!(
+                "Snapshot version {} is not supported, expected version {}",
+                SNAPSHOT_VERSION - 1,
+                SNAPSHOT_VERSION
+            )
+        );
+        assert!(Snapshot::from_json("{}")
+            .unwrap_err()
+            .starts_with("Not a snapshot"));
+    }
+
+    #[test]
+    fn memory_must_be_hex_bytes() {
+        let json = snapshot().to_json().unwrap();
+        let with_memory = |memory: &str| json.replace("\"007fff10\"", memory);
+
+        let error = Snapshot::from_json(&with_memory("\"007ff\"")).unwrap_err();
+        assert!(
+            error.contains("memory has an odd number of hex digits"),
+            "{}",
+            error
+        );
+
+        let error = Snapshot::from_json(&with_memory("\"00zz\"")).unwrap_err();
+        assert!(error.contains("invalid hex byte at 2"), "{}", error);
+
+        let error = Snapshot::from_json(&with_memory("\"+1\"")).unwrap_err();
+        assert!(error.contains("invalid hex byte at 0"), "{}", error);
+    }
+}
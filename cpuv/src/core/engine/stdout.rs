@@ -0,0 +1,280 @@
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use super::{StdLogLevel, StdLogMessage};
+use crate::config::{ProgramConfig, StdoutOption};
+
+/// Fans the output of the running program out to every sink listed in `program.stdout`.
+///
+/// The virtual sink is the stdout panel of the UI, so it is simply dropped when nothing is
+/// listening on the other end of the channel.
+pub struct StdoutRouter {
+    sender: mpsc::Sender<StdLogMessage>,
+    virtual_stdout: bool,
+    console: bool,
+    file: Option<RefCell<StdoutFile>>,
+    /// Everything printed since the program was loaded, kept for snapshots
+    printed: RefCell<Vec<String>>,
+}
+
+struct StdoutFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl StdoutRouter {
+    /// Builds the router from the config, returning it along with an error for a file sink that
+    /// could not be opened. The other sinks still work in that case.
+    pub fn new(
+        config: &ProgramConfig,
+        sender: mpsc::Sender<StdLogMessage>,
+    ) -> (Self, Option<String>) {
+        let mut router = Self {
+            sender,
+            virtual_stdout: false,
+            console: false,
+            file: None,
+            printed: RefCell::new(Vec::new()),
+        };
+        let error = router.reconfigure(config);
+
+        (router, error)
+    }
+
+    /// Switches to the sinks of a new config. The stdout file is only recreated when it is turned
+    /// on or its path changed, otherwise it keeps the output printed so far.
+    pub fn reconfigure(&mut self, config: &ProgramConfig) -> Option<String> {
+        let enabled = |option: StdoutOption| {
+            config
+                .stdout
+                .iter()
+                .any(|o| *o == option || *o == StdoutOption::All)
+        };
+
+        self.virtual_stdout = enabled(StdoutOption::Virtual);
+        self.console = enabled(StdoutOption::Console);
+
+        if !enabled(StdoutOption::File) {
+            self.file = None;
+            return None;
+        }
+
+        if let Some(file) = &self.file {
+            if file.borrow().path == Path::new(&config.stdout_path) {
+                return None;
+            }
+        }
+
+        match create_file(&config.stdout_path) {
+            Ok(file) => {
+                self.file = Some(RefCell::new(StdoutFile {
+                    path: PathBuf::from(&config.stdout_path),
+                    file,
+                }));
+                None
+            }
+            Err(e) => {
+                self.file = None;
+                Some(format!(
+                    "Could not open stdout file {}! {}",
+                    config.stdout_path, e
+                ))
+            }
+        }
+    }
+
+    pub fn print(&self, text: &str) {
+        self.printed.borrow_mut().push(text.to_string());
+
+        if self.virtual_stdout {
+            let _ = self.sender.send(StdLogMessage {
+                message: text.to_string(),
+                log_level: StdLogLevel::UserPrint,
+            });
+        }
+
+        if self.console {
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{}", text);
+            let _ = stdout.flush();
+        }
+
+        if let Some(file) = &self.file {
+            if let Err(e) = writeln!(file.borrow_mut().file, "{}", text) {
+                eprintln!("Failed to write to stdout file! {}", e);
+            }
+        }
+    }
+
+    pub fn printed(&self) -> Vec<String> {
+        self.printed.borrow().clone()
+    }
+
+    /// Forgets the output of the previous program
+    pub fn clear(&self) {
+        self.printed.borrow_mut().clear();
+    }
+
+    /// Takes the output of a restored snapshot as what was printed so far. The sinks already got
+    /// it when it was printed, so it is not sent to them again.
+    pub fn replay(&self, lines: &[String]) {
+        *self.printed.borrow_mut() = lines.to_vec();
+    }
+}
+
+fn create_file(path: &str) -> std::io::Result<File> {
+    let path = Path::new(path);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    File::create(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn stdout_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cpuv-stdout-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn config(stdout: Vec<StdoutOption>, path: &Path) -> ProgramConfig {
+        ProgramConfig {
+            stdout,
+            stdout_path: path.display().to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn received(receiver: &mpsc::Receiver<StdLogMessage>) -> Vec<String> {
+        receiver
+            .try_iter()
+            .map(|message| {
+                assert_eq!(message.log_level, StdLogLevel::UserPrint);
+                message.message
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_virtual_sink_sends_each_line() {
+        let (sender, receiver) = mpsc::channel();
+        let path = stdout_dir("virtual").join("stdout.txt");
+        let (router, error) =
+            StdoutRouter::new(&config(vec![StdoutOption::Virtual], &path), sender);
+        assert_eq!(error, None);
+
+        router.print("1");
+        router.print("2");
+        assert_eq!(received(&receiver), ["1", "2"]);
+        assert_eq!(router.printed(), ["1", "2"]);
+        assert!(!router.console);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn the_file_sink_writes_each_line() {
+        let (sender, receiver) = mpsc::channel();
+        let dir = stdout_dir("file");
+        let path = dir.join("out").join("stdout.txt");
+        let (router, error) = StdoutRouter::new(&config(vec![StdoutOption::File], &path), sender);
+        assert_eq!(error, None);
+
+        router.print("1");
+        router.print("2");
+        let written = fs::read_to_string(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(written.unwrap(), "1\n2\n");
+        assert!(received(&receiver).is_empty());
+        assert_eq!(router.printed(), ["1", "2"]);
+    }
+
+    #[test]
+    fn all_enables_every_sink() {
+        let (sender, _receiver) = mpsc::channel();
+        let dir = stdout_dir("all");
+        let path = dir.join("stdout.txt");
+        let (router, error) = StdoutRouter::new(&config(vec![StdoutOption::All], &path), sender);
+        let created = path.exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(error, None);
+        assert!(router.virtual_stdout && router.console && created);
+    }
+
+    #[test]
+    fn the_file_is_kept_until_its_path_changes() {
+        let (sender, _receiver) = mpsc::channel();
+        let dir = stdout_dir("reconfigure");
+        let first = dir.join("first.txt");
+        let second = dir.join("second.txt");
+        let (mut router, _) = StdoutRouter::new(&config(vec![StdoutOption::File], &first), sender);
+
+        router.print("1");
+        assert_eq!(
+            router.reconfigure(&config(
+                vec![StdoutOption::File, StdoutOption::Virtual],
+                &first
+            )),
+            None
+        );
+        router.print("2");
+        assert_eq!(
+            router.reconfigure(&config(vec![StdoutOption::File], &second)),
+            None
+        );
+        router.print("3");
+        assert_eq!(
+            router.reconfigure(&config(vec![StdoutOption::Virtual], &second)),
+            None
+        );
+        router.print("4");
+        let written = (fs::read_to_string(&first), fs::read_to_string(&second));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(written.0.unwrap(), "1\n2\n");
+        assert_eq!(written.1.unwrap(), "3\n");
+    }
+
+    #[test]
+    fn a_file_that_cannot_be_created_leaves_the_other_sinks() {
+        let (sender, receiver) = mpsc::channel();
+        let dir = stdout_dir("error");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), "").unwrap();
+        let path = dir.join("file").join("stdout.txt");
+        let (router, error) = StdoutRouter::new(
+            &config(vec![StdoutOption::Virtual, StdoutOption::File], &path),
+            sender,
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        let error = error.unwrap();
+        assert!(error.starts_with("Could not open stdout file"), "{}", error);
+        router.print("1");
+        assert_eq!(received(&receiver), ["1"]);
+    }
+
+    #[test]
+    fn replayed_output_is_not_sent_again() {
+        let (sender, receiver) = mpsc::channel();
+        let path = stdout_dir("replay").join("stdout.txt");
+        let (router, _) = StdoutRouter::new(&config(vec![StdoutOption::Virtual], &path), sender);
+
+        router.print("1");
+        router.replay(&["a".to_string(), "b".to_string()]);
+        assert_eq!(router.printed(), ["a", "b"]);
+        assert_eq!(received(&receiver), ["1"]);
+
+        router.clear();
+        assert!(router.printed().is_empty());
+    }
+}
@@ -0,0 +1,420 @@
+//! Execution traces, one entry per executed instruction.
+//!
+//! The trace is written while the program runs, as JSON Lines or CSV, so that long runs don't have
+//! to be kept in memory. Stepping back does not take entries back, the trace lists instructions in
+//! the order they were executed, including the ones that were run again.
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use irv::IrInstruction;
+
+use super::Flags;
+use crate::config::{TraceConfig, TraceFormat};
+
+/// Header of CSV traces, lists are joined with `;` inside of their column
+const CSV_HEADER: &str = "tick,address,line,instruction,operands,registers,zf,nf,memory,next";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// Tick the instruction ran on, steps run outside of ticks share the tick they paused at
+    pub tick: usize,
+    pub address: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub instruction: String,
+    pub operands: Vec<String>,
+    /// Register writes in the order they happened
+    pub registers: Vec<RegisterWrite>,
+    /// Flags after the instruction ran
+    pub flags: Flags,
+    pub memory: Vec<MemoryAccess>,
+    /// Address of the instruction that runs next
+    pub next: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisterWrite {
+    pub register: String,
+    pub old: i128,
+    pub new: i128,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub address: usize,
+    /// The bytes that were read or written, as hex
+    pub bytes: String,
+}
+
+impl TraceEntry {
+    fn csv_row(&self) -> String {
+        let registers = self
+            .registers
+            .iter()
+            .map(|write| format!("{}:{}->{}", write.register, write.old, write.new))
+            .collect::<Vec<String>>();
+        let memory = self
+            .memory
+            .iter()
+            .map(|access| {
+                let kind = match access.kind {
+                    AccessKind::Read => "R",
+                    AccessKind::Write => "W",
+                };
+                format!("{} {:#06x} {}", kind, access.address, access.bytes)
+            })
+            .collect::<Vec<String>>();
+
+        [
+            self.tick.to_string(),
+            self.address.to_string(),
+            self.line.map(|line| line.to_string()).unwrap_or_default(),
+            self.instruction.clone(),
+            csv_field(&self.operands.join(";")),
+            csv_field(&registers.join(";")),
+            (self.flags.zero as u8).to_string(),
+            (self.flags.negative as u8).to_string(),
+            csv_field(&memory.join(";")),
+            self.next.to_string(),
+        ]
+        .join(",")
+    }
+}
+
+/// Reads a JSON Lines trace written by the engine
+pub fn read_trace(path: &Path) -> Result<Vec<TraceEntry>, String> {
+    if path.extension().is_some_and(|ext| ext == "csv") {
+        return Err(format!(
+            "{} is a CSV trace, only JSON Lines traces (format = \"jsonl\") can be read back",
+            path.display()
+        ));
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| format!("{} line {}: {}", path.display(), index + 1, e))
+        })
+        .collect()
+}
+
+/// Quotes a field if it holds a separator or a quote
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+struct TraceFile {
+    path: String,
+    format: TraceFormat,
+    writer: BufWriter<File>,
+    entries: usize,
+}
+
+/// Records the executed instructions to the trace file of `program.output-trace`.
+///
+/// Reads happen while the engine is only borrowed, so the entry being recorded is kept in a
+/// `RefCell`.
+#[derive(Default)]
+pub struct Tracer {
+    file: Option<TraceFile>,
+    recording: RefCell<Option<TraceEntry>>,
+}
+
+impl Tracer {
+    /// Starts a new trace for a program that was just loaded, replacing the previous one. Returns
+    /// an error if the file could not be created, tracing is off in that case.
+    pub fn start(&mut self, config: &TraceConfig) -> Option<String> {
+        self.file = None;
+        self.recording = RefCell::new(None);
+        if !config.should_write {
+            return None;
+        }
+
+        let path = Path::new(&config.path);
+        let writer = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| File::create(path))
+            .map(BufWriter::new)
+            .and_then(|mut writer| {
+                if config.format == TraceFormat::Csv {
+                    writeln!(writer, "{}", CSV_HEADER)?;
+                }
+                Ok(writer)
+            });
+
+        match writer {
+            Ok(writer) => {
+                self.file = Some(TraceFile {
+                    path: config.path.clone(),
+                    format: config.format,
+                    writer,
+                    entries: 0,
+                });
+                None
+            }
+            Err(e) => Some(format!(
+                "Could not create trace file {}! {}",
+                config.path, e
+            )),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Starts recording the instruction that is about to run
+    pub fn begin(&self, tick: usize, instruction: &IrInstruction) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        *self.recording.borrow_mut() = Some(TraceEntry {
+            tick,
+            address: instruction.address,
+            line: instruction.line,
+            instruction: format!("{:?}", instruction.ty),
+            operands: instruction
+                .operands
+                .iter()
+                .map(|operand| operand.to_string())
+                .collect(),
+            registers: Vec::new(),
+            flags: Flags::default(),
+            memory: Vec::new(),
+            next: instruction.address,
+        });
+    }
+
+    pub fn record_register(&self, register: &str, old: i128, new: i128) {
+        if let Some(entry) = self.recording.borrow_mut().as_mut() {
+            entry.registers.push(RegisterWrite {
+                register: register.to_string(),
+                old,
+                new,
+            });
+        }
+    }
+
+    pub fn record_memory(&self, kind: AccessKind, address: usize, bytes: &[u8]) {
+        if let Some(entry) = self.recording.borrow_mut().as_mut() {
+            entry.memory.push(MemoryAccess {
+                kind,
+                address,
+                bytes: bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            });
+        }
+    }
+
+    /// Writes the instruction recorded since [`Tracer::begin`]. A write error ends the trace and is
+    /// returned.
+    pub fn finish(&mut self, flags: Flags, next: usize) -> Option<String> {
+        let mut entry = self.recording.get_mut().take()?;
+        let file = self.file.as_mut()?;
+        entry.flags = flags;
+        entry.next = next;
+
+        let line = match file.format {
+            TraceFormat::Jsonl => serde_json::to_string(&entry).map_err(|e| e.to_string()),
+            TraceFormat::Csv => Ok(entry.csv_row()),
+        };
+        let written =
+            line.and_then(|line| writeln!(file.writer, "{}", line).map_err(|e| e.to_string()));
+
+        match written {
+            Ok(()) => {
+                file.entries += 1;
+                None
+            }
+            Err(e) => {
+                let error = format!("Failed to write trace file {}! {}", file.path, e);
+                self.file = None;
+                Some(error)
+            }
+        }
+    }
+
+    /// Writes out buffered entries, returning where the trace is and how many entries it has
+    pub fn flush(&mut self) -> Option<(String, usize)> {
+        let file = self.file.as_mut()?;
+        let _ = file.writer.flush();
+        Some((file.path.clone(), file.entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+
+    use irv::{InstructionType, Operand};
+
+    use super::*;
+
+    fn trace_path(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cpuv-trace-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    fn instruction(address: usize, line: Option<usize>, operands: Vec<Operand>) -> IrInstruction {
+        IrInstruction {
+            address,
+            ty: InstructionType::ADD,
+            operands,
+            line,
+        }
+    }
+
+    /// Traces two instructions, the first adding 1 to rax and writing two bytes to the heap
+    fn write_trace(path: &Path, format: TraceFormat, operands: Vec<Operand>) -> String {
+        let mut tracer = Tracer::default();
+        let config = TraceConfig {
+            should_write: true,
+            path: path.display().to_string(),
+            format,
+        };
+        assert_eq!(tracer.start(&config), None);
+
+        tracer.begin(3, &instruction(12, Some(9), operands));
+        tracer.record_register("rax", 1, 2);
+        tracer.record_memory(AccessKind::Write, 16, &[1, 2]);
+        let flags = Flags {
+            zero: true,
+            negative: false,
+        };
+        assert_eq!(tracer.finish(flags, 13), None);
+
+        tracer.begin(3, &instruction(13, None, Vec::new()));
+        assert_eq!(tracer.finish(Flags::default(), 14), None);
+
+        assert_eq!(tracer.flush(), Some((config.path, 2)));
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn jsonl_traces_have_one_object_per_line() {
+        let path = trace_path("pinned.jsonl");
+        let operands = vec![Operand::Register("rax".to_string()), Operand::Immediate(1)];
+        let written = write_trace(&path, TraceFormat::Jsonl, operands);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            written,
+            "{\"tick\":3,\"address\":12,\"line\":9,\"instruction\":\"ADD\",\
+             \"operands\":[\"reg rax\",\"imm 1\"],\
+             \"registers\":[{\"register\":\"rax\",\"old\":1,\"new\":2}],\
+             \"flags\":{\"zero\":true,\"negative\":false},\
+             \"memory\":[{\"kind\":\"write\",\"address\":16,\"bytes\":\"0102\"}],\"next\":13}\n\
+             {\"tick\":3,\"address\":13,\"instruction\":\"ADD\",\"operands\":[],\"registers\":[],\
+             \"flags\":{\"zero\":false,\"negative\":false},\"memory\":[],\"next\":14}\n"
+        );
+    }
+
+    #[test]
+    fn csv_traces_have_a_header_and_quote_fields() {
+        let path = trace_path("pinned.csv");
+        let operands = vec![
+            Operand::Function("a,b".to_string()),
+            Operand::Function("say \"hi\"".to_string()),
+        ];
+        let written = write_trace(&path, TraceFormat::Csv, operands);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            written,
+            "tick,address,line,instruction,operands,registers,zf,nf,memory,next\n\
+             3,12,9,ADD,\"fn a,b;fn say \"\"hi\"\"\",rax:1->2,1,0,W 0x0010 0102,13\n\
+             3,13,,ADD,,,0,0,,14\n"
+        );
+    }
+
+    #[test]
+    fn jsonl_traces_read_back() {
+        let path = trace_path("read-back.jsonl");
+        let operands = vec![Operand::Register("rax".to_string()), Operand::Immediate(1)];
+        write_trace(&path, TraceFormat::Jsonl, operands);
+        let entries = read_trace(&path);
+        fs::remove_file(&path).unwrap();
+
+        let entries = entries.unwrap();
+        assert_eq!(
+            entries,
+            [
+                TraceEntry {
+                    tick: 3,
+                    address: 12,
+                    line: Some(9),
+                    instruction: "ADD".to_string(),
+                    operands: vec!["reg rax".to_string(), "imm 1".to_string()],
+                    registers: vec![RegisterWrite {
+                        register: "rax".to_string(),
+                        old: 1,
+                        new: 2,
+                    }],
+                    flags: Flags {
+                        zero: true,
+                        negative: false,
+                    },
+                    memory: vec![MemoryAccess {
+                        kind: AccessKind::Write,
+                        address: 16,
+                        bytes: "0102".to_string(),
+                    }],
+                    next: 13,
+                },
+                TraceEntry {
+                    tick: 3,
+                    address: 13,
+                    line: None,
+                    instruction: "ADD".to_string(),
+                    operands: Vec::new(),
+                    registers: Vec::new(),
+                    flags: Flags::default(),
+                    memory: Vec::new(),
+                    next: 14,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn only_jsonl_traces_can_be_read() {
+        let error = read_trace(Path::new("out/trace.csv")).unwrap_err();
+        assert!(error.contains("is a CSV trace"), "{}", error);
+
+        let path = trace_path("broken.jsonl");
+        fs::write(&path, "{\"tick\":3}\n").unwrap();
+        let error = read_trace(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(
+            error.contains("broken.jsonl line 1: missing field"),
+            "{}",
+            error
+        );
+    }
+}
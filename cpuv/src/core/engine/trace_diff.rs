@@ -0,0 +1,348 @@
+//! Compares two execution traces instruction by instruction.
+//!
+//! The traces are aligned by position, the n-th executed instruction of one run against the n-th
+//! of the other. Register state is rebuilt from the writes of each trace, starting with every
+//! register at 0 like a freshly loaded program.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::trace::TraceEntry;
+
+/// Matching instruction pairs shown before the divergence
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceDiff {
+    /// Number of instructions in each trace
+    pub lengths: (usize, usize),
+    /// Instructions that matched before the divergence, or all of them
+    pub matching: usize,
+    pub divergence: Option<Divergence>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// Position of the first instruction that differs, 0-based
+    pub index: usize,
+    /// The instructions at `index`, none for a trace that already ended
+    pub a: Option<TraceEntry>,
+    pub b: Option<TraceEntry>,
+    pub kinds: Vec<DivergenceKind>,
+    /// The matching pairs right before the divergence
+    pub context: Vec<(TraceEntry, TraceEntry)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DivergenceKind {
+    /// A different instruction ran, or it continued at a different address
+    ControlFlow,
+    /// Registers holding different values afterwards, with the value in each trace
+    Registers(Vec<(String, i128, i128)>),
+    Flags,
+    /// Memory was read or written at different addresses or with different bytes
+    Memory,
+    /// One of the traces has no more instructions
+    Ended,
+}
+
+pub fn diff(a: &[TraceEntry], b: &[TraceEntry]) -> TraceDiff {
+    let mut registers = (BTreeMap::new(), BTreeMap::new());
+    let mut divergence = None;
+
+    for index in 0..a.len().max(b.len()) {
+        let (Some(left), Some(right)) = (a.get(index), b.get(index)) else {
+            divergence = Some((index, vec![DivergenceKind::Ended]));
+            break;
+        };
+
+        apply(&mut registers.0, left);
+        apply(&mut registers.1, right);
+
+        let mut kinds = Vec::new();
+        if left.address != right.address
+            || left.instruction != right.instruction
+            || left.next != right.next
+        {
+            kinds.push(DivergenceKind::ControlFlow);
+        }
+
+        let changed = changed_registers(&registers.0, &registers.1);
+        if !changed.is_empty() {
+            kinds.push(DivergenceKind::Registers(changed));
+        }
+
+        if left.flags != right.flags {
+            kinds.push(DivergenceKind::Flags);
+        }
+
+        if left.memory != right.memory {
+            kinds.push(DivergenceKind::Memory);
+        }
+
+        if !kinds.is_empty() {
+            divergence = Some((index, kinds));
+            break;
+        }
+    }
+
+    let lengths = (a.len(), b.len());
+    let Some((index, kinds)) = divergence else {
+        return TraceDiff {
+            lengths,
+            matching: a.len(),
+            divergence: None,
+        };
+    };
+
+    let start = index.saturating_sub(CONTEXT);
+    TraceDiff {
+        lengths,
+        matching: index,
+        divergence: Some(Divergence {
+            index,
+            a: a.get(index).cloned(),
+            b: b.get(index).cloned(),
+            kinds,
+            context: a[start..index]
+                .iter()
+                .cloned()
+                .zip(b[start..index].iter().cloned())
+                .collect(),
+        }),
+    }
+}
+
+fn apply(registers: &mut BTreeMap<String, i128>, entry: &TraceEntry) {
+    for write in &entry.registers {
+        registers.insert(write.register.clone(), write.new);
+    }
+}
+
+/// Registers that are not written by a trace still hold their initial 0
+fn changed_registers(
+    a: &BTreeMap<String, i128>,
+    b: &BTreeMap<String, i128>,
+) -> Vec<(String, i128, i128)> {
+    let mut names = a.keys().chain(b.keys()).collect::<Vec<&String>>();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let left = a.get(name).copied().unwrap_or_default();
+            let right = b.get(name).copied().unwrap_or_default();
+            (left != right).then(|| (name.clone(), left, right))
+        })
+        .collect()
+}
+
+/// One line per instruction, like `0012 line 9  ADD reg rax, imm 1 -> 0013`
+pub fn describe(entry: &TraceEntry) -> String {
+    let line = entry
+        .line
+        .map(|line| format!("line {}", line))
+        .unwrap_or_default();
+    let instruction = format!("{:<5} {}", entry.instruction, entry.operands.join(", "));
+    format!(
+        "{:04} {:<8} {} -> {:04}",
+        entry.address,
+        line,
+        instruction.trim_end(),
+        entry.next
+    )
+}
+
+impl fmt::Display for DivergenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DivergenceKind::ControlFlow => write!(f, "control flow"),
+            DivergenceKind::Registers(registers) => {
+                let registers = registers
+                    .iter()
+                    .map(|(name, a, b)| format!("{} {} / {}", name, a, b))
+                    .collect::<Vec<String>>();
+                write!(f, "registers {}", registers.join(", "))
+            }
+            DivergenceKind::Flags => write!(f, "flags"),
+            DivergenceKind::Memory => write!(f, "memory"),
+            DivergenceKind::Ended => write!(f, "trace ended"),
+        }
+    }
+}
+
+impl fmt::Display for TraceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(divergence) = &self.divergence else {
+            return writeln!(f, "Traces match, {} instructions", self.matching);
+        };
+
+        writeln!(
+            f,
+            "Traces diverge at instruction {} ({} and {} instructions in total)",
+            divergence.index + 1,
+            self.lengths.0,
+            self.lengths.1
+        )?;
+        for kind in &divergence.kinds {
+            writeln!(f, "  {}", kind)?;
+        }
+
+        writeln!(f)?;
+        for (a, b) in &divergence.context {
+            writeln!(f, "  = {}", describe(a))?;
+            if describe(a) != describe(b) {
+                writeln!(f, "  = {}", describe(b))?;
+            }
+        }
+
+        let side = |entry: &Option<TraceEntry>| match entry {
+            Some(entry) => format!("tick {:<5} {}", entry.tick, describe(entry)),
+            None => "(ended)".to_string(),
+        };
+        writeln!(f, "  a {}", side(&divergence.a))?;
+        writeln!(f, "  b {}", side(&divergence.b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::trace::{AccessKind, MemoryAccess, RegisterWrite};
+    use super::super::Flags;
+    use super::*;
+
+    fn entry(address: usize, instruction: &str) -> TraceEntry {
+        TraceEntry {
+            tick: address,
+            address,
+            line: None,
+            instruction: instruction.to_string(),
+            operands: Vec::new(),
+            registers: Vec::new(),
+            flags: Flags::default(),
+            memory: Vec::new(),
+            next: address + 1,
+        }
+    }
+
+    fn write_register(mut entry: TraceEntry, register: &str, new: i128) -> TraceEntry {
+        entry.registers.push(RegisterWrite {
+            register: register.to_string(),
+            old: 0,
+            new,
+        });
+        entry
+    }
+
+    fn write_memory(mut entry: TraceEntry, address: usize, bytes: &str) -> TraceEntry {
+        entry.memory.push(MemoryAccess {
+            kind: AccessKind::Write,
+            address,
+            bytes: bytes.to_string(),
+        });
+        entry
+    }
+
+    fn trace() -> Vec<TraceEntry> {
+        vec![
+            write_register(entry(0, "Load"), "rax", 1),
+            write_memory(entry(1, "Store"), 0, "01"),
+            entry(2, "Nop"),
+            entry(3, "Nop"),
+            entry(4, "Nop"),
+            entry(5, "Exit"),
+        ]
+    }
+
+    #[test]
+    fn identical_traces_match() {
+        let result = diff(&trace(), &trace());
+        assert_eq!(result.lengths, (6, 6));
+        assert_eq!(result.matching, 6);
+        assert_eq!(result.divergence, None);
+        assert_eq!(result.to_string(), "Traces match, 6 instructions\n");
+    }
+
+    #[test]
+    fn register_divergence_lists_both_values() {
+        let mut b = trace();
+        b[4] = write_register(entry(4, "Nop"), "rax", 2);
+
+        let result = diff(&trace(), &b);
+        assert_eq!(result.matching, 4);
+        let divergence = result.divergence.unwrap();
+        assert_eq!(divergence.index, 4);
+        assert_eq!(
+            divergence.kinds,
+            [DivergenceKind::Registers(vec![("rax".to_string(), 1, 2)])]
+        );
+        assert_eq!(divergence.a, Some(trace()[4].clone()));
+        assert_eq!(divergence.b, Some(b[4].clone()));
+        let context = divergence
+            .context
+            .iter()
+            .map(|(a, _)| a.address)
+            .collect::<Vec<usize>>();
+        assert_eq!(context, [1, 2, 3]);
+    }
+
+    #[test]
+    fn registers_that_were_never_written_are_zero() {
+        let mut b = trace();
+        b[2] = write_register(entry(2, "Nop"), "rbx", 0);
+        assert_eq!(diff(&trace(), &b).divergence, None);
+    }
+
+    #[test]
+    fn memory_divergence_is_found() {
+        let mut b = trace();
+        b[1] = write_memory(entry(1, "Store"), 0, "02");
+
+        let divergence = diff(&trace(), &b).divergence.unwrap();
+        assert_eq!(divergence.index, 1);
+        assert_eq!(divergence.kinds, [DivergenceKind::Memory]);
+        assert_eq!(divergence.context.len(), 1);
+    }
+
+    #[test]
+    fn control_flow_divergence_is_found() {
+        let mut b = trace();
+        b[3].next = 5;
+
+        let divergence = diff(&trace(), &b).divergence.unwrap();
+        assert_eq!(divergence.index, 3);
+        assert_eq!(divergence.kinds, [DivergenceKind::ControlFlow]);
+    }
+
+    #[test]
+    fn a_shorter_trace_ends_first() {
+        let b = trace()[..4].to_vec();
+
+        let result = diff(&trace(), &b);
+        assert_eq!(result.lengths, (6, 4));
+        assert_eq!(result.matching, 4);
+        let divergence = result.divergence.as_ref().unwrap();
+        assert_eq!(divergence.index, 4);
+        assert_eq!(divergence.kinds, [DivergenceKind::Ended]);
+        assert_eq!(divergence.a, Some(trace()[4].clone()));
+        assert_eq!(divergence.b, None);
+
+        let text = result.to_string();
+        assert!(text.starts_with(
+            "Traces diverge at instruction 5 (6 and 4 instructions in total)\n  trace ended\n"
+        ));
+        assert!(text.ends_with("  b (ended)\n"));
+    }
+
+    #[test]
+    fn entries_are_described_on_one_line() {
+        let mut entry = entry(12, "Add");
+        entry.line = Some(9);
+        entry.operands = vec!["reg rax".to_string(), "imm 1".to_string()];
+        assert_eq!(
+            describe(&entry),
+            "0012 line 9   Add   reg rax, imm 1 -> 0013"
+        );
+    }
+}
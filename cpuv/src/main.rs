@@ -2,72 +2,130 @@ mod config;
 mod core;
 mod ui;
 
+use core::engine::trace::read_trace;
+use core::engine::trace_diff::diff;
 use core::engine::Engine;
 
-use clap::Parser;
-use config::RootConfig;
-use std::{process::exit, thread};
+use clap::{Parser, Subcommand};
+use config::{load_config, watch_config, writable_config_path, ConfigOverrides, RootConfig};
+use std::{
+    path::{Path, PathBuf},
+    process::exit,
+    sync::mpsc,
+    thread,
+};
 
 const FPS: u64 = 60;
 
 fn main() {
     let args = CliArgs::parse();
-    let options = parse_config(args.config_path);
+    if let Some(Command::Trace {
+        action: TraceCommand::Diff { a, b },
+    }) = &args.command
+    {
+        exit(diff_traces(a, b));
+    }
+
+    let overrides = ConfigOverrides {
+        config_path: args.config_path,
+        program_path: args.file,
+        write_out: args.write_out,
+    };
+    let loaded = load_config(&overrides).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+
+    if let Some(Command::Config {
+        action: ConfigCommand::Show,
+    }) = args.command
+    {
+        print!("{}", loaded.show());
+        return;
+    }
 
+    for warning in &loaded.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let config = loaded.config.clone();
+    let config_path = writable_config_path(&overrides);
     let (engine, client_command_sender, engine_data_reciever, stdlog_reciever) =
-        Engine::new(options);
+        Engine::new(loaded.config);
+
+    let (config_sender, config_reciever) = mpsc::channel();
+    watch_config(overrides, client_command_sender.clone(), config_sender);
 
     thread::spawn(move || {
         engine.run();
     });
 
-    let _ = ui::window::init(client_command_sender, engine_data_reciever, stdlog_reciever);
+    let _ = ui::window::init(
+        config,
+        config_path,
+        client_command_sender,
+        engine_data_reciever,
+        stdlog_reciever,
+        config_reciever,
+    );
+}
+
+/// Prints where two traces diverge. Exits with 0 when they match, 1 when they diverge and 2 when a
+/// trace could not be read, like `diff`.
+fn diff_traces(a: &Path, b: &Path) -> i32 {
+    let traces = read_trace(a).and_then(|a| Ok((a, read_trace(b)?)));
+    match traces {
+        Ok((a, b)) => {
+            let diff = diff(&a, &b);
+            print!("{}", diff);
+            diff.divergence.is_some() as i32
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
 #[command(version)]
 pub struct CliArgs {
-    #[arg(short, long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short, long, global = true)]
     config_path: Option<String>,
 
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     file: Option<String>,
 
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     write_out: Option<bool>,
 }
 
-pub fn parse_config(config_path: Option<String>) -> RootConfig {
-    let mut config = RootConfig::default();
-    let home_config = std::fs::read_to_string("~/.config/cpuv/config.toml");
-
-    if config_path.is_none() && home_config.is_err() {
-        println!("Config not specified, Defaulting.",);
-        return config;
-    } else if home_config.is_ok() {
-        let tmp = toml::from_str::<config::RootConfig>(&home_config.unwrap());
-        if let Err(ref e) = tmp {
-            eprintln!("{}", e);
-            exit(1);
-        }
-        config = tmp.unwrap();
-    } else if config_path.is_some() {
-        let config_path = config_path.clone().unwrap();
-        let raw = std::fs::read_to_string(&config_path);
-
-        if raw.is_err() {
-            eprintln!("Config file not found at {:?}. Exiting...", config_path);
-            exit(1);
-        }
-
-        let tmp = toml::from_str::<config::RootConfig>(&raw.unwrap());
-        if let Err(ref e) = tmp {
-            eprintln!("{}", e);
-            exit(1);
-        }
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Work with execution traces written with `program.output-trace`
+    Trace {
+        #[command(subcommand)]
+        action: TraceCommand,
+    },
+}
 
-        config = tmp.unwrap();
-    };
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the effective config and where each value comes from
+    Show,
+}
 
-    config
+#[derive(Subcommand, Debug)]
+pub enum TraceCommand {
+    /// Show the first instruction where two JSON Lines traces differ in control flow, registers
+    /// or flags
+    Diff { a: PathBuf, b: PathBuf },
 }
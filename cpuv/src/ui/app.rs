@@ -1,12 +1,27 @@
 use egui_code_editor::{ColorTheme, Syntax};
 use egui_file_dialog::FileDialog;
-use std::{sync::mpsc, time::Duration};
+use std::{
+    path::PathBuf,
+    sync::{mpsc, Arc},
+    time::Duration,
+};
 
-use egui::Ui;
+use egui::{
+    epaint::text::cursor::PCursor, text_edit::TextEditOutput, CursorIcon, Rect, RichText,
+    ScrollArea, Sense, Ui,
+};
+use irv::{AsmFrontend, Frontend, Program, BINARY_EXTENSION};
 
-use super::{center_pannel, sidebar, text_editor};
+use super::log::LogView;
+use super::trace_diff::TraceDiffView;
+use super::{center_pannel, debugger, settings, sidebar, text_editor, theme};
 use crate::{
-    core::engine::{ClientCommands, EngineData, StdLogMessage},
+    config::{ConfigReload, RootConfig},
+    core::engine::{
+        publisher::StateReceiver, BreakpointSpec, ClientCommandType, ClientCommands, EngineData,
+        EngineRunningState, StdLogLevel, StdLogMessage, WatchKind, WatchpointSpec,
+        SNAPSHOT_EXTENSION,
+    },
     FPS,
 };
 
@@ -15,6 +30,8 @@ pub enum ParsingResultViewOptions {
     Raw,
     #[default]
     Tabled,
+    IR,
+    Binary,
     None,
 }
 
@@ -24,54 +41,146 @@ pub struct UiOptions {
 }
 
 pub struct UiApp {
-    pub data_recv: mpsc::Receiver<EngineData>,
+    pub data_recv: StateReceiver,
     pub command_sender: mpsc::Sender<ClientCommands>,
     pub stdlog_reciever: mpsc::Receiver<StdLogMessage>,
+    config_reciever: mpsc::Receiver<ConfigReload>,
+
+    pub config: RootConfig,
+    /// `config` as it was loaded, saving only writes the keys that were changed since
+    pub saved_config: RootConfig,
+    /// File the settings panel saves `config` to
+    pub config_path: PathBuf,
+    /// Set when `config` changed and has to be applied to the egui context
+    config_changed: bool,
+    code_theme: ColorTheme,
 
     pub previous_data: EngineData,
     pub sidebar_shown: bool,
 
     file_dialog: FileDialog,
+    save_dialog: FileDialog,
+    snapshot_dialog: FileDialog,
     file_path: Option<String>,
 
     pub code: String,
+    /// irv emitted from the program of another frontend, regenerated when the program changes
+    generated: Option<(Arc<Program>, String)>,
+    /// 1-based line of the cursor in the source editor, used by "Run To Cursor"
+    pub cursor_line: Option<usize>,
+    /// Breakpoints by source line, toggled from the editor gutter
+    pub breakpoints: Vec<BreakpointSpec>,
+    pub watchpoints: Vec<WatchpointSpec>,
+    /// The watchpoint being entered in the debugger panel
+    pub new_watchpoint: WatchpointSpec,
+    pub trace_diff: TraceDiffView,
 
-    pub system_logs: Vec<String>,
-    pub stdout: Vec<String>,
+    pub system_logs: LogView,
+    pub stdout: LogView,
 
     pub ui_opts: UiOptions,
 }
 
 impl UiApp {
     pub fn new(
+        config: RootConfig,
+        config_path: PathBuf,
         command_sender: mpsc::Sender<ClientCommands>,
-        data_recv: mpsc::Receiver<EngineData>,
+        data_recv: StateReceiver,
         stdlog_reciever: mpsc::Receiver<StdLogMessage>,
+        config_reciever: mpsc::Receiver<ConfigReload>,
     ) -> Self {
         Self {
             data_recv,
             command_sender,
             stdlog_reciever,
+            config_reciever,
+            saved_config: config.clone(),
+            config,
+            config_path,
+            config_changed: true,
+            code_theme: ColorTheme::GRUVBOX_DARK,
             sidebar_shown: true,
             previous_data: EngineData::default(),
             code: "".to_string(),
+            generated: None,
+            cursor_line: None,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            new_watchpoint: WatchpointSpec {
+                target: String::new(),
+                kind: WatchKind::Change,
+            },
+            trace_diff: TraceDiffView::default(),
             file_dialog: FileDialog::new(),
+            save_dialog: FileDialog::new().default_file_name("program.irvb"),
+            snapshot_dialog: FileDialog::new().default_file_name("program.cpuvs"),
             file_path: None,
-            system_logs: vec!["".to_string()],
-            stdout: vec!["".to_string()],
+            system_logs: LogView::new("System Logs"),
+            stdout: LogView::new("Stdout"),
             ui_opts: Default::default(),
         }
     }
 
     pub fn show_code_editor(&mut self, ui: &mut Ui, _ctx: &egui::Context) {
-        egui_code_editor::CodeEditor::default()
-            .id_source("Code Editor")
-            .with_rows(20)
-            .with_fontsize(14.0)
-            .with_theme(ColorTheme::GRUVBOX_DARK)
-            .with_syntax(Syntax::asm())
-            .with_numlines(true)
-            .show(ui, &mut self.code);
+        let frontend = &self.previous_data.frontend;
+        let current_line = self.previous_data.current_line;
+        let (theme, font_size) = (self.code_theme, self.config.ui.font_size as f32);
+        let is_asm = frontend.is_empty() || frontend == AsmFrontend.name();
+
+        // languages other than the assembly syntax are shown next to the irv they compile to
+        let mut generated = match &self.previous_data.program {
+            Some(program) if !is_asm => {
+                let cached = self
+                    .generated
+                    .as_ref()
+                    .is_some_and(|(generated_from, _)| Arc::ptr_eq(generated_from, program));
+                if !cached {
+                    self.generated = Some((Arc::clone(program), irv::emit(program)));
+                }
+                self.generated
+                    .as_ref()
+                    .map_or("", |(_, text)| text.as_str())
+            }
+            _ => {
+                let (output, toggled) = source_editor(
+                    ui,
+                    code_editor("Code Editor", Syntax::asm(), theme, font_size),
+                    &mut self.code,
+                    &mut self.breakpoints,
+                    current_line,
+                );
+                self.source_edited(&output, toggled);
+                return;
+            }
+        };
+
+        let (output, toggled) = ui.columns(2, |columns| {
+            columns[0].label(RichText::new(frontend).strong());
+            let source = source_editor(
+                &mut columns[0],
+                code_editor("Code Editor", Syntax::rust(), theme, font_size),
+                &mut self.code,
+                &mut self.breakpoints,
+                current_line,
+            );
+
+            columns[1].label(RichText::new("Generated irv").strong());
+            code_editor("Generated irv", Syntax::asm(), theme, font_size)
+                .show(&mut columns[1], &mut generated);
+            source
+        });
+        self.source_edited(&output, toggled);
+    }
+
+    fn source_edited(&mut self, output: &TextEditOutput, breakpoints_toggled: bool) {
+        if let Some(range) = output.cursor_range {
+            self.cursor_line = Some(range.primary.pcursor.paragraph + 1);
+        }
+
+        if breakpoints_toggled {
+            debugger::send_breakpoints(self);
+        }
     }
 
     pub fn show_file_picker(&mut self, ctx: &eframe::egui::Context, ui: &mut egui::Ui) {
@@ -88,20 +197,250 @@ impl UiApp {
 
             self.file_path = Some(path.to_string_lossy().to_string());
 
+            // binaries are loaded by the engine, the disassembly replaces the code once it is done
+            if path.extension().is_some_and(|ext| ext == BINARY_EXTENSION) {
+                let _ = self.command_sender.send(ClientCommands {
+                    command_type: ClientCommandType::LoadBinary,
+                    payload: self.file_path.clone(),
+                });
+                return;
+            }
+
+            // snapshots bring their own program, its source replaces the code once restored
+            if path
+                .extension()
+                .is_some_and(|ext| ext == SNAPSHOT_EXTENSION)
+            {
+                let _ = self.command_sender.send(ClientCommands {
+                    command_type: ClientCommandType::LoadSnapshot,
+                    payload: self.file_path.clone(),
+                });
+                return;
+            }
+
+            let _ = self.command_sender.send(ClientCommands {
+                command_type: ClientCommandType::SelectFrontend,
+                payload: self.file_path.clone(),
+            });
+
             if let Ok(file) = std::fs::read_to_string(path) {
                 self.code = file;
             }
         }
     }
+
+    pub fn show_save_binary(&mut self, ctx: &eframe::egui::Context, ui: &mut egui::Ui) {
+        if ui
+            .add_enabled(
+                self.previous_data.program.is_some(),
+                egui::Button::new("Save Binary"),
+            )
+            .clicked()
+        {
+            self.save_dialog.save_file();
+        }
+
+        self.save_dialog.update(ctx);
+        if let Some(path) = self.save_dialog.take_picked() {
+            let _ = self.command_sender.send(ClientCommands {
+                command_type: ClientCommandType::SaveBinary,
+                payload: Some(
+                    path.with_extension(BINARY_EXTENSION)
+                        .to_string_lossy()
+                        .to_string(),
+                ),
+            });
+        }
+    }
+
+    pub fn show_save_snapshot(&mut self, ctx: &eframe::egui::Context, ui: &mut egui::Ui) {
+        if ui
+            .add_enabled(
+                self.previous_data.engine_running_state != EngineRunningState::Stopped,
+                egui::Button::new("Save Snapshot"),
+            )
+            .on_hover_text("Save the state of the running program, open the file to restore it")
+            .clicked()
+        {
+            self.snapshot_dialog.save_file();
+        }
+
+        self.snapshot_dialog.update(ctx);
+        if let Some(path) = self.snapshot_dialog.take_picked() {
+            let _ = self.command_sender.send(ClientCommands {
+                command_type: ClientCommandType::SaveSnapshot,
+                payload: Some(
+                    path.with_extension(SNAPSHOT_EXTENSION)
+                        .to_string_lossy()
+                        .to_string(),
+                ),
+            });
+        }
+    }
+}
+
+/// Shows the source editor with a breakpoint gutter over its line numbers, and highlights the
+/// line the program is paused at. Returns the editor output and whether a breakpoint was toggled.
+fn source_editor(
+    ui: &mut Ui,
+    editor: egui_code_editor::CodeEditor,
+    code: &mut String,
+    breakpoints: &mut Vec<BreakpointSpec>,
+    current_line: Option<usize>,
+) -> (TextEditOutput, bool) {
+    // the gutter is painted inside the same scroll area as the editor so that it scrolls with it
+    ScrollArea::vertical()
+        .id_salt("Code Editor scroll")
+        .show(ui, |ui| {
+            let output = editor.vscroll(false).show(ui, code);
+            let toggled = breakpoint_gutter(ui, &output, breakpoints, current_line);
+            (output, toggled)
+        })
+        .inner
+}
+
+fn breakpoint_gutter(
+    ui: &mut Ui,
+    output: &TextEditOutput,
+    breakpoints: &mut Vec<BreakpointSpec>,
+    current_line: Option<usize>,
+) -> bool {
+    let editor = output.response.rect;
+    let gutter = Rect::from_x_y_ranges(ui.min_rect().left()..=editor.left(), editor.y_range());
+    let last_line = output.galley.end().pcursor.paragraph + 1;
+
+    // screen rect of the first row of a line
+    let row = |line: usize| {
+        let pcursor = PCursor {
+            paragraph: line - 1,
+            offset: 0,
+            prefer_next_row: false,
+        };
+        output
+            .galley
+            .pos_from_pcursor(pcursor)
+            .translate(output.galley_pos.to_vec2())
+    };
+
+    let mut toggled = false;
+    let response = ui
+        .interact(
+            gutter,
+            output.response.id.with("breakpoints"),
+            Sense::click(),
+        )
+        .on_hover_cursor(CursorIcon::PointingHand);
+    if let Some(pos) = response
+        .interact_pointer_pos()
+        .filter(|_| response.clicked())
+    {
+        let line = output
+            .galley
+            .cursor_from_pos(pos - output.galley_pos)
+            .pcursor
+            .paragraph
+            + 1;
+        // clicks below the last line land on it as well
+        if row(line).y_range().contains(pos.y) {
+            match breakpoints
+                .iter()
+                .position(|breakpoint| breakpoint.line == line)
+            {
+                Some(index) => {
+                    breakpoints.remove(index);
+                }
+                None => {
+                    breakpoints.push(BreakpointSpec {
+                        line,
+                        condition: String::new(),
+                    });
+                    breakpoints.sort_by_key(|breakpoint| breakpoint.line);
+                }
+            }
+            toggled = true;
+        }
+    }
+
+    let painter = ui.painter();
+    if let Some(line) = current_line.filter(|line| (1..=last_line).contains(line)) {
+        let highlight = Rect::from_x_y_ranges(editor.x_range(), row(line).y_range());
+        painter.rect_filled(
+            highlight,
+            0.0,
+            ui.visuals().warn_fg_color.gamma_multiply(0.2),
+        );
+    }
+
+    for breakpoint in breakpoints.iter().filter(|b| b.line <= last_line) {
+        let rect = row(breakpoint.line);
+        let radius = rect.height() * 0.3;
+        let center = egui::pos2(gutter.left() + radius + 1.0, rect.center().y);
+        // conditional breakpoints are told apart by their color
+        let color = match breakpoint.condition.trim() {
+            "" => ui.visuals().error_fg_color,
+            _ => ui.visuals().warn_fg_color,
+        };
+        painter.circle_filled(center, radius, color);
+    }
+
+    toggled
+}
+
+fn code_editor(
+    id: &str,
+    syntax: Syntax,
+    theme: ColorTheme,
+    font_size: f32,
+) -> egui_code_editor::CodeEditor {
+    egui_code_editor::CodeEditor::default()
+        .id_source(id)
+        .with_rows(20)
+        .with_fontsize(font_size)
+        .with_theme(theme)
+        .with_syntax(syntax)
+        .with_numlines(true)
 }
 
 impl eframe::App for UiApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         ctx.request_repaint_after(Duration::from_millis(1000 / FPS));
-        if let Ok(data) = self.data_recv.try_recv() {
+        if let Ok(reload) = self.config_reciever.try_recv() {
+            match reload {
+                Ok(config) => {
+                    self.saved_config = config.clone();
+                    self.config = config;
+                    self.config_changed = true;
+                }
+                Err(e) => self
+                    .system_logs
+                    .push(StdLogLevel::ERROR, format!("Config reload failed! {}", e)),
+            }
+        }
+
+        if self.config_changed {
+            theme::apply(ctx, &self.config.ui);
+            self.code_theme = theme::code_theme(&self.config.ui);
+            self.config_changed = false;
+        }
+
+        // states are coalesced by the engine, the ones left are the responses and the latest state
+        for data in self.data_recv.drain() {
+            if data.responding_to == Some(ClientCommandType::LoadBinary) {
+                if let Some(program) = &data.program {
+                    self.code = irv::emit(program);
+                }
+            }
+            if data.responding_to == Some(ClientCommandType::LoadSnapshot) {
+                match (&data.source, &data.program) {
+                    (Some(source), _) => self.code = source.clone(),
+                    (None, Some(program)) => self.code = irv::emit(program),
+                    (None, None) => {}
+                }
+            }
             self.previous_data = data;
         }
 
+        settings::render(self, ctx);
         sidebar::render(self, ctx);
         text_editor::render(self, ctx);
         center_pannel::render(self, ctx);
@@ -0,0 +1,243 @@
+use egui::{ComboBox, Grid, ProgressBar, RichText, Slider};
+
+use super::app::UiApp;
+use crate::core::engine::{
+    ClientCommandType, ClientCommands, DebugPointStatus, EngineRunningState, WatchKind,
+};
+
+/// Timeline, breakpoint conditions, watchpoints, their hit counts and why the program stopped
+pub fn render(app: &mut UiApp, ui: &mut egui::Ui) {
+    egui::CollapsingHeader::new(RichText::new("Debugger").strong().size(24.0))
+        .default_open(true)
+        .show(ui, |ui| {
+            if app.previous_data.engine_running_state != EngineRunningState::Running {
+                if let Some(reason) = &app.previous_data.stop_reason {
+                    ui.label(RichText::new(format!("Stopped: {}", reason)).strong());
+                }
+            }
+
+            render_performance(app, ui);
+            render_stall(app, ui);
+            ui.add_space(5.0);
+            render_timeline(app, ui);
+            ui.add_space(5.0);
+            render_breakpoints(app, ui);
+            ui.add_space(10.0);
+            render_watchpoints(app, ui);
+        });
+}
+
+/// Cycles spent so far and the average cycles per instruction
+fn render_performance(app: &UiApp, ui: &mut egui::Ui) {
+    let data = &app.previous_data;
+    let cpi = data
+        .cpi()
+        .map_or("-".to_string(), |cpi| format!("{:.2}", cpi));
+
+    ui.label(format!(
+        "{} cycles, {} instructions, CPI {}",
+        data.cycles, data.instructions, cpi
+    ))
+    .on_hover_text("Instruction latencies are set in engine.cycle-costs of the config");
+}
+
+/// How long the next instruction still waits for the heap, while heap access simulation is on
+fn render_stall(app: &UiApp, ui: &mut egui::Ui) {
+    let Some(stall) = app
+        .previous_data
+        .stall
+        .filter(|stall| stall.address == app.previous_data.instruction_ptr)
+    else {
+        return;
+    };
+
+    let waited = stall.ticks - stall.remaining;
+    ui.horizontal(|ui| {
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            format!("Waiting for memory at {:04}", stall.address),
+        );
+        ui.add(
+            ProgressBar::new(waited as f32 / stall.ticks as f32)
+                .text(format!("{} / {} ticks", waited, stall.ticks)),
+        )
+        .on_hover_text(
+            "Registers are read right away, the heap takes engine.heap-access-simulation ticks",
+        );
+    });
+}
+
+/// Scrubber over the recorded instructions, dragging it moves the program back and forth
+fn render_timeline(app: &mut UiApp, ui: &mut egui::Ui) {
+    let data = &app.previous_data;
+    let mut position = data.history_position;
+
+    ui.horizontal(|ui| {
+        ui.label("Timeline");
+        let slider = ui
+            .add_enabled(
+                data.history_len > 0,
+                Slider::new(&mut position, 0..=data.history_len).suffix(" instructions"),
+            )
+            .on_hover_text("Instructions recorded since the program was started");
+
+        if slider.changed() {
+            let _ = app.command_sender.send(ClientCommands {
+                command_type: ClientCommandType::SeekHistory,
+                payload: Some(position.to_string()),
+            });
+        }
+    });
+}
+
+fn render_breakpoints(app: &mut UiApp, ui: &mut egui::Ui) {
+    if app.breakpoints.is_empty() {
+        ui.label("Click next to a line number to add a breakpoint");
+        return;
+    }
+
+    let mut changed = false;
+    let mut removed = None;
+
+    Grid::new("Breakpoints").striped(true).show(ui, |ui| {
+        ui.label(RichText::new("Line").strong());
+        ui.label(RichText::new("Condition").strong());
+        ui.label(RichText::new("Hits").strong());
+        ui.end_row();
+
+        for (index, breakpoint) in app.breakpoints.iter_mut().enumerate() {
+            // the status is looked up by position, the condition may be edited but not yet sent
+            let status = app
+                .previous_data
+                .breakpoints
+                .get(index)
+                .filter(|status| status.spec.line == breakpoint.line);
+
+            ui.label(breakpoint.line.to_string());
+            let condition = ui.add(
+                egui::TextEdit::singleline(&mut breakpoint.condition)
+                    .hint_text("always, or like rax > 10 && zf")
+                    .desired_width(180.0),
+            );
+            changed |= condition.lost_focus();
+            show_status(ui, status);
+
+            if ui.small_button("Remove").clicked() {
+                removed = Some(index);
+            }
+            ui.end_row();
+        }
+    });
+
+    if let Some(index) = removed {
+        app.breakpoints.remove(index);
+        changed = true;
+    }
+
+    if changed {
+        send_breakpoints(app);
+    }
+}
+
+fn render_watchpoints(app: &mut UiApp, ui: &mut egui::Ui) {
+    let mut removed = None;
+
+    if !app.watchpoints.is_empty() {
+        Grid::new("Watchpoints").striped(true).show(ui, |ui| {
+            ui.label(RichText::new("Watching").strong());
+            ui.label(RichText::new("On").strong());
+            ui.label(RichText::new("Hits").strong());
+            ui.end_row();
+
+            for (index, watchpoint) in app.watchpoints.iter().enumerate() {
+                let status = app
+                    .previous_data
+                    .watchpoints
+                    .get(index)
+                    .filter(|status| &status.spec == watchpoint);
+
+                ui.monospace(&watchpoint.target);
+                ui.label(format!("{:?}", watchpoint.kind));
+                show_status(ui, status);
+
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(index);
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    let mut added = false;
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut app.new_watchpoint.target)
+                .hint_text("rax, a variable or mem[0..8]")
+                .desired_width(180.0),
+        );
+
+        ComboBox::from_id_salt("Watch kind")
+            .selected_text(format!("{:?}", app.new_watchpoint.kind))
+            .show_ui(ui, |ui| {
+                for kind in [WatchKind::Change, WatchKind::Write] {
+                    ui.selectable_value(&mut app.new_watchpoint.kind, kind, format!("{:?}", kind));
+                }
+            });
+
+        let target = app.new_watchpoint.target.trim();
+        added = ui
+            .add_enabled(!target.is_empty(), egui::Button::new("Watch"))
+            .clicked();
+    });
+
+    if added {
+        let mut watchpoint = app.new_watchpoint.clone();
+        watchpoint.target = watchpoint.target.trim().to_string();
+        app.watchpoints.push(watchpoint);
+        app.new_watchpoint.target.clear();
+    }
+
+    if let Some(index) = removed {
+        app.watchpoints.remove(index);
+    }
+
+    if added || removed.is_some() {
+        send_watchpoints(app);
+    }
+}
+
+/// Hit count, with the error that keeps the breakpoint or watchpoint from working instead if any
+fn show_status<T>(ui: &mut egui::Ui, status: Option<&DebugPointStatus<T>>) {
+    match status {
+        Some(DebugPointStatus {
+            error: Some(error), ..
+        }) => {
+            ui.colored_label(ui.visuals().error_fg_color, "Error")
+                .on_hover_text(error);
+        }
+        Some(status) => {
+            ui.label(status.hits.to_string());
+        }
+        None => {
+            ui.label("-");
+        }
+    }
+}
+
+pub fn send_breakpoints(app: &UiApp) {
+    if let Ok(payload) = serde_json::to_string(&app.breakpoints) {
+        let _ = app.command_sender.send(ClientCommands {
+            command_type: ClientCommandType::SetBreakpoints,
+            payload: Some(payload),
+        });
+    }
+}
+
+fn send_watchpoints(app: &UiApp) {
+    if let Ok(payload) = serde_json::to_string(&app.watchpoints) {
+        let _ = app.command_sender.send(ClientCommands {
+            command_type: ClientCommandType::SetWatchpoints,
+            payload: Some(payload),
+        });
+    }
+}
@@ -1,15 +1,127 @@
-use egui::{RichText, TopBottomPanel};
+use std::collections::VecDeque;
+
+use egui::{RichText, ScrollArea, TextStyle, TopBottomPanel};
 
 use crate::core::engine::StdLogLevel;
 
 use super::app::UiApp;
 
+/// Lines kept per log, the oldest are dropped once there are more
+const MAX_LINES: usize = 10_000;
+
+/// A bounded log along with how it is shown: the search, level filters and auto-scroll
+pub struct LogView {
+    id: &'static str,
+    lines: VecDeque<(StdLogLevel, String)>,
+    search: String,
+    show_info: bool,
+    show_warn: bool,
+    show_error: bool,
+    /// Keeps the newest line in view, turned off to read older ones while lines come in
+    auto_scroll: bool,
+}
+
+impl LogView {
+    pub fn new(id: &'static str) -> Self {
+        Self {
+            id,
+            lines: VecDeque::new(),
+            search: String::new(),
+            show_info: true,
+            show_warn: true,
+            show_error: true,
+            auto_scroll: true,
+        }
+    }
+
+    pub fn push(&mut self, level: StdLogLevel, message: String) {
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((level, message));
+    }
+
+    /// Lines passing the level filters and containing the search, ignoring case
+    fn visible(&self) -> Vec<&(StdLogLevel, String)> {
+        let search = self.search.to_lowercase();
+        self.lines
+            .iter()
+            .filter(|(level, _)| match level {
+                StdLogLevel::INFO => self.show_info,
+                StdLogLevel::WARN => self.show_warn,
+                StdLogLevel::ERROR => self.show_error,
+                StdLogLevel::UserPrint => true,
+            })
+            .filter(|(_, message)| search.is_empty() || message.to_lowercase().contains(&search))
+            .collect()
+    }
+
+    fn render(&mut self, ui: &mut egui::Ui, levels: bool) {
+        ui.horizontal_wrapped(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.search)
+                    .hint_text("Search")
+                    .desired_width(150.0),
+            );
+            if levels {
+                ui.checkbox(&mut self.show_info, "Info");
+                ui.checkbox(&mut self.show_warn, "Warn");
+                ui.checkbox(&mut self.show_error, "Error");
+            }
+            ui.checkbox(&mut self.auto_scroll, "Auto-scroll");
+
+            if ui
+                .button("Copy")
+                .on_hover_text("Copy the shown lines")
+                .clicked()
+            {
+                let text = self
+                    .visible()
+                    .iter()
+                    .map(|(_, message)| message.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n");
+                ui.ctx().copy_text(text);
+            }
+            if ui.button("Clear").clicked() {
+                self.lines.clear();
+            }
+        });
+
+        let visible = self.visible();
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        let (warn, error) = (ui.visuals().warn_fg_color, ui.visuals().error_fg_color);
+
+        egui::Frame::none()
+            .fill(ui.visuals().extreme_bg_color)
+            .show(ui, |ui| {
+                ScrollArea::both()
+                    .id_salt(self.id)
+                    .auto_shrink(false)
+                    .stick_to_bottom(self.auto_scroll)
+                    .show_rows(ui, row_height, visible.len(), |ui, rows| {
+                        // only the rows in view are laid out, the log can hold thousands of lines
+                        for (level, message) in &visible[rows] {
+                            let text = RichText::new(message).monospace();
+                            match level {
+                                StdLogLevel::WARN => ui.label(text.color(warn)),
+                                StdLogLevel::ERROR => ui.label(text.color(error)),
+                                _ => ui.label(text),
+                            };
+                        }
+                    });
+            });
+    }
+}
+
 pub fn render(app: &mut UiApp, ctx: &egui::Context, ui: &mut egui::Ui) {
-    if let Ok(log_message) = app.stdlog_reciever.try_recv() {
+    // everything sent since the last frame, so that bursts of output show up right away
+    while let Ok(log_message) = app.stdlog_reciever.try_recv() {
         if log_message.log_level == StdLogLevel::UserPrint {
-            app.stdout.push(log_message.message);
+            app.stdout.push(log_message.log_level, log_message.message);
         } else {
-            app.system_logs.push(log_message.message);
+            app.system_logs
+                .push(log_message.log_level, log_message.message);
         }
     }
 
@@ -23,33 +135,12 @@ pub fn render(app: &mut UiApp, ctx: &egui::Context, ui: &mut egui::Ui) {
                 ui.add_space(10.0);
             });
 
-            ui.horizontal(|ui| {
-                ui.label("System Logs");
-                ui.add_space(ui.available_width() * 0.481);
-                ui.label("Stdout");
-            });
+            ui.columns(2, |columns| {
+                columns[0].label("System Logs");
+                app.system_logs.render(&mut columns[0], true);
 
-            // System Logs
-            ui.horizontal(|ui| {
-                ui.vertical(|ui| {
-                    ui.set_width(ui.available_width() * 0.5);
-                    egui::TextEdit::multiline(&mut app.system_logs.join("\n"))
-                        .desired_rows(10)
-                        .desired_width(ui.available_width())
-                        .clip_text(true)
-                        .interactive(false)
-                        .show(ui);
-                });
-
-                // Virtual Stdout
-                ui.vertical(|ui| {
-                    egui::TextEdit::multiline(&mut app.stdout.join("\n"))
-                        .desired_rows(10)
-                        .desired_width(ui.available_width())
-                        .clip_text(true)
-                        .interactive(false)
-                        .show(ui);
-                })
+                columns[1].label("Stdout");
+                app.stdout.render(&mut columns[1], false);
             });
         });
 }
@@ -1,7 +1,11 @@
 pub mod app;
 pub mod center_pannel;
+pub mod debugger;
 pub mod log;
+pub mod settings;
 pub mod sidebar;
 pub mod text_editor;
+pub mod theme;
+pub mod trace_diff;
 pub mod visual;
 pub mod window;
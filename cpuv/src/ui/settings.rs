@@ -0,0 +1,195 @@
+use egui::{ComboBox, RichText, Slider, TopBottomPanel};
+
+use super::app::UiApp;
+use crate::config::{save_config, LogLevel, Rate, Timing};
+use crate::core::engine::{ClientCommandType, ClientCommands, StdLogLevel};
+
+/// Control bar with the engine settings, edits `app.config` and sends every finished change to the
+/// engine
+pub fn render(app: &mut UiApp, ctx: &egui::Context) {
+    TopBottomPanel::top("Settings").show(ctx, |ui| {
+        egui::CollapsingHeader::new(RichText::new("Settings").strong().size(20.0))
+            .default_open(false)
+            .show(ui, |ui| {
+                let mut changed = false;
+
+                ui.horizontal_wrapped(|ui| {
+                    changed |= render_engine_settings(app, ui);
+                });
+
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Save to config file")
+                        .on_hover_text(
+                            "Writes the settings changed here, the rest of the file is kept",
+                        )
+                        .clicked()
+                    {
+                        let saved = save_config(&app.config_path, &app.config, &app.saved_config);
+                        let (level, message) = match saved {
+                            Ok(()) => {
+                                app.saved_config = app.config.clone();
+                                (
+                                    StdLogLevel::INFO,
+                                    format!("Saved config to {}", app.config_path.display()),
+                                )
+                            }
+                            Err(e) => (StdLogLevel::ERROR, format!("Failed to save config! {}", e)),
+                        };
+                        app.system_logs.push(level, message);
+                    }
+
+                    ui.label(app.config_path.display().to_string());
+                });
+
+                if changed {
+                    send_config(app);
+                }
+            });
+    });
+}
+
+fn render_engine_settings(app: &mut UiApp, ui: &mut egui::Ui) -> bool {
+    let engine = &mut app.config.engine;
+    let mut changed = false;
+
+    let mut tps = engine.tps.per_unit();
+    ui.label("Ticks per second");
+    let response = ui.add_enabled(
+        !engine.unthrottled,
+        Slider::new(&mut tps, 0.1..=1000.0)
+            .logarithmic(true)
+            .custom_formatter(|n, _| {
+                Rate::from_per_unit(n).map_or_else(|_| n.to_string(), |tps| tps.to_string())
+            }),
+    );
+    if response.changed() {
+        if let Ok(tps) = Rate::from_per_unit(tps) {
+            engine.tps = tps;
+        }
+    }
+    changed |= committed(&response);
+    changed |= ui
+        .checkbox(&mut engine.unthrottled, "Unthrottled")
+        .on_hover_text("Run ticks back to back as fast as possible")
+        .changed();
+
+    ComboBox::from_label("Timing")
+        .selected_text(format!("{:?}", engine.timing))
+        .show_ui(ui, |ui| {
+            for timing in [Timing::Instructions, Timing::Cycles] {
+                changed |= ui
+                    .selectable_value(&mut engine.timing, timing, format!("{:?}", timing))
+                    .changed();
+            }
+        })
+        .response
+        .on_hover_text("Cycles makes slow instructions like DIV take more ticks");
+
+    // the config stores instructions (or cycles) per tick, the slider shows its inverse
+    let mut ticks_per_instruction = 1.0 / engine.ipt.per_unit();
+    ui.label(match engine.timing {
+        Timing::Instructions => "Ticks per instruction",
+        Timing::Cycles => "Ticks per cycle",
+    });
+    let response = ui.add(
+        Slider::new(&mut ticks_per_instruction, 0.01..=100.0)
+            .logarithmic(true)
+            .custom_formatter(|n, _| {
+                let ticks = Rate::from_per_unit(1.0 / n).map_or(n, |ipt| 1.0 / ipt.per_unit());
+                format!("{:.2}", ticks)
+            }),
+    );
+    if response.changed() {
+        if let Ok(ipt) = Rate::from_per_unit(1.0 / ticks_per_instruction) {
+            engine.ipt = ipt;
+        }
+    }
+    changed |= committed(&response);
+
+    ui.separator();
+
+    ui.label("Heap size");
+    changed |= committed(
+        &ui.add(
+            egui::DragValue::new(&mut engine.heap_memory_size)
+                .range(1..=usize::MAX)
+                .suffix(" bytes"),
+        )
+        .on_hover_text("Applied the next time a program is started"),
+    );
+
+    let simulation = &mut engine.heap_access_simulation;
+    changed |= ui
+        .checkbox(&mut simulation.enabled, "Heap access simulation")
+        .changed();
+    ui.add_enabled_ui(simulation.enabled, |ui| {
+        ui.label("Minimum delay");
+        changed |= committed(
+            &ui.add(egui::DragValue::new(&mut simulation.minimum_delay).suffix(" ticks")),
+        );
+        ui.label("Jitter");
+        changed |= committed(
+            &ui.add(
+                egui::DragValue::new(&mut simulation.jitter)
+                    .prefix("+0..")
+                    .suffix(" ticks"),
+            )
+            .on_hover_text("Random extra ticks added to each delay, 0 disables the jitter"),
+        );
+        ui.label("Seed");
+        changed |= committed(
+            &ui.add(egui::DragValue::new(&mut simulation.seed))
+                .on_hover_text("Applied the next time a program is started"),
+        );
+    });
+
+    ui.separator();
+
+    ComboBox::from_label("Log level")
+        .selected_text(format!("{:?}", engine.log_level))
+        .show_ui(ui, |ui| {
+            for level in [LogLevel::Info, LogLevel::Warn, LogLevel::Error] {
+                changed |= ui
+                    .selectable_value(&mut engine.log_level, level, format!("{:?}", level))
+                    .changed();
+            }
+        });
+
+    ui.separator();
+
+    let trace = &mut app.config.program.output_trace;
+    changed |= ui
+        .checkbox(&mut trace.should_write, "Record trace")
+        .on_hover_text(format!(
+            "Writes every executed instruction to {}, applied the next time a program is started",
+            trace.path
+        ))
+        .changed();
+
+    changed
+}
+
+/// Whether an edit is finished. Values that are dragged or typed in count once they are let go
+/// of, so the engine gets the config once instead of on every frame of the drag.
+fn committed(response: &egui::Response) -> bool {
+    response.drag_stopped()
+        || response.lost_focus()
+        || (response.changed() && !response.dragged() && !response.has_focus())
+}
+
+fn send_config(app: &mut UiApp) {
+    match toml::to_string(&app.config) {
+        Ok(payload) => {
+            let _ = app.command_sender.send(ClientCommands {
+                command_type: ClientCommandType::UpdateConfig,
+                payload: Some(payload),
+            });
+        }
+        Err(e) => app.system_logs.push(
+            StdLogLevel::ERROR,
+            format!("Failed to send the config! {}", e),
+        ),
+    }
+}
@@ -56,6 +56,10 @@ pub fn show_engine_data_table(ui: &mut egui::Ui, data: BTreeMap<String, serde_js
         "IR Repserentation",
         "Parsing Result",
         "registers",
+        "breakpoints",
+        "watchpoints",
+        "source",
+        "Memory Stall",
     ]);
 
     let column_width = ui.available_width() / 2.0;
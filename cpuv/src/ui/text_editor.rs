@@ -1,11 +1,12 @@
-use egui::{Button, Color32, ComboBox, RichText, ScrollArea};
+use egui::{Button, ComboBox, RichText, ScrollArea};
 use egui_extras::{Column, TableBuilder};
 use strum::IntoEnumIterator;
 
-use irv::{Label, Variable};
+use irv::{instruction_bytes, Label, Program, Variable};
 
 use super::app::{ParsingResultViewOptions, UiApp};
-use crate::core::engine::{ClientCommandType, ClientCommands};
+use super::{debugger, trace_diff};
+use crate::core::engine::{ClientCommandType, ClientCommands, EngineRunningState};
 
 pub fn render(app: &mut UiApp, ctx: &egui::Context) {
     let window_size = ctx.screen_rect().max;
@@ -32,11 +33,18 @@ pub fn render(app: &mut UiApp, ctx: &egui::Context) {
                 //ui.set_height(available_height * 0.8);
                 ui.horizontal(|ui| {
                     app.show_file_picker(ctx, ui);
+                    app.show_save_binary(ctx, ui);
+                    app.show_save_snapshot(ctx, ui);
                 });
 
                 app.show_code_editor(ui, ctx);
             });
 
+            ui.add_space(10.0);
+            debugger::render(app, ui);
+            ui.add_space(10.0);
+            trace_diff::render(app, ctx, ui);
+
             ui.add_space(20.0);
             ui.label(RichText::new("Parsing Results").strong().size(24.0));
 
@@ -53,6 +61,16 @@ pub fn render(app: &mut UiApp, ctx: &egui::Context) {
                         ParsingResultViewOptions::Tabled,
                         "Tabled",
                     );
+                    ui.selectable_value(
+                        &mut app.ui_opts.parsing_results,
+                        ParsingResultViewOptions::IR,
+                        "IR",
+                    );
+                    ui.selectable_value(
+                        &mut app.ui_opts.parsing_results,
+                        ParsingResultViewOptions::Binary,
+                        "Binary",
+                    );
                     ui.selectable_value(
                         &mut app.ui_opts.parsing_results,
                         ParsingResultViewOptions::None,
@@ -86,7 +104,7 @@ pub fn render_parsing_results(app: &mut UiApp, ui: &mut egui::Ui) {
                 let _output = egui::TextEdit::multiline(&mut viewable)
                     .desired_rows(150)
                     .desired_width(ui.available_width())
-                    .background_color(Color32::BLACK)
+                    .background_color(ui.visuals().extreme_bg_color)
                     .interactive(false)
                     .clip_text(true)
                     .show(ui);
@@ -108,6 +126,30 @@ pub fn render_parsing_results(app: &mut UiApp, ui: &mut egui::Ui) {
             }
         }
 
+        ParsingResultViewOptions::IR => {
+            if app.previous_data.ir_repsersentation.is_empty() {
+                ui.label("No IR Found! Use TranslateToIR to generate it.");
+                return;
+            }
+
+            ScrollArea::vertical().show(ui, |ui| {
+                let mut viewable = app.previous_data.ir_repsersentation.as_str();
+
+                let _output = egui::TextEdit::multiline(&mut viewable)
+                    .desired_rows(150)
+                    .desired_width(ui.available_width())
+                    .background_color(ui.visuals().extreme_bg_color)
+                    .code_editor()
+                    .interactive(false)
+                    .clip_text(true)
+                    .show(ui);
+            });
+        }
+
+        ParsingResultViewOptions::Binary => {
+            render_binary(&app.previous_data.program.clone().unwrap(), ui);
+        }
+
         ParsingResultViewOptions::None => {}
     };
 }
@@ -175,8 +217,96 @@ pub fn render_static_variables(vars: &Vec<Variable>, ui: &mut egui::Ui) {
             });
     });
 }
-pub fn render_labels(_labels: &[Label], _ui: &mut egui::Ui) {
-    todo!()
+pub fn render_labels(labels: &[Label], ui: &mut egui::Ui) {
+    let avail_width = ui.available_width() / 2.0;
+    ui.add_space(30.0);
+    ui.push_id("label_table", |ui| {
+        TableBuilder::new(ui)
+            .id_salt("label_table")
+            .striped(true)
+            .resizable(true)
+            .vscroll(true)
+            .column(Column::exact(avail_width).resizable(true))
+            .column(Column::remainder().resizable(true))
+            .header(30.0, |mut header| {
+                for head in ["Label", "Instructions"] {
+                    header.col(|ui| {
+                        ui.heading(head);
+                        ui.add_space(10.0)
+                    });
+                }
+            })
+            .body(|mut body| {
+                for label in labels {
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            ui.label(label.label_name.to_string());
+                        });
+
+                        row.col(|ui| {
+                            ui.label(label.instructions.as_ref().map_or(0, Vec::len).to_string());
+                        });
+                    })
+                }
+            });
+    });
+}
+
+pub fn render_binary(program: &Program, ui: &mut egui::Ui) {
+    let avail_width = ui.available_width() / 4.0;
+    let instructions = program
+        .labels
+        .iter()
+        .flatten()
+        .flat_map(|label| label.instructions.iter().flatten())
+        .zip(instruction_bytes(program))
+        .enumerate();
+
+    ui.add_space(30.0);
+    ui.push_id("binary_table", |ui| {
+        TableBuilder::new(ui)
+            .id_salt("binary_table")
+            .striped(true)
+            .resizable(true)
+            .vscroll(true)
+            .column(Column::exact(avail_width * 0.5).resizable(true))
+            .column(Column::exact(avail_width * 1.5).resizable(true))
+            .column(Column::remainder().resizable(true))
+            .header(30.0, |mut header| {
+                for head in ["Address", "Instruction", "Bytes"] {
+                    header.col(|ui| {
+                        ui.heading(head);
+                        ui.add_space(10.0)
+                    });
+                }
+            })
+            .body(|mut body| {
+                for (address, (instruction, bytes)) in instructions {
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            ui.monospace(format!("{:04}", address));
+                        });
+
+                        row.col(|ui| {
+                            ui.monospace(format!(
+                                "{:?} {}",
+                                instruction.ty,
+                                instruction.operand_tokens().join(" ")
+                            ));
+                        });
+
+                        row.col(|ui| {
+                            let hex = bytes
+                                .iter()
+                                .map(|b| format!("{:02x}", b))
+                                .collect::<Vec<String>>()
+                                .join(" ");
+                            ui.monospace(hex);
+                        });
+                    })
+                }
+            });
+    });
 }
 
 pub fn render_controls(app: &mut UiApp, _ctx: &egui::Context, ui: &mut egui::Ui) {
@@ -190,8 +320,13 @@ pub fn render_controls(app: &mut UiApp, _ctx: &egui::Context, ui: &mut egui::Ui)
             let _ = app.command_sender.send(ClientCommands {
                 payload: match command_type {
                     ClientCommandType::Start
+                    | ClientCommandType::StepInstruction
+                    | ClientCommandType::StepOver
+                    | ClientCommandType::StepOut
                     | ClientCommandType::ParseFile
-                    | ClientCommandType::ParseWithoutUpdate => Some(app.code.clone()),
+                    | ClientCommandType::ParseWithoutUpdate
+                    | ClientCommandType::TranslateToIR
+                    | ClientCommandType::TranslateToIRWithoutUpdate => Some(app.code.clone()),
                     _ => None,
                 },
 
@@ -199,4 +334,25 @@ pub fn render_controls(app: &mut UiApp, _ctx: &egui::Context, ui: &mut egui::Ui)
             });
         }
     }
+
+    let run_to_cursor = ui
+        .add_enabled(
+            app.cursor_line.is_some(),
+            Button::new(RichText::new("Run To Cursor").size(16.0)),
+        )
+        .on_hover_text("Runs until the first instruction at or after the cursor line");
+    if let (true, Some(line)) = (run_to_cursor.clicked(), app.cursor_line) {
+        // a stopped program is loaded first, paused before its first instruction
+        if app.previous_data.engine_running_state == EngineRunningState::Stopped {
+            let _ = app.command_sender.send(ClientCommands {
+                command_type: ClientCommandType::StepInstruction,
+                payload: Some(app.code.clone()),
+            });
+        }
+
+        let _ = app.command_sender.send(ClientCommands {
+            command_type: ClientCommandType::RunToCursor,
+            payload: Some(line.to_string()),
+        });
+    }
 }
@@ -0,0 +1,114 @@
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use egui::{Color32, TextStyle, Visuals};
+use egui_code_editor::ColorTheme;
+
+use crate::config::{parse_color, UIConfig, UITheme};
+
+/// Applies the theme, palette, font color and font size to every following frame
+pub fn apply(ctx: &egui::Context, config: &UIConfig) {
+    ctx.set_visuals(visuals(config));
+
+    let size = config.font_size as f32;
+    ctx.style_mut(|style| {
+        for (text_style, font) in style.text_styles.iter_mut() {
+            font.size = match text_style {
+                TextStyle::Heading => size * 1.5,
+                TextStyle::Small => size * 0.75,
+                _ => size,
+            };
+        }
+    });
+}
+
+pub fn visuals(config: &UIConfig) -> Visuals {
+    let mut visuals = match config.theme {
+        UITheme::light => Visuals::light(),
+        UITheme::dark => Visuals::dark(),
+    };
+
+    visuals.override_text_color = color(&config.font_color);
+
+    let palette = &config.palette;
+    if let Some(background) = palette.background.as_deref().and_then(color) {
+        visuals.panel_fill = background;
+        visuals.window_fill = background;
+    }
+
+    if let Some(surface) = palette.surface.as_deref().and_then(color) {
+        visuals.extreme_bg_color = surface;
+        visuals.faint_bg_color = surface;
+        visuals.code_bg_color = surface;
+    }
+
+    if let Some(accent) = palette.accent.as_deref().and_then(color) {
+        visuals.selection.bg_fill = accent;
+        visuals.hyperlink_color = accent;
+        visuals.widgets.hovered.bg_stroke.color = accent;
+        visuals.widgets.active.bg_fill = accent;
+    }
+
+    visuals
+}
+
+/// The gruvbox theme matching `config.theme`, with the palette colors swapped in
+pub fn code_theme(config: &UIConfig) -> ColorTheme {
+    let mut theme = match config.theme {
+        UITheme::light => ColorTheme::GRUVBOX_LIGHT,
+        UITheme::dark => ColorTheme::GRUVBOX_DARK,
+    };
+
+    let custom = |hex: &Option<String>| -> Option<&'static str> {
+        let hex = hex.as_ref().filter(|hex| parse_color(hex).is_some())?;
+        Some(intern(hex))
+    };
+
+    let palette = &config.palette;
+    let overrides = [
+        (&mut theme.bg, &palette.surface),
+        (&mut theme.selection, &palette.accent),
+        (&mut theme.comments, &palette.comments),
+        (&mut theme.functions, &palette.functions),
+        (&mut theme.keywords, &palette.keywords),
+        (&mut theme.literals, &palette.literals),
+        (&mut theme.numerics, &palette.numerics),
+        (&mut theme.punctuation, &palette.punctuation),
+        (&mut theme.strs, &palette.strings),
+        (&mut theme.types, &palette.types),
+        (&mut theme.special, &palette.special),
+    ];
+    for (field, hex) in overrides {
+        if let Some(hex) = custom(hex) {
+            *field = hex;
+        }
+    }
+
+    if config.font_color != "default" {
+        if let Some(hex) = custom(&Some(config.font_color.clone())) {
+            theme.cursor = hex;
+        }
+    }
+
+    theme
+}
+
+/// ColorTheme only holds static strings, so every color is leaked once and reused by the themes
+/// rebuilt on later config changes
+fn intern(hex: &str) -> &'static str {
+    static COLORS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+    let mut colors = COLORS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(interned) = colors.get(hex) {
+        return interned;
+    }
+
+    let interned: &'static str = Box::leak(hex.to_string().into_boxed_str());
+    colors.insert(interned);
+    interned
+}
+
+fn color(hex: &str) -> Option<Color32> {
+    let [r, g, b] = parse_color(hex)?;
+    Some(Color32::from_rgb(r, g, b))
+}
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use egui::{Grid, RichText};
+use egui_file_dialog::FileDialog;
+
+use super::app::UiApp;
+use crate::core::engine::trace::read_trace;
+use crate::core::engine::trace_diff::{describe, diff, TraceDiff};
+
+/// The two traces being compared and the result of the last comparison
+pub struct TraceDiffView {
+    dialog: FileDialog,
+    /// Which of the traces the open dialog picks
+    picking: usize,
+    paths: [Option<PathBuf>; 2],
+    result: Option<Result<TraceDiff, String>>,
+}
+
+impl Default for TraceDiffView {
+    fn default() -> Self {
+        Self {
+            dialog: FileDialog::new(),
+            picking: 0,
+            paths: [None, None],
+            result: None,
+        }
+    }
+}
+
+/// Picks two JSON Lines traces and shows the first instruction where they diverge
+pub fn render(app: &mut UiApp, ctx: &egui::Context, ui: &mut egui::Ui) {
+    let current_trace = PathBuf::from(&app.config.program.output_trace.path);
+    let view = &mut app.trace_diff;
+
+    egui::CollapsingHeader::new(RichText::new("Trace Diff").strong().size(24.0))
+        .default_open(false)
+        .show(ui, |ui| {
+            for (index, name) in ["A", "B"].into_iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(name).strong());
+                    if ui.button("Pick").clicked() {
+                        view.picking = index;
+                        view.dialog.pick_file();
+                    }
+                    if ui
+                        .button("Current trace")
+                        .on_hover_text(current_trace.display().to_string())
+                        .clicked()
+                    {
+                        view.paths[index] = Some(current_trace.clone());
+                    }
+
+                    match &view.paths[index] {
+                        Some(path) => ui.monospace(path.display().to_string()),
+                        None => ui.label("No trace picked"),
+                    };
+                });
+            }
+
+            let ready = view.paths.iter().all(Option::is_some);
+            if ui
+                .add_enabled(ready, egui::Button::new("Compare"))
+                .clicked()
+            {
+                if let [Some(a), Some(b)] = &view.paths {
+                    view.result = Some(read_trace(a).and_then(|a| Ok(diff(&a, &read_trace(b)?))));
+                }
+            }
+
+            ui.add_space(5.0);
+            match &view.result {
+                Some(Ok(result)) => show_diff(ui, result),
+                Some(Err(e)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
+                None => {}
+            }
+        });
+
+    view.dialog.update(ctx);
+    if let Some(path) = view.dialog.take_picked() {
+        view.paths[view.picking] = Some(path);
+        view.result = None;
+    }
+}
+
+fn show_diff(ui: &mut egui::Ui, result: &TraceDiff) {
+    let Some(divergence) = &result.divergence else {
+        ui.label(format!("Traces match, {} instructions", result.matching));
+        return;
+    };
+
+    ui.label(
+        RichText::new(format!(
+            "Diverge at instruction {} of {} / {}",
+            divergence.index + 1,
+            result.lengths.0,
+            result.lengths.1
+        ))
+        .strong(),
+    );
+    for kind in &divergence.kinds {
+        ui.colored_label(ui.visuals().warn_fg_color, kind.to_string());
+    }
+
+    Grid::new("Trace Diff").striped(true).show(ui, |ui| {
+        ui.label(RichText::new("#").strong());
+        ui.label(RichText::new("A").strong());
+        ui.label(RichText::new("B").strong());
+        ui.end_row();
+
+        let start = divergence.index - divergence.context.len();
+        for (offset, (a, b)) in divergence.context.iter().enumerate() {
+            ui.label((start + offset + 1).to_string());
+            ui.monospace(describe(a));
+            ui.monospace(describe(b));
+            ui.end_row();
+        }
+
+        let side = |entry: &Option<_>| entry.as_ref().map_or("(ended)".to_string(), describe);
+        let color = ui.visuals().error_fg_color;
+        ui.colored_label(color, (divergence.index + 1).to_string());
+        ui.colored_label(color, RichText::new(side(&divergence.a)).monospace());
+        ui.colored_label(color, RichText::new(side(&divergence.b)).monospace());
+        ui.end_row();
+    });
+}
@@ -63,7 +63,7 @@ pub fn render_unsigned_registers(
                 for (key, value) in register_data.iter() {
                     body.row(20.0, |mut row| {
                         row.col(|ui| {
-                            ui.label(format!("{}", key));
+                            ui.label(key.to_string());
                         });
 
                         row.col(|ui| {
@@ -101,7 +101,7 @@ pub fn render_signed_registers(
             for (key, value) in register_data.iter() {
                 body.row(20.0, |mut row| {
                     row.col(|ui| {
-                        ui.label(format!("{}", key));
+                        ui.label(key.to_string());
                     });
 
                     row.col(|ui| {
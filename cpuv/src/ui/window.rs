@@ -1,9 +1,12 @@
 #![allow(clippy::mem_forget)]
 
 use eframe::egui;
+use std::path::PathBuf;
 use std::sync::mpsc;
 
-use crate::core::engine::{ClientCommands, EngineData, StdLogMessage};
+use crate::config::{ConfigReload, RootConfig};
+use crate::core::engine::publisher::StateReceiver;
+use crate::core::engine::{ClientCommands, StdLogMessage};
 use crate::ui::app::UiApp;
 
 // wasm imports
@@ -13,9 +16,12 @@ pub const WINDOW_HEIGHT: f32 = 1440.0;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub fn init(
+    config: RootConfig,
+    config_path: PathBuf,
     client_command_sender: mpsc::Sender<ClientCommands>,
-    engine_data_recv: mpsc::Receiver<EngineData>,
+    engine_data_recv: StateReceiver,
     stdlog_reciever: mpsc::Receiver<StdLogMessage>,
+    config_reciever: mpsc::Receiver<ConfigReload>,
 ) -> eframe::Result {
     let opts = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([WINDOW_WIDTH, WINDOW_HEIGHT]),
@@ -28,22 +34,28 @@ pub fn init(
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
             Ok(Box::<UiApp>::new(UiApp::new(
+                config,
+                config_path,
                 client_command_sender,
                 engine_data_recv,
                 stdlog_reciever,
+                config_reciever,
             )))
         }),
     );
 
-    return Ok(());
+    Ok(())
 }
 
 //#[wasm_bindgen::prelude::wasm_bindgen]
 #[cfg(target_arch = "wasm32")]
 pub fn init(
+    config: RootConfig,
+    config_path: PathBuf,
     client_command_sender: mpsc::Sender<ClientCommands>,
-    engine_data_recv: mpsc::Receiver<EngineData>,
+    engine_data_recv: StateReceiver,
     stdlog_reciever: mpsc::Receiver<StdLogMessage>,
+    config_reciever: mpsc::Receiver<ConfigReload>,
 ) -> eframe::Result {
     todo!()
 }
@@ -0,0 +1,760 @@
+//! A compact binary encoding of a [`Program`].
+//!
+//! ```text
+//! header   "IRVB" version:u8 flags:u8
+//! strings  count, (len, utf8 bytes)*          every name and raw text used below
+//! extern   count, name*                       only when the extern section is present
+//! data     count, (name, type:u8, initial)*   initial values are kept as written in the source
+//! symbols  count, (label, has_code:u8, instruction count)*
+//! code     (opcode:u8, value)*                instructions of every label, in symbol order
+//! ```
+//!
+//! Counts, lengths and string indices are unsigned LEB128 varints. An instruction value is either a
+//! list of operands, `0 count operand*`, or the two halves of a multiple value, `1 left right`.
+//! Operands are tagged so that the common cases only take a byte or two:
+//!
+//! | tag | operand                  | payload                               |
+//! |-----|--------------------------|---------------------------------------|
+//! | 0   | raw text                 | string index                          |
+//! | 1   | register                 | `u8` index into [`Registers::NAMES`]  |
+//! | 2   | immediate                | zigzag varint                         |
+//! | 3   | label                    | symbol index                          |
+//! | 4   | variable                 | data index                            |
+//! | 5   | extern function          | extern index                          |
+//!
+//! Anything that would not decode back to the exact same text is stored as raw text, so
+//! [`disassemble`] always returns the program given to [`assemble`].
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{
+    parse_immediate, DataType, Instruction, InstructionType, InstructionValue, Label, Program,
+    Registers, Variable,
+};
+
+pub const BINARY_EXTENSION: &str = "irvb";
+
+const MAGIC: &[u8; 4] = b"IRVB";
+const VERSION: u8 = 1;
+
+const FLAG_EXTERN: u8 = 1 << 0;
+const FLAG_LABELS: u8 = 1 << 1;
+
+const VALUE_OPERANDS: u8 = 0;
+const VALUE_MULTIPLE: u8 = 1;
+
+const OPERAND_RAW: u8 = 0;
+const OPERAND_REGISTER: u8 = 1;
+const OPERAND_IMMEDIATE: u8 = 2;
+const OPERAND_LABEL: u8 = 3;
+const OPERAND_VARIABLE: u8 = 4;
+const OPERAND_FUNCTION: u8 = 5;
+
+/// Instruction opcodes, the opcode of an instruction is its index in this table. New instructions
+/// must be added at the end so existing binaries keep decoding.
+const OPCODES: [InstructionType; 18] = [
+    InstructionType::NOP,
+    InstructionType::LOAD,
+    InstructionType::MOVE,
+    InstructionType::INC,
+    InstructionType::DEC,
+    InstructionType::ADD,
+    InstructionType::SUB,
+    InstructionType::MUL,
+    InstructionType::DIV,
+    InstructionType::CMP,
+    InstructionType::JMP,
+    InstructionType::JEQ,
+    InstructionType::JLT,
+    InstructionType::JGT,
+    InstructionType::CALL,
+    InstructionType::RET,
+    InstructionType::BRK,
+    InstructionType::EXIT,
+];
+
+const DATA_TYPES: [DataType; 16] = [
+    DataType::Byte,
+    DataType::SByte,
+    DataType::TByte,
+    DataType::Word,
+    DataType::SWord,
+    DataType::DWord,
+    DataType::SDWord,
+    DataType::QWord,
+    DataType::Real4,
+    DataType::Real8,
+    DataType::Str4,
+    DataType::Str8,
+    DataType::Str16,
+    DataType::Str32,
+    DataType::Str64,
+    DataType::Str128,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEof,
+    VarintOverflow,
+    InvalidOpcode(u8),
+    InvalidDataType(u8),
+    InvalidValueKind(u8),
+    InvalidOperandTag(u8),
+    IndexOutOfRange { kind: &'static str, index: usize },
+    InvalidUtf8,
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryError::BadMagic => write!(f, "Not an irv binary (missing IRVB header)"),
+            BinaryError::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "Unsupported irv binary version {} (expected {})",
+                    v, VERSION
+                )
+            }
+            BinaryError::UnexpectedEof => write!(f, "Unexpected end of file"),
+            BinaryError::VarintOverflow => write!(f, "Varint is too large"),
+            BinaryError::InvalidOpcode(op) => write!(f, "Invalid opcode {:#04x}", op),
+            BinaryError::InvalidDataType(ty) => write!(f, "Invalid data type {:#04x}", ty),
+            BinaryError::InvalidValueKind(kind) => {
+                write!(f, "Invalid instruction value kind {:#04x}", kind)
+            }
+            BinaryError::InvalidOperandTag(tag) => write!(f, "Invalid operand tag {:#04x}", tag),
+            BinaryError::IndexOutOfRange { kind, index } => {
+                write!(f, "{} index {} is out of range", kind, index)
+            }
+            BinaryError::InvalidUtf8 => write!(f, "String table contains invalid UTF-8"),
+        }
+    }
+}
+
+/// Encodes a program into the irv binary format.
+pub fn assemble(program: &Program) -> Vec<u8> {
+    let mut encoder = Encoder::new(program);
+
+    let mut code = Vec::new();
+    for instruction in program
+        .labels
+        .iter()
+        .flatten()
+        .flat_map(|label| label.instructions.iter().flatten())
+    {
+        code.extend(encoder.instruction(instruction));
+    }
+
+    let mut sections = Vec::new();
+    if let Some(functions) = &program.extern_functions {
+        write_varint(&mut sections, functions.len() as u128);
+        for function in functions {
+            let idx = encoder.string(function);
+            write_varint(&mut sections, idx as u128);
+        }
+    }
+
+    write_varint(&mut sections, program.static_variables.len() as u128);
+    for variable in &program.static_variables {
+        let name = encoder.string(&variable.name);
+        let value = encoder.string(&variable.inital_value);
+        write_varint(&mut sections, name as u128);
+        sections.push(DATA_TYPES.iter().position(|ty| *ty == variable.ty).unwrap() as u8);
+        write_varint(&mut sections, value as u128);
+    }
+
+    if let Some(labels) = &program.labels {
+        write_varint(&mut sections, labels.len() as u128);
+        for label in labels {
+            let name = encoder.string(&label.label_name);
+            write_varint(&mut sections, name as u128);
+            sections.push(label.instructions.is_some() as u8);
+            write_varint(
+                &mut sections,
+                label.instructions.as_ref().map_or(0, Vec::len) as u128,
+            );
+        }
+    }
+
+    let mut flags = 0;
+    if program.extern_functions.is_some() {
+        flags |= FLAG_EXTERN;
+    }
+    if program.labels.is_some() {
+        flags |= FLAG_LABELS;
+    }
+
+    let mut out = Vec::new();
+    out.extend(MAGIC);
+    out.push(VERSION);
+    out.push(flags);
+
+    write_varint(&mut out, encoder.strings.len() as u128);
+    for string in &encoder.strings {
+        write_varint(&mut out, string.len() as u128);
+        out.extend(string.as_bytes());
+    }
+
+    out.extend(sections);
+    out.extend(code);
+    out
+}
+
+/// The encoded bytes of every instruction, in address order. String table indices refer to the
+/// table that [`assemble`] would build for the same program.
+pub fn instruction_bytes(program: &Program) -> Vec<Vec<u8>> {
+    let mut encoder = Encoder::new(program);
+
+    program
+        .labels
+        .iter()
+        .flatten()
+        .flat_map(|label| label.instructions.iter().flatten())
+        .map(|instruction| encoder.instruction(instruction))
+        .collect()
+}
+
+/// Decodes a program produced by [`assemble`].
+pub fn disassemble(bytes: &[u8]) -> Result<Program, BinaryError> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(4)? != MAGIC {
+        return Err(BinaryError::BadMagic);
+    }
+
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(BinaryError::UnsupportedVersion(version));
+    }
+    let flags = reader.byte()?;
+
+    let mut strings = Vec::new();
+    for _ in 0..reader.varint()? {
+        let len = reader.varint()?;
+        let raw = reader.take(len)?;
+        strings.push(
+            std::str::from_utf8(raw)
+                .map_err(|_| BinaryError::InvalidUtf8)?
+                .to_string(),
+        );
+    }
+    let string =
+        |idx: usize| -> Result<String, BinaryError> { lookup(&strings, idx, "String").cloned() };
+
+    let mut program = Program::new();
+
+    if flags & FLAG_EXTERN != 0 {
+        let mut functions = Vec::new();
+        for _ in 0..reader.varint()? {
+            functions.push(string(reader.varint()?)?);
+        }
+        program.extern_functions = Some(functions);
+    }
+
+    for _ in 0..reader.varint()? {
+        let name = string(reader.varint()?)?;
+        let ty = reader.byte()?;
+        let ty = DATA_TYPES
+            .get(ty as usize)
+            .cloned()
+            .ok_or(BinaryError::InvalidDataType(ty))?;
+        let inital_value = string(reader.varint()?)?;
+        program.static_variables.push(Variable {
+            name,
+            ty,
+            inital_value,
+        });
+    }
+
+    let mut symbols = Vec::new();
+    if flags & FLAG_LABELS != 0 {
+        for _ in 0..reader.varint()? {
+            let label_name = string(reader.varint()?)?;
+            let has_code = reader.byte()? != 0;
+            let count = reader.varint()?;
+            symbols.push((label_name, has_code, count));
+        }
+    }
+
+    let label_refs = symbols
+        .iter()
+        .map(|(name, _, _)| format!("@{}", crate::label_name(name)))
+        .collect::<Vec<String>>();
+
+    let mut labels = Vec::new();
+    for (label_name, has_code, count) in &symbols {
+        let mut instructions = Vec::new();
+        for _ in 0..*count {
+            let opcode = reader.byte()?;
+            let ty = OPCODES
+                .get(opcode as usize)
+                .cloned()
+                .ok_or(BinaryError::InvalidOpcode(opcode))?;
+
+            let val = match reader.byte()? {
+                VALUE_OPERANDS => {
+                    let mut tokens = Vec::new();
+                    for _ in 0..reader.varint()? {
+                        let tag = reader.byte()?;
+                        let token = match tag {
+                            OPERAND_RAW => string(reader.varint()?)?,
+                            OPERAND_REGISTER => {
+                                lookup(&Registers::NAMES, reader.byte()? as usize, "Register")?
+                                    .to_string()
+                            }
+                            OPERAND_IMMEDIATE => reader.zigzag()?.to_string(),
+                            OPERAND_LABEL => {
+                                lookup(&label_refs, reader.varint()?, "Label")?.clone()
+                            }
+                            OPERAND_VARIABLE => {
+                                lookup(&program.static_variables, reader.varint()?, "Variable")?
+                                    .name
+                                    .clone()
+                            }
+                            OPERAND_FUNCTION => lookup(
+                                program.extern_functions.as_deref().unwrap_or_default(),
+                                reader.varint()?,
+                                "Extern function",
+                            )?
+                            .clone(),
+                            _ => return Err(BinaryError::InvalidOperandTag(tag)),
+                        };
+                        tokens.push(token);
+                    }
+                    InstructionValue::SingleValue(tokens.join(" "))
+                }
+                VALUE_MULTIPLE => {
+                    let left = string(reader.varint()?)?;
+                    let right = string(reader.varint()?)?;
+                    InstructionValue::MultipleValue((left, right))
+                }
+                kind => return Err(BinaryError::InvalidValueKind(kind)),
+            };
+
+            instructions.push(Instruction {
+                ty,
+                val,
+                line: None,
+            });
+        }
+
+        labels.push(Label {
+            label_name: label_name.clone(),
+            instructions: has_code.then_some(instructions),
+        });
+    }
+
+    if flags & FLAG_LABELS != 0 {
+        program.labels = Some(labels);
+    }
+
+    Ok(program)
+}
+
+fn lookup<'a, T>(items: &'a [T], index: usize, kind: &'static str) -> Result<&'a T, BinaryError> {
+    items
+        .get(index)
+        .ok_or(BinaryError::IndexOutOfRange { kind, index })
+}
+
+struct Encoder<'a> {
+    program: &'a Program,
+    strings: Vec<String>,
+    string_index: HashMap<String, usize>,
+}
+
+impl<'a> Encoder<'a> {
+    fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            strings: Vec::new(),
+            string_index: HashMap::new(),
+        }
+    }
+
+    fn string(&mut self, s: &str) -> usize {
+        if let Some(idx) = self.string_index.get(s) {
+            return *idx;
+        }
+
+        self.strings.push(s.to_string());
+        self.string_index
+            .insert(s.to_string(), self.strings.len() - 1);
+        self.strings.len() - 1
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) -> Vec<u8> {
+        let opcode = OPCODES.iter().position(|ty| *ty == instruction.ty).unwrap();
+        let mut out = vec![opcode as u8];
+
+        match &instruction.val {
+            InstructionValue::SingleValue(val) => {
+                out.push(VALUE_OPERANDS);
+
+                // splitting on single spaces keeps the exact spacing, empty tokens become raw text
+                let tokens = if val.is_empty() {
+                    Vec::new()
+                } else {
+                    val.split(' ').collect()
+                };
+
+                write_varint(&mut out, tokens.len() as u128);
+                for token in tokens {
+                    self.operand(token, &mut out);
+                }
+            }
+            InstructionValue::MultipleValue((left, right)) => {
+                out.push(VALUE_MULTIPLE);
+                let left = self.string(left);
+                let right = self.string(right);
+                write_varint(&mut out, left as u128);
+                write_varint(&mut out, right as u128);
+            }
+        }
+
+        out
+    }
+
+    fn operand(&mut self, token: &str, out: &mut Vec<u8>) {
+        if let Some(idx) = Registers::NAMES.iter().position(|name| *name == token) {
+            out.extend([OPERAND_REGISTER, idx as u8]);
+            return;
+        }
+
+        if let Some(value) = parse_immediate(token).filter(|v| v.to_string() == token) {
+            out.push(OPERAND_IMMEDIATE);
+            write_varint(out, zigzag(value));
+            return;
+        }
+
+        let labels = self.program.labels.as_deref().unwrap_or_default();
+        if let Some(idx) = labels
+            .iter()
+            .position(|label| token.strip_prefix('@') == Some(label.name()))
+        {
+            out.push(OPERAND_LABEL);
+            write_varint(out, idx as u128);
+            return;
+        }
+
+        let variables = &self.program.static_variables;
+        if let Some(idx) = variables.iter().position(|v| v.name == token) {
+            out.push(OPERAND_VARIABLE);
+            write_varint(out, idx as u128);
+            return;
+        }
+
+        let functions = self.program.extern_functions.as_deref().unwrap_or_default();
+        if let Some(idx) = functions.iter().position(|f| f == token) {
+            out.push(OPERAND_FUNCTION);
+            write_varint(out, idx as u128);
+            return;
+        }
+
+        out.push(OPERAND_RAW);
+        let idx = self.string(token);
+        write_varint(out, idx as u128);
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u128) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn zigzag(value: i128) -> u128 {
+    ((value << 1) ^ (value >> 127)) as u128
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BinaryError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or(BinaryError::UnexpectedEof)?;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(BinaryError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> Result<u8, BinaryError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint_u128(&mut self) -> Result<u128, BinaryError> {
+        let mut value = 0u128;
+        for shift in (0..128).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u128) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(BinaryError::VarintOverflow)
+    }
+
+    fn varint(&mut self) -> Result<usize, BinaryError> {
+        usize::try_from(self.varint_u128()?).map_err(|_| BinaryError::VarintOverflow)
+    }
+
+    fn zigzag(&mut self) -> Result<i128, BinaryError> {
+        let value = self.varint_u128()?;
+        Ok(((value >> 1) as i128) ^ -((value & 1) as i128))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::try_parse;
+
+    const SOURCE: &str = "\
+.section .extern
+    printf
+
+.section .data:
+    byte b 1
+    sbyte sb -1
+    tbyte tb 10
+    word w 300
+    sword sw -300
+    dword dw 70_000
+    sdword sdw -70_000
+    qword q 18446744073709551615
+    real4 r4 1.5
+    real8 r8 -2.25
+    str4 s4 \"abc\"
+    str8 s8 \"rust\"
+    str16 s16 \"assembly\"
+    str32 s32 \"a longer string\"
+    str64 s64 \"\"
+    str128 s128 \"with  two spaces\"
+
+.section .program:
+@start:
+    LOAD 5 rax
+    LOAD -1 srbx
+    LOAD -64 srcx
+    LOAD -65 srdx
+    LOAD 8192 r15
+    LOAD 170141183460469231731687303715884105727 rax
+    LOAD -170141183460469231731687303715884105728 srax
+    LOAD 0x10 rbx
+    LOAD 1_000 rcx
+    MOVE q dw
+    INC rax
+    DEC rbx
+    ADD sb b
+    SUB w 2
+    MUL srax -3
+    DIV rax 2
+    CMP rax rcx
+    JEQ @end
+    JLT @end
+    JGT @empty
+    CALL @empty
+    CALL printf rax s8
+    NOP
+    BRK
+    JMP @end
+
+@empty:
+
+@end:
+    RET
+    EXIT 0
+";
+
+    fn program() -> Program {
+        try_parse(SOURCE).expect("the test program should parse")
+    }
+
+    #[test]
+    fn round_trips_every_operand_and_data_type() {
+        let program = program();
+        assert_eq!(disassemble(&assemble(&program)), Ok(program));
+    }
+
+    #[test]
+    fn round_trips_multiple_values_and_missing_sections() {
+        let mut program = Program::new();
+        program.labels = Some(vec![Label {
+            label_name: "start:".to_string(),
+            instructions: Some(vec![
+                Instruction {
+                    ty: InstructionType::LOAD,
+                    val: InstructionValue::MultipleValue(("1".to_string(), "rax".to_string())),
+                    line: None,
+                },
+                Instruction {
+                    ty: InstructionType::RET,
+                    val: InstructionValue::SingleValue(String::new()),
+                    line: None,
+                },
+            ]),
+        }]);
+
+        assert_eq!(disassemble(&assemble(&program)), Ok(program));
+        assert_eq!(disassemble(&assemble(&Program::new())), Ok(Program::new()));
+    }
+
+    #[test]
+    fn round_trips_multi_byte_string_indices() {
+        // more than 127 strings, so that their indices take two bytes
+        let mut program = Program::new();
+        program.labels = Some(vec![Label {
+            label_name: "start:".to_string(),
+            instructions: Some(
+                (0..300)
+                    .map(|i| Instruction {
+                        ty: InstructionType::NOP,
+                        val: InstructionValue::SingleValue(format!("text_{}", i)),
+                        line: None,
+                    })
+                    .collect(),
+            ),
+        }]);
+
+        assert_eq!(disassemble(&assemble(&program)), Ok(program));
+    }
+
+    #[test]
+    fn immediates_are_zigzag_varints() {
+        for value in [
+            0,
+            1,
+            -1,
+            63,
+            -64,
+            64,
+            -65,
+            8191,
+            -8192,
+            i128::MAX,
+            i128::MIN,
+        ] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, zigzag(value));
+
+            let mut reader = Reader {
+                bytes: &bytes,
+                pos: 0,
+            };
+            assert_eq!(reader.zigzag(), Ok(value));
+            assert_eq!(reader.pos, bytes.len());
+        }
+
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, zigzag(-65));
+        assert_eq!(bytes, [0x81, 0x01]);
+    }
+
+    #[test]
+    fn truncated_binaries_are_rejected() {
+        let bytes = assemble(&program());
+        for len in 0..bytes.len() {
+            assert_eq!(
+                disassemble(&bytes[..len]),
+                Err(BinaryError::UnexpectedEof),
+                "truncated to {} bytes",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn headers_are_checked() {
+        let mut bytes = assemble(&program());
+
+        bytes[0] = b'X';
+        assert_eq!(disassemble(&bytes), Err(BinaryError::BadMagic));
+
+        bytes[0] = MAGIC[0];
+        bytes[4] = VERSION + 1;
+        assert_eq!(
+            disassemble(&bytes),
+            Err(BinaryError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn invalid_bytes_are_errors() {
+        // header without sections: one string, no data, one label with one NOP
+        let binary = |code: &[u8]| {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend([VERSION, FLAG_LABELS, 1, 6]);
+            bytes.extend(b"start:");
+            bytes.extend([0, 1, 0, 1, 1]);
+            bytes.extend(code);
+            bytes
+        };
+
+        assert!(disassemble(&binary(&[0, VALUE_OPERANDS, 0])).is_ok());
+        assert_eq!(
+            disassemble(&binary(&[0xff, VALUE_OPERANDS, 0])),
+            Err(BinaryError::InvalidOpcode(0xff))
+        );
+        assert_eq!(
+            disassemble(&binary(&[0, 7])),
+            Err(BinaryError::InvalidValueKind(7))
+        );
+        assert_eq!(
+            disassemble(&binary(&[0, VALUE_OPERANDS, 1, 9])),
+            Err(BinaryError::InvalidOperandTag(9))
+        );
+        assert_eq!(
+            disassemble(&binary(&[0, VALUE_OPERANDS, 1, OPERAND_REGISTER, 200])),
+            Err(BinaryError::IndexOutOfRange {
+                kind: "Register",
+                index: 200
+            })
+        );
+        assert_eq!(
+            disassemble(&binary(&[0, VALUE_OPERANDS, 1, OPERAND_VARIABLE, 0])),
+            Err(BinaryError::IndexOutOfRange {
+                kind: "Variable",
+                index: 0
+            })
+        );
+        assert_eq!(
+            disassemble(&binary(&[0, VALUE_OPERANDS, 1, OPERAND_RAW, 0x80, 0x01])),
+            Err(BinaryError::IndexOutOfRange {
+                kind: "String",
+                index: 128
+            })
+        );
+
+        let mut overflow = binary(&[0, VALUE_OPERANDS, 1, OPERAND_IMMEDIATE]);
+        overflow.extend([0xff; 19]);
+        assert_eq!(disassemble(&overflow), Err(BinaryError::VarintOverflow));
+
+        let mut bad_type = MAGIC.to_vec();
+        bad_type.extend([VERSION, 0, 1, 1, b'x', 1, 0, 42, 0]);
+        assert_eq!(
+            disassemble(&bad_type),
+            Err(BinaryError::InvalidDataType(42))
+        );
+
+        let mut bad_utf8 = MAGIC.to_vec();
+        bad_utf8.extend([VERSION, 0, 1, 2, 0xc3, 0x28]);
+        assert_eq!(disassemble(&bad_utf8), Err(BinaryError::InvalidUtf8));
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{AsmFrontend, Program, TinyFrontend};
+
+/// A source language that compiles down to an irv [`Program`].
+///
+/// Implement this to make a new language runnable on the CPU-V platform, then add it to a
+/// [`FrontendRegistry`] under the file extensions it handles.
+pub trait Frontend: Send + Sync {
+    /// Human readable name of the language
+    fn name(&self) -> &'static str;
+
+    /// File extensions (without the leading `.`) handled by this frontend
+    fn extensions(&self) -> &'static [&'static str];
+
+    fn compile(&self, source: &str) -> Result<Program, Diagnostics>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// 1-based source line the diagnostic points at, if any
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn push(&mut self, line: Option<usize>, message: impl Into<String>) {
+        self.0.push(Diagnostic::new(line, message));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.0.iter()
+    }
+}
+
+impl From<Diagnostic> for Diagnostics {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self(vec![diagnostic])
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect::<Vec<String>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Frontends keyed by the file extensions they handle.
+#[derive(Clone)]
+pub struct FrontendRegistry {
+    frontends: BTreeMap<String, Arc<dyn Frontend>>,
+}
+
+impl FrontendRegistry {
+    /// An empty registry. Use [`FrontendRegistry::default`] to get one with the built-in frontends.
+    pub fn new() -> Self {
+        Self {
+            frontends: BTreeMap::new(),
+        }
+    }
+
+    /// Registers a frontend under all of its extensions, replacing any previous owner.
+    pub fn register(&mut self, frontend: impl Frontend + 'static) {
+        let frontend: Arc<dyn Frontend> = Arc::new(frontend);
+        for extension in frontend.extensions() {
+            self.frontends
+                .insert(extension.to_lowercase(), Arc::clone(&frontend));
+        }
+    }
+
+    pub fn for_extension(&self, extension: &str) -> Option<Arc<dyn Frontend>> {
+        let extension = extension.trim_start_matches('.').to_lowercase();
+        self.frontends.get(&extension).cloned()
+    }
+
+    pub fn for_path(&self, path: impl AsRef<Path>) -> Option<Arc<dyn Frontend>> {
+        self.for_extension(path.as_ref().extension()?.to_str()?)
+    }
+
+    /// The frontend with the given [`Frontend::name`], like `Tiny`
+    pub fn for_name(&self, name: &str) -> Result<Arc<dyn Frontend>, String> {
+        if let Some(frontend) = self.frontends.values().find(|f| f.name() == name) {
+            return Ok(Arc::clone(frontend));
+        }
+
+        let mut names = self
+            .frontends
+            .values()
+            .map(|frontend| frontend.name())
+            .collect::<Vec<&str>>();
+        names.sort();
+        names.dedup();
+        Err(format!(
+            "Unknown frontend {}! Known frontends: {}",
+            name,
+            names.join(", ")
+        ))
+    }
+
+    /// All registered extensions, sorted
+    pub fn extensions(&self) -> impl Iterator<Item = &str> {
+        self.frontends.keys().map(String::as_str)
+    }
+}
+
+impl Default for FrontendRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(AsmFrontend);
+        registry.register(TinyFrontend);
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Upper;
+
+    impl Frontend for Upper {
+        fn name(&self) -> &'static str {
+            "Upper"
+        }
+
+        fn extensions(&self) -> &'static [&'static str] {
+            &["UP", "cpu"]
+        }
+
+        fn compile(&self, _source: &str) -> Result<Program, Diagnostics> {
+            Err(Diagnostic::new(None, "not a real frontend").into())
+        }
+    }
+
+    #[test]
+    fn frontends_are_found_by_extension() {
+        let registry = FrontendRegistry::default();
+        assert_eq!(
+            registry.for_extension("irv").unwrap().name(),
+            "CPU-V Assembly"
+        );
+        assert_eq!(
+            registry.for_extension(".CPU").unwrap().name(),
+            "CPU-V Assembly"
+        );
+        assert_eq!(registry.for_path("src/main.tiny").unwrap().name(), "Tiny");
+        assert!(registry.for_extension("rs").is_none());
+        assert!(registry.for_path("Makefile").is_none());
+        assert_eq!(
+            registry.extensions().collect::<Vec<&str>>(),
+            ["cpu", "irv", "tiny"]
+        );
+    }
+
+    #[test]
+    fn frontends_are_found_by_name() {
+        let registry = FrontendRegistry::default();
+        assert_eq!(registry.for_name("Tiny").unwrap().extensions(), ["tiny"]);
+        assert_eq!(
+            registry.for_name("CPU-V Assembly").unwrap().extensions(),
+            ["irv", "cpu"]
+        );
+    }
+
+    #[test]
+    fn unknown_frontends_are_an_error() {
+        let registry = FrontendRegistry::default();
+        assert_eq!(
+            registry.for_name("tiny").err(),
+            Some("Unknown frontend tiny! Known frontends: CPU-V Assembly, Tiny".to_string())
+        );
+        assert_eq!(
+            FrontendRegistry::new().for_name("Tiny").err(),
+            Some("Unknown frontend Tiny! Known frontends: ".to_string())
+        );
+    }
+
+    #[test]
+    fn later_registrations_take_over_shared_extensions() {
+        let mut registry = FrontendRegistry::default();
+        registry.register(Upper);
+
+        assert_eq!(registry.for_extension("cpu").unwrap().name(), "Upper");
+        assert_eq!(registry.for_extension("up").unwrap().name(), "Upper");
+        assert_eq!(
+            registry.for_extension("irv").unwrap().name(),
+            "CPU-V Assembly"
+        );
+        assert_eq!(
+            registry.extensions().collect::<Vec<&str>>(),
+            ["cpu", "irv", "tiny", "up"]
+        );
+
+        registry.register(Upper);
+        assert_eq!(registry.extensions().count(), 4);
+        assert!(registry.for_name("Upper").is_ok());
+    }
+}
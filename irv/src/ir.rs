@@ -0,0 +1,398 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{label_name, DataType, InstructionType, Program, Registers};
+
+/// A fully resolved program: labels are turned into instruction addresses, operands are typed and
+/// static variables are given an address in the data section.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct IrProgram {
+    pub extern_functions: Vec<String>,
+    pub data: Vec<DataSlot>,
+    pub labels: Vec<IrLabel>,
+    pub instructions: Vec<IrInstruction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DataSlot {
+    pub name: String,
+    pub ty: DataType,
+    pub address: usize,
+    pub initial_value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IrLabel {
+    pub name: String,
+    pub address: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IrInstruction {
+    pub address: usize,
+    pub ty: InstructionType,
+    pub operands: Vec<Operand>,
+    /// Source line of the instruction, if the frontend recorded one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Operand {
+    Register(String),
+    Immediate(i128),
+    Label {
+        name: String,
+        address: usize,
+    },
+    Variable {
+        name: String,
+        address: usize,
+        ty: DataType,
+    },
+    Function(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IrError {
+    DuplicateLabel(String),
+    DuplicateVariable(String),
+    UnresolvedLabel { label: String, address: usize },
+    UnknownOperand { operand: String, address: usize },
+}
+
+impl fmt::Display for IrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IrError::DuplicateLabel(name) => write!(f, "Label @{} is defined more than once", name),
+            IrError::DuplicateVariable(name) => {
+                write!(f, "Variable \"{}\" is defined more than once", name)
+            }
+            IrError::UnresolvedLabel { label, address } => {
+                write!(f, "{:04}: Jump to undefined label @{}", address, label)
+            }
+            IrError::UnknownOperand { operand, address } => write!(
+                f,
+                "{:04}: \"{}\" is not a register, number, label, variable or extern function",
+                address, operand
+            ),
+        }
+    }
+}
+
+/// Resolves a parsed [`Program`] into its [`IrProgram`].
+///
+/// Instructions are laid out in label order, so the address of an instruction is its index in the
+/// flattened program. Static variables are packed into the data section in declaration order.
+pub fn translate(program: &Program) -> Result<IrProgram, IrError> {
+    let mut ir = IrProgram {
+        extern_functions: program.extern_functions.clone().unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let mut address = 0;
+    for variable in &program.static_variables {
+        if ir.data.iter().any(|slot| slot.name == variable.name) {
+            return Err(IrError::DuplicateVariable(variable.name.clone()));
+        }
+
+        ir.data.push(DataSlot {
+            name: variable.name.clone(),
+            ty: variable.ty.clone(),
+            address,
+            initial_value: variable.inital_value.clone(),
+        });
+        address += variable.ty.size();
+    }
+
+    let labels = program.labels.as_deref().unwrap_or_default();
+
+    let mut address = 0;
+    for label in labels {
+        if ir.labels.iter().any(|l| l.name == label.name()) {
+            return Err(IrError::DuplicateLabel(label.name().to_string()));
+        }
+
+        ir.labels.push(IrLabel {
+            name: label.name().to_string(),
+            address,
+        });
+        address += label.instructions.as_ref().map_or(0, Vec::len);
+    }
+
+    for instruction in labels
+        .iter()
+        .flat_map(|label| label.instructions.iter().flatten())
+    {
+        let address = ir.instructions.len();
+        let operands = instruction
+            .operand_tokens()
+            .into_iter()
+            .map(|token| ir.resolve_operand(token, address))
+            .collect::<Result<Vec<Operand>, IrError>>()?;
+
+        ir.instructions.push(IrInstruction {
+            address,
+            ty: instruction.ty.clone(),
+            operands,
+            line: instruction.line,
+        });
+    }
+
+    Ok(ir)
+}
+
+impl IrProgram {
+    fn resolve_operand(&self, token: &str, address: usize) -> Result<Operand, IrError> {
+        if token.starts_with('@') {
+            let name = label_name(token);
+            return match self.label_address(name) {
+                Some(target) => Ok(Operand::Label {
+                    name: name.to_string(),
+                    address: target,
+                }),
+                None => Err(IrError::UnresolvedLabel {
+                    label: name.to_string(),
+                    address,
+                }),
+            };
+        }
+
+        if Registers::is_register(token) {
+            return Ok(Operand::Register(token.to_string()));
+        }
+
+        if let Some(value) = parse_immediate(token) {
+            return Ok(Operand::Immediate(value));
+        }
+
+        if let Some(slot) = self.data.iter().find(|slot| slot.name == token) {
+            return Ok(Operand::Variable {
+                name: slot.name.clone(),
+                address: slot.address,
+                ty: slot.ty.clone(),
+            });
+        }
+
+        if self.extern_functions.iter().any(|f| f == token) {
+            return Ok(Operand::Function(token.to_string()));
+        }
+
+        Err(IrError::UnknownOperand {
+            operand: token.to_string(),
+            address,
+        })
+    }
+
+    pub fn label_address(&self, name: &str) -> Option<usize> {
+        self.labels
+            .iter()
+            .find(|label| label.name == name)
+            .map(|label| label.address)
+    }
+
+    /// Address of the first instruction compiled from `line`, or from the closest line after it
+    /// when `line` has no code (a blank line or a comment)
+    pub fn address_for_line(&self, line: usize) -> Option<usize> {
+        let closest = self
+            .instructions
+            .iter()
+            .filter_map(|instruction| instruction.line)
+            .filter(|&l| l >= line)
+            .min()?;
+
+        self.instructions
+            .iter()
+            .find(|instruction| instruction.line == Some(closest))
+            .map(|instruction| instruction.address)
+    }
+
+    /// Total size of the data section in bytes
+    pub fn data_size(&self) -> usize {
+        self.data
+            .last()
+            .map_or(0, |slot| slot.address + slot.ty.size())
+    }
+}
+
+/// Parses a decimal (`-12`, `1_000`) or hexadecimal (`0xff`) integer literal.
+pub fn parse_immediate(token: &str) -> Option<i128> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, token),
+    };
+
+    let digits = digits.replace('_', "");
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => {
+            digits.parse::<i128>().ok()?
+        }
+        None => return None,
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(name) => write!(f, "reg {}", name),
+            Operand::Immediate(value) => write!(f, "imm {}", value),
+            Operand::Label { name, address } => write!(f, "label @{} ({:04})", name, address),
+            Operand::Variable { name, address, ty } => write!(
+                f,
+                "var {} [{:#06x} {}]",
+                name,
+                address,
+                format!("{:?}", ty).to_lowercase()
+            ),
+            Operand::Function(name) => write!(f, "fn {}", name),
+        }
+    }
+}
+
+impl fmt::Display for IrProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, ".extern")?;
+        for function in &self.extern_functions {
+            writeln!(f, "    {}", function)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, ".data ; {} bytes", self.data_size())?;
+        for slot in &self.data {
+            writeln!(
+                f,
+                "    {:#06x}  {:<6}  {} = {}",
+                slot.address,
+                format!("{:?}", slot.ty).to_lowercase(),
+                slot.name,
+                slot.initial_value
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, ".program ; {} instructions", self.instructions.len())?;
+        for instruction in &self.instructions {
+            for label in self
+                .labels
+                .iter()
+                .filter(|label| label.address == instruction.address)
+            {
+                writeln!(f, "@{}:", label.name)?;
+            }
+
+            let operands = instruction
+                .operands
+                .iter()
+                .map(Operand::to_string)
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            let line = format!(
+                "    {:04}  {:<5} {}",
+                instruction.address,
+                format!("{:?}", instruction.ty),
+                operands
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        // labels that point past the last instruction (e.g. an empty trailing label)
+        for label in self
+            .labels
+            .iter()
+            .filter(|label| label.address >= self.instructions.len())
+        {
+            writeln!(f, "@{}:", label.name)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{translate, try_parse};
+
+    #[test]
+    fn lines_without_code_map_to_the_next_instruction() {
+        let program = try_parse(
+            ".section .program:\n\
+             @start:\n\
+             \x20   LOAD 1 rax\n\
+             \n\
+             \x20   // comment\n\
+             \x20   INC rax // trailing comment\n\
+             @end:\n\
+             \x20   EXIT 0\n\
+             \n",
+        )
+        .unwrap();
+        let ir = translate(&program).unwrap();
+
+        let addresses = (1..=10)
+            .map(|line| ir.address_for_line(line))
+            .collect::<Vec<Option<usize>>>();
+        assert_eq!(
+            addresses,
+            [
+                Some(0),
+                Some(0),
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(2),
+                Some(2),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn programs_are_dumped_section_by_section() {
+        let program = try_parse(
+            ".section .extern\n\
+             \x20   printf\n\
+             .section .data:\n\
+             \x20   word count 3\n\
+             \x20   byte flag 1\n\
+             .section .program:\n\
+             @start:\n\
+             \x20   LOAD count rax\n\
+             @loop:\n\
+             \x20   DEC rax\n\
+             \x20   CALL printf rax\n\
+             \x20   CMP rax 0\n\
+             \x20   JGT @loop\n\
+             \x20   EXIT 0\n\
+             @end:\n",
+        )
+        .unwrap();
+        let ir = translate(&program).unwrap();
+
+        assert_eq!(
+            ir.to_string(),
+            ".extern\n\
+             \x20   printf\n\
+             \n\
+             .data ; 3 bytes\n\
+             \x20   0x0000  word    count = 3\n\
+             \x20   0x0002  byte    flag = 1\n\
+             \n\
+             .program ; 6 instructions\n\
+             @start:\n\
+             \x20   0000  LOAD  var count [0x0000 word], reg rax\n\
+             @loop:\n\
+             \x20   0001  DEC   reg rax\n\
+             \x20   0002  CALL  fn printf, reg rax\n\
+             \x20   0003  CMP   reg rax, imm 0\n\
+             \x20   0004  JGT   label @loop (0001)\n\
+             \x20   0005  EXIT  imm 0\n\
+             @end:\n"
+        );
+    }
+}
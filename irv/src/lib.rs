@@ -1,5 +1,13 @@
+pub mod binary;
+pub mod frontend;
+pub mod ir;
 pub mod parser;
+pub mod tiny;
 pub mod types;
 
+pub use binary::{assemble, disassemble, instruction_bytes, BinaryError, BINARY_EXTENSION};
+pub use frontend::*;
+pub use ir::*;
 pub use parser::*;
+pub use tiny::TinyFrontend;
 pub use types::*;
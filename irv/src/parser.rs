@@ -1,135 +1,202 @@
-use crate::{DataType, Instruction, Label, Program, Variable};
+use crate::{
+    DataType, Diagnostics, Frontend, Instruction, InstructionValue, Label, Program, Variable,
+};
 use std::str::FromStr;
 
-// TODO: add in label and data parsing
+/// The assembly-like syntax described in the language reference. This is the language CPU-V has
+/// always spoken, and the reference [`Frontend`] implementation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsmFrontend;
+
+impl Frontend for AsmFrontend {
+    fn name(&self) -> &'static str {
+        "CPU-V Assembly"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["irv", "cpu"]
+    }
+
+    fn compile(&self, source: &str) -> Result<Program, Diagnostics> {
+        try_parse(source)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Section {
+    None,
+    Extern,
+    Data,
+    Program,
+}
+
+/// Parses a program, panicking on the first error. Prefer [`try_parse`].
 pub fn parse(file: String) -> Program {
+    match try_parse(&file) {
+        Ok(program) => program,
+        Err(diagnostics) => panic!("{}", diagnostics),
+    }
+}
+
+/// Parses a program, collecting every error instead of stopping at the first one.
+pub fn try_parse(file: &str) -> Result<Program, Diagnostics> {
     let mut program = Program::new();
-    let f = file.split("\n").collect::<Vec<&str>>();
+    let mut diagnostics = Diagnostics::default();
+    let mut section = Section::None;
 
-    let mut skip = 0;
+    for (line_idx, line) in file.lines().enumerate() {
+        let line_num = Some(line_idx + 1);
+        let line = strip_comment(line).trim();
 
-    for (line_num, line) in f.clone().into_iter().enumerate() {
-        if skip > 0 {
-            skip -= 1;
+        if line.is_empty() {
             continue;
         }
 
-        if line.contains(".section") {
-            let (_, section_name) = line.split_once(" ").unwrap();
-
-            match section_name {
-                ".data:" => {
-                    let (variables, s) = parse_variables(&f[line_num + 1..]);
-                    program.static_variables = variables;
-                    skip = s;
-                    continue;
+        if let Some(section_name) = line.strip_prefix(".section") {
+            section = match section_name.trim().trim_end_matches(':') {
+                ".data" => Section::Data,
+                ".extern" => Section::Extern,
+                ".program" => Section::Program,
+                _ => {
+                    diagnostics.push(
+                        line_num,
+                        format!(
+                            "Invalid Section Name: \"{}\"!. Perhaps you were trying to create a @label?",
+                            section_name.trim()
+                        ),
+                    );
+                    Section::None
                 }
+            };
+            continue;
+        }
 
-                ".extern" => {
-                    let (functions, s) = parse_extern(&f[line_num + 1..]);
-                    program.extern_functions = Some(functions);
-                    skip = s;
+        match section {
+            Section::Extern => program
+                .extern_functions
+                .get_or_insert_with(Vec::new)
+                .push(line.to_string()),
+
+            Section::Data => match parse_variable(line) {
+                Ok(variable) => program.static_variables.push(variable),
+                Err(e) => diagnostics.push(line_num, e),
+            },
+
+            Section::Program | Section::None => {
+                if line.starts_with('@') {
+                    program.labels.get_or_insert_with(Vec::new).push(Label {
+                        label_name: line.to_string(),
+                        instructions: Some(Vec::new()),
+                    });
                     continue;
                 }
 
-                ".program:" => {
+                let Some(label) = program.labels.as_mut().and_then(|l| l.last_mut()) else {
+                    diagnostics.push(
+                        line_num,
+                        format!("Instruction \"{}\" must be inside of a @label", line),
+                    );
                     continue;
-                }
-
-                _ => {
-                    panic!(
-                        "Invalid Section Name: \"{}\"!. Perhaps you were trying to create a @label?",
-                        section_name
-                    )
+                };
+
+                match Instruction::from_str(line) {
+                    Ok(instruction) => {
+                        label
+                            .instructions
+                            .get_or_insert_with(Vec::new)
+                            .push(Instruction {
+                                line: line_num,
+                                ..instruction
+                            })
+                    }
+                    Err(e) => diagnostics.push(line_num, e),
                 }
             }
         }
-
-        if line.contains("@") && !has_jump(&line) {
-            let label = line.trim();
-            let result = parse_label(&f[line_num + 1..]);
-
-            skip = result.1;
-
-            if program.labels.is_none() {
-                program.labels = Some(Vec::new());
-            }
-
-            if let Some(ref mut labels) = program.labels {
-                labels.push(Label {
-                    label_name: label.to_string(),
-                    instructions: Some(result.0),
-                });
-            }
-        }
     }
 
-    return program;
-}
-
-pub fn has_jump(line: &&str) -> bool {
-    line.contains("JMP") || line.contains("JEQ") || line.contains("JLT") || line.contains("JGT")
+    if diagnostics.is_empty() {
+        Ok(program)
+    } else {
+        Err(diagnostics)
+    }
 }
 
-pub fn parse_extern(label_functions: &[&str]) -> (Vec<String>, usize) {
-    let mut functions: Vec<String> = Vec::new();
+/// Removes a trailing `// comment`, ignoring any `//` inside of a string literal.
+pub fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut prev = ' ';
 
-    for (line_idx, line) in label_functions.iter().enumerate() {
-        if line.is_empty() {
-            continue;
-        }
-
-        if line.contains(".section") {
-            return (functions, line_idx);
+    for (idx, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '/' if prev == '/' && !in_string => return &line[..idx - 1],
+            _ => {}
         }
-
-        functions.push(line.trim().to_string());
+        prev = c;
     }
 
-    (functions, label_functions.len() - 1)
+    line
 }
 
-pub fn parse_label(label_instructions: &[&str]) -> (Vec<Instruction>, usize) {
-    let mut instructions: Vec<Instruction> = Vec::new();
-
-    for (line_idx, line) in label_instructions.iter().enumerate() {
-        if line.is_empty() {
-            continue;
-        }
+/// Parses a single `type name value` line from the `.data` section.
+pub fn parse_variable(line: &str) -> Result<Variable, String> {
+    let (ty, rest) = line
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| format!("Expected `type name value`, found \"{}\"", line.trim()))?;
+
+    let (name, value) = rest
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| format!("Variable \"{}\" is missing an inital value", rest.trim()))?;
+
+    Ok(Variable {
+        ty: DataType::from_str(ty)?,
+        name: name.to_string(),
+        inital_value: value.trim().to_string(),
+    })
+}
 
-        if line.contains("@") && !has_jump(line) {
-            return (instructions, line_idx);
-        }
+/// Writes a program back out in the assembly syntax understood by [`try_parse`].
+pub fn emit(program: &Program) -> String {
+    let mut out = String::new();
 
-        if line.contains(".section") {
-            return (instructions, line_idx);
+    if let Some(functions) = &program.extern_functions {
+        out.push_str(".section .extern\n");
+        for function in functions {
+            out.push_str(&format!("    {}\n", function));
         }
-
-        instructions.push(Instruction::from_str(line).unwrap())
+        out.push('\n');
     }
 
-    (instructions, label_instructions.len() - 1)
-}
-
-pub fn parse_variables(variable_label: &[&str]) -> (Vec<Variable>, usize) {
-    let mut variables: Vec<Variable> = Vec::new();
-
-    for (line_idx, line) in variable_label.iter().enumerate() {
-        if line.is_empty() {
-            continue;
+    if !program.static_variables.is_empty() {
+        out.push_str(".section .data:\n");
+        for variable in &program.static_variables {
+            out.push_str(&format!(
+                "    {} {} {}\n",
+                format!("{:?}", variable.ty).to_lowercase(),
+                variable.name,
+                variable.inital_value
+            ));
         }
+        out.push('\n');
+    }
 
-        let var_info = line.trim().splitn(3, " ").collect::<Vec<&str>>();
-        if line.contains(".section") {
-            return (variables, line_idx);
+    out.push_str(".section .program:\n");
+    for label in program.labels.iter().flatten() {
+        out.push_str(&format!("{}\n", label.label_name));
+
+        for instruction in label.instructions.iter().flatten() {
+            let value = match &instruction.val {
+                InstructionValue::SingleValue(val) => val.clone(),
+                InstructionValue::MultipleValue((left, right)) => format!("{},{}", left, right),
+            };
+            out.push_str(format!("    {:?} {}", instruction.ty, value).trim_end());
+            out.push('\n');
         }
-
-        variables.push(Variable {
-            ty: DataType::from_str(var_info[0]).unwrap(),
-            name: var_info[1].to_string(),
-            inital_value: var_info[2].to_string(),
-        })
+        out.push('\n');
     }
 
-    (variables, variable_label.len())
+    out
 }
@@ -0,0 +1,1243 @@
+//! Tiny, a small C-like language that compiles down to irv.
+//!
+//! ```text
+//! var total = 0;
+//!
+//! fn square(x) {
+//!     return x * x;
+//! }
+//!
+//! var i = 0;
+//! while (i < 5) {
+//!     total = total + square(i);
+//!     i = i + 1;
+//! }
+//!
+//! if (total >= 30) {
+//!     print("big");
+//! } else {
+//!     print(total);
+//! }
+//! ```
+//!
+//! Every variable is a `qword` in the data section. Top-level statements run from `@start`,
+//! functions become `@fn_<name>` labels that take their parameters in `<name>.<param>` variables and
+//! return their result in `srax`. Expressions are evaluated on a small stack of signed registers
+//! (`srax`, `srbx`, ...). Since parameters and locals are static, a function that calls itself,
+//! directly or through other functions, is rejected.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    DataType, Diagnostics, Frontend, Instruction, InstructionType, InstructionValue, Label,
+    Program, Registers, Variable,
+};
+
+/// Registers used as the expression stack, in push order. They are signed so that negative values
+/// compare and print as such.
+const TEMPS: [&str; 6] = ["srax", "srbx", "srcx", "srdx", "srsi", "srdi"];
+
+const KEYWORDS: [&str; 7] = ["var", "fn", "if", "else", "while", "return", "print"];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TinyFrontend;
+
+impl Frontend for TinyFrontend {
+    fn name(&self) -> &'static str {
+        "Tiny"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["tiny"]
+    }
+
+    fn compile(&self, source: &str) -> Result<Program, Diagnostics> {
+        let tokens = lex(source)?;
+        let items = Parser { tokens, pos: 0 }.parse_program()?;
+        Codegen::default().compile(&items)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Number(i128),
+    Ident(String),
+    Str(String),
+    Sym(&'static str),
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    line: usize,
+}
+
+const SYMBOLS: [&str; 17] = [
+    "==", "!=", "<=", ">=", "<", ">", "=", "+", "-", "*", "/", "(", ")", "{", "}", ",", ";",
+];
+
+fn lex(source: &str) -> Result<Vec<Token>, Diagnostics> {
+    let mut tokens = Vec::new();
+    let mut diagnostics = Diagnostics::default();
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let line_num = line_idx + 1;
+        let line = crate::strip_comment(line);
+        let mut rest = line.trim_start();
+
+        while !rest.is_empty() {
+            let c = rest.chars().next().unwrap();
+
+            let len = if c.is_ascii_digit() {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                match crate::parse_immediate(&rest[..len]) {
+                    Some(value) => tokens.push(Token {
+                        tok: Tok::Number(value),
+                        line: line_num,
+                    }),
+                    None => diagnostics.push(
+                        Some(line_num),
+                        format!("Invalid number \"{}\"", &rest[..len]),
+                    ),
+                }
+                len
+            } else if c.is_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                tokens.push(Token {
+                    tok: Tok::Ident(rest[..len].to_string()),
+                    line: line_num,
+                });
+                len
+            } else if c == '"' {
+                match rest[1..].find('"') {
+                    Some(end) => {
+                        tokens.push(Token {
+                            tok: Tok::Str(rest[1..end + 1].to_string()),
+                            line: line_num,
+                        });
+                        end + 2
+                    }
+                    None => {
+                        diagnostics.push(Some(line_num), "Unterminated string");
+                        rest.len()
+                    }
+                }
+            } else if let Some(sym) = SYMBOLS.iter().find(|sym| rest.starts_with(*sym)) {
+                tokens.push(Token {
+                    tok: Tok::Sym(sym),
+                    line: line_num,
+                });
+                sym.len()
+            } else {
+                diagnostics.push(Some(line_num), format!("Unexpected character '{}'", c));
+                c.len_utf8()
+            };
+
+            rest = rest[len..].trim_start();
+        }
+    }
+
+    tokens.push(Token {
+        tok: Tok::Eof,
+        line: source.lines().count().max(1),
+    });
+
+    if diagnostics.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i128),
+    Var(String, usize),
+    Neg(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>, usize),
+}
+
+impl Expr {
+    /// Names of the functions called anywhere in the expression
+    fn calls(&self, calls: &mut Vec<String>) {
+        match self {
+            Expr::Number(_) | Expr::Var(..) => {}
+            Expr::Neg(value) => value.calls(calls),
+            Expr::Binary(_, left, right) => {
+                left.calls(calls);
+                right.calls(calls);
+            }
+            Expr::Call(name, args, _) => {
+                calls.push(name.clone());
+                args.iter().for_each(|arg| arg.calls(calls));
+            }
+        }
+    }
+
+    fn has_call(&self) -> bool {
+        let mut calls = Vec::new();
+        self.calls(&mut calls);
+        !calls.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Cond {
+    left: Expr,
+    /// Comparison operator and right hand side, `None` for a bare `if (x)`
+    compare: Option<(&'static str, Expr)>,
+    line: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Var(String, Option<Expr>, usize),
+    Assign(String, Expr, usize),
+    If(Cond, Vec<Stmt>, Vec<Stmt>),
+    While(Cond, Vec<Stmt>),
+    Print(Expr, usize),
+    PrintStr(String, usize),
+    Return(Option<Expr>, usize),
+    Expr(Expr, usize),
+}
+
+impl Stmt {
+    fn line(&self) -> usize {
+        match self {
+            Stmt::Var(_, _, line)
+            | Stmt::Assign(_, _, line)
+            | Stmt::Print(_, line)
+            | Stmt::PrintStr(_, line)
+            | Stmt::Return(_, line)
+            | Stmt::Expr(_, line) => *line,
+            Stmt::If(cond, ..) | Stmt::While(cond, _) => cond.line,
+        }
+    }
+
+    /// Names of the functions called anywhere in the statement, including nested blocks
+    fn calls(&self, calls: &mut Vec<String>) {
+        let cond_calls = |cond: &Cond, calls: &mut Vec<String>| {
+            cond.left.calls(calls);
+            if let Some((_, right)) = &cond.compare {
+                right.calls(calls);
+            }
+        };
+
+        match self {
+            Stmt::Var(_, None, _) | Stmt::PrintStr(..) | Stmt::Return(None, _) => {}
+            Stmt::Var(_, Some(value), _)
+            | Stmt::Assign(_, value, _)
+            | Stmt::Print(value, _)
+            | Stmt::Return(Some(value), _)
+            | Stmt::Expr(value, _) => value.calls(calls),
+            Stmt::If(cond, then, otherwise) => {
+                cond_calls(cond, calls);
+                then.iter()
+                    .chain(otherwise)
+                    .for_each(|stmt| stmt.calls(calls));
+            }
+            Stmt::While(cond, body) => {
+                cond_calls(cond, calls);
+                body.iter().for_each(|stmt| stmt.calls(calls));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    Function {
+        name: String,
+        params: Vec<String>,
+        body: Vec<Stmt>,
+        line: usize,
+    },
+    Stmt(Stmt),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+type ParseResult<T> = Result<T, crate::Diagnostic>;
+
+impl Parser {
+    fn parse_program(mut self) -> Result<Vec<Item>, Diagnostics> {
+        let mut items = Vec::new();
+
+        while self.peek() != &Tok::Eof {
+            let item = if self.peek_ident("fn") {
+                self.function()
+            } else {
+                self.statement().map(Item::Stmt)
+            };
+
+            // There is no error recovery, the first syntax error ends parsing
+            items.push(item.map_err(Diagnostics::from)?);
+        }
+
+        Ok(items)
+    }
+
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].tok
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].line
+    }
+
+    fn peek_ident(&self, ident: &str) -> bool {
+        matches!(self.peek(), Tok::Ident(i) if i == ident)
+    }
+
+    fn peek_sym(&self, sym: &str) -> bool {
+        matches!(self.peek(), Tok::Sym(s) if *s == sym)
+    }
+
+    fn next(&mut self) -> Tok {
+        let tok = self.tokens[self.pos].tok.clone();
+        if tok != Tok::Eof {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> ParseResult<T> {
+        Err(crate::Diagnostic::new(Some(self.line()), message))
+    }
+
+    fn expect_sym(&mut self, sym: &str) -> ParseResult<()> {
+        if self.peek_sym(sym) {
+            self.next();
+            return Ok(());
+        }
+        self.error(format!(
+            "Expected '{}', found {}",
+            sym,
+            describe(self.peek())
+        ))
+    }
+
+    fn expect_ident(&mut self) -> ParseResult<String> {
+        match self.peek().clone() {
+            Tok::Ident(ident) if KEYWORDS.contains(&ident.as_str()) => self.error(format!(
+                "\"{}\" is a keyword and cannot be used as a name",
+                ident
+            )),
+            Tok::Ident(ident) if Registers::is_register(&ident) => self.error(format!(
+                "\"{}\" is a register name and cannot be used as a name",
+                ident
+            )),
+            Tok::Ident(ident) => {
+                self.next();
+                Ok(ident)
+            }
+            tok => self.error(format!("Expected a name, found {}", describe(&tok))),
+        }
+    }
+
+    fn function(&mut self) -> ParseResult<Item> {
+        let line = self.line();
+        self.next();
+        let name = self.expect_ident()?;
+
+        self.expect_sym("(")?;
+        let mut params = Vec::new();
+        while !self.peek_sym(")") {
+            params.push(self.expect_ident()?);
+            if !self.peek_sym(")") {
+                self.expect_sym(",")?;
+            }
+        }
+        self.expect_sym(")")?;
+
+        Ok(Item::Function {
+            name,
+            params,
+            body: self.block()?,
+            line,
+        })
+    }
+
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
+        self.expect_sym("{")?;
+        let mut body = Vec::new();
+        while !self.peek_sym("}") {
+            if self.peek() == &Tok::Eof {
+                return self.error("Expected '}' before the end of the file");
+            }
+            body.push(self.statement()?);
+        }
+        self.expect_sym("}")?;
+        Ok(body)
+    }
+
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        let line = self.line();
+
+        let stmt = match self.peek().clone() {
+            Tok::Ident(kw) if kw == "var" => {
+                self.next();
+                let name = self.expect_ident()?;
+                let value = if self.peek_sym("=") {
+                    self.next();
+                    Some(self.expr()?)
+                } else {
+                    None
+                };
+                Stmt::Var(name, value, line)
+            }
+
+            Tok::Ident(kw) if kw == "if" => {
+                self.next();
+                let cond = self.condition()?;
+                let then = self.block()?;
+                let otherwise = if self.peek_ident("else") {
+                    self.next();
+                    if self.peek_ident("if") {
+                        vec![self.statement()?]
+                    } else {
+                        self.block()?
+                    }
+                } else {
+                    Vec::new()
+                };
+                return Ok(Stmt::If(cond, then, otherwise));
+            }
+
+            Tok::Ident(kw) if kw == "while" => {
+                self.next();
+                let cond = self.condition()?;
+                return Ok(Stmt::While(cond, self.block()?));
+            }
+
+            Tok::Ident(kw) if kw == "print" => {
+                self.next();
+                self.expect_sym("(")?;
+                let stmt = match self.peek().clone() {
+                    Tok::Str(s) => {
+                        self.next();
+                        Stmt::PrintStr(s, line)
+                    }
+                    _ => Stmt::Print(self.expr()?, line),
+                };
+                self.expect_sym(")")?;
+                stmt
+            }
+
+            Tok::Ident(kw) if kw == "return" => {
+                self.next();
+                let value = if self.peek_sym(";") {
+                    None
+                } else {
+                    Some(self.expr()?)
+                };
+                Stmt::Return(value, line)
+            }
+
+            Tok::Ident(_) if self.tokens[self.pos + 1].tok == Tok::Sym("=") => {
+                let name = self.expect_ident()?;
+                self.next();
+                Stmt::Assign(name, self.expr()?, line)
+            }
+
+            _ => Stmt::Expr(self.expr()?, line),
+        };
+
+        self.expect_sym(";")?;
+        Ok(stmt)
+    }
+
+    fn condition(&mut self) -> ParseResult<Cond> {
+        let line = self.line();
+        self.expect_sym("(")?;
+        let left = self.expr()?;
+        let compare = match self.peek().clone() {
+            Tok::Sym(op @ ("==" | "!=" | "<" | "<=" | ">" | ">=")) => {
+                self.next();
+                Some((op, self.expr()?))
+            }
+            _ => None,
+        };
+        self.expect_sym(")")?;
+        Ok(Cond {
+            left,
+            compare,
+            line,
+        })
+    }
+
+    fn expr(&mut self) -> ParseResult<Expr> {
+        let mut left = self.term()?;
+        while let Tok::Sym(op @ ("+" | "-")) = self.peek().clone() {
+            self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> ParseResult<Expr> {
+        let mut left = self.factor()?;
+        while let Tok::Sym(op @ ("*" | "/")) = self.peek().clone() {
+            self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.factor()?));
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> ParseResult<Expr> {
+        let line = self.line();
+        match self.peek().clone() {
+            Tok::Number(value) => {
+                self.next();
+                Ok(Expr::Number(value))
+            }
+            Tok::Sym("-") => {
+                self.next();
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Tok::Sym("(") => {
+                self.next();
+                let expr = self.expr()?;
+                self.expect_sym(")")?;
+                Ok(expr)
+            }
+            Tok::Ident(_) => {
+                let name = self.expect_ident()?;
+                if !self.peek_sym("(") {
+                    return Ok(Expr::Var(name, line));
+                }
+
+                self.next();
+                let mut args = Vec::new();
+                while !self.peek_sym(")") {
+                    args.push(self.expr()?);
+                    if !self.peek_sym(")") {
+                        self.expect_sym(",")?;
+                    }
+                }
+                self.expect_sym(")")?;
+                Ok(Expr::Call(name, args, line))
+            }
+            tok => self.error(format!("Expected an expression, found {}", describe(&tok))),
+        }
+    }
+}
+
+fn describe(tok: &Tok) -> String {
+    match tok {
+        Tok::Number(n) => format!("number {}", n),
+        Tok::Ident(i) => format!("\"{}\"", i),
+        Tok::Str(s) => format!("string \"{}\"", s),
+        Tok::Sym(s) => format!("'{}'", s),
+        Tok::Eof => "end of file".to_string(),
+    }
+}
+
+#[derive(Default)]
+struct Codegen {
+    program: Program,
+    diagnostics: Diagnostics,
+
+    /// Function name to parameter names
+    functions: BTreeMap<String, Vec<String>>,
+    globals: Vec<String>,
+    /// The function being compiled and its locals, `None` at the top level
+    scope: Option<(String, Vec<String>)>,
+
+    next_label: usize,
+    strings: usize,
+    /// Call sites compiled so far, each one gets its own argument and spill variables
+    calls: usize,
+    /// Source line of the statement being compiled, attached to every emitted instruction
+    line: Option<usize>,
+}
+
+impl Codegen {
+    fn compile(mut self, items: &[Item]) -> Result<Program, Diagnostics> {
+        for item in items {
+            match item {
+                Item::Function {
+                    name, params, line, ..
+                } => {
+                    if self
+                        .functions
+                        .insert(name.clone(), params.clone())
+                        .is_some()
+                    {
+                        self.diagnostics.push(
+                            Some(*line),
+                            format!("Function \"{}\" is defined more than once", name),
+                        );
+                    }
+                    for param in params {
+                        self.declare(format!("{}.{}", name, param));
+                    }
+                }
+                Item::Stmt(stmt) => self.declare_globals(std::slice::from_ref(stmt)),
+            }
+        }
+        self.check_recursion(items);
+
+        self.label("@start:".to_string());
+        for item in items {
+            if let Item::Stmt(stmt) = item {
+                self.statement(stmt);
+            }
+        }
+        self.line = None;
+        self.emit(InstructionType::EXIT, &["0"]);
+
+        for item in items {
+            if let Item::Function {
+                name,
+                params,
+                body,
+                line,
+            } = item
+            {
+                self.scope = Some((name.clone(), params.clone()));
+                self.label(format!("@fn_{}:", name));
+                for stmt in body {
+                    self.statement(stmt);
+                }
+                if !matches!(body.last(), Some(Stmt::Return(..))) {
+                    self.line = Some(*line);
+                    self.emit(InstructionType::RET, &[]);
+                }
+            }
+        }
+
+        if self.diagnostics.is_empty() {
+            Ok(self.program)
+        } else {
+            Err(self.diagnostics)
+        }
+    }
+
+    /// Top-level variables are global, including the ones declared inside of an if or while
+    fn declare_globals(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Var(name, _, _) if !self.globals.contains(name) => {
+                    self.globals.push(name.clone());
+                    self.declare(name.clone());
+                }
+                Stmt::If(_, then, otherwise) => {
+                    self.declare_globals(then);
+                    self.declare_globals(otherwise);
+                }
+                Stmt::While(_, body) => self.declare_globals(body),
+                _ => {}
+            }
+        }
+    }
+
+    /// Parameters and locals are static, so a call to a function that has not returned yet would
+    /// overwrite the ones of the running call
+    fn check_recursion(&mut self, items: &[Item]) {
+        let mut graph = BTreeMap::new();
+        for item in items {
+            if let Item::Function { name, body, .. } = item {
+                let mut calls = Vec::new();
+                body.iter().for_each(|stmt| stmt.calls(&mut calls));
+                graph.insert(name.clone(), calls);
+            }
+        }
+
+        for item in items {
+            let Item::Function { name, line, .. } = item else {
+                continue;
+            };
+
+            let mut path = vec![name.clone()];
+            if call_path(&graph, name, name, &mut path, &mut BTreeSet::new()) {
+                self.diagnostics.push(
+                    Some(*line),
+                    format!(
+                        "Function \"{}\" is recursive ({}), which is not supported since \
+                         parameters and locals are static variables",
+                        name,
+                        path.join(" -> ")
+                    ),
+                );
+            }
+        }
+    }
+
+    fn declare(&mut self, name: String) {
+        if self.program.static_variables.iter().any(|v| v.name == name) {
+            return;
+        }
+
+        self.program.static_variables.push(Variable {
+            name,
+            ty: DataType::QWord,
+            inital_value: "0".to_string(),
+        });
+    }
+
+    fn label(&mut self, label_name: String) {
+        self.program
+            .labels
+            .get_or_insert_with(Vec::new)
+            .push(Label {
+                label_name,
+                instructions: Some(Vec::new()),
+            });
+    }
+
+    fn new_label(&mut self, kind: &str) -> String {
+        self.next_label += 1;
+        format!("@{}_{}", kind, self.next_label)
+    }
+
+    fn emit(&mut self, ty: InstructionType, operands: &[&str]) {
+        let label = self
+            .program
+            .labels
+            .as_mut()
+            .and_then(|labels| labels.last_mut())
+            .expect("code is always emitted after @start");
+
+        label
+            .instructions
+            .get_or_insert_with(Vec::new)
+            .push(Instruction {
+                ty,
+                val: InstructionValue::SingleValue(operands.join(" ")),
+                line: self.line,
+            });
+    }
+
+    /// Resolves a source variable to its data section name
+    fn variable(&mut self, name: &str, line: usize) -> String {
+        if let Some((function, locals)) = &self.scope {
+            if locals.iter().any(|local| local == name) {
+                return format!("{}.{}", function, name);
+            }
+        }
+
+        if !self.globals.iter().any(|global| global == name) {
+            self.diagnostics
+                .push(Some(line), format!("Unknown variable \"{}\"", name));
+        }
+        name.to_string()
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        self.line = Some(stmt.line());
+        match stmt {
+            Stmt::Var(name, value, line) => {
+                let target = match &mut self.scope {
+                    Some((function, locals)) => {
+                        if !locals.contains(name) {
+                            locals.push(name.clone());
+                        }
+                        format!("{}.{}", function, name)
+                    }
+                    None => self.variable(name, *line),
+                };
+                self.declare(target.clone());
+
+                let value = match value {
+                    Some(value) => self.operand(value, 0),
+                    None => "0".to_string(),
+                };
+                self.emit(InstructionType::LOAD, &[&value, &target]);
+            }
+
+            Stmt::Assign(name, value, line) => {
+                let target = self.variable(name, *line);
+                let value = self.operand(value, 0);
+                self.emit(InstructionType::LOAD, &[&value, &target]);
+            }
+
+            Stmt::If(cond, then, otherwise) => {
+                let else_label = self.new_label("if_else");
+                let end_label = self.new_label("if_end");
+
+                self.condition(cond, &else_label);
+                then.iter().for_each(|stmt| self.statement(stmt));
+
+                if otherwise.is_empty() {
+                    self.label(format!("{}:", else_label));
+                } else {
+                    self.line = Some(cond.line);
+                    self.emit(InstructionType::JMP, &[&end_label]);
+                    self.label(format!("{}:", else_label));
+                    otherwise.iter().for_each(|stmt| self.statement(stmt));
+                }
+                self.label(format!("{}:", end_label));
+            }
+
+            Stmt::While(cond, body) => {
+                let top_label = self.new_label("while");
+                let end_label = self.new_label("while_end");
+
+                self.label(format!("{}:", top_label));
+                self.condition(cond, &end_label);
+                body.iter().for_each(|stmt| self.statement(stmt));
+                self.line = Some(cond.line);
+                self.emit(InstructionType::JMP, &[&top_label]);
+                self.label(format!("{}:", end_label));
+            }
+
+            Stmt::Print(value, _) => {
+                self.use_printf();
+                let reg = self.expr(value, 0);
+                self.emit(InstructionType::CALL, &["printf", reg]);
+            }
+
+            Stmt::PrintStr(value, line) => {
+                let Some(ty) = [
+                    DataType::Str4,
+                    DataType::Str8,
+                    DataType::Str16,
+                    DataType::Str32,
+                    DataType::Str64,
+                    DataType::Str128,
+                ]
+                .into_iter()
+                .find(|ty| ty.size() >= value.len()) else {
+                    self.diagnostics.push(
+                        Some(*line),
+                        "Strings can be at most 128 bytes long".to_string(),
+                    );
+                    return;
+                };
+
+                self.use_printf();
+                self.strings += 1;
+                let name = format!("str.{}", self.strings);
+                self.program.static_variables.push(Variable {
+                    name: name.clone(),
+                    ty,
+                    inital_value: format!("\"{}\"", value),
+                });
+                self.emit(InstructionType::CALL, &["printf", &name]);
+            }
+
+            Stmt::Return(value, line) => {
+                if self.scope.is_none() {
+                    self.diagnostics
+                        .push(Some(*line), "return can only be used inside of a function");
+                    return;
+                }
+
+                if let Some(value) = value {
+                    self.expr(value, 0);
+                }
+                self.emit(InstructionType::RET, &[]);
+            }
+
+            Stmt::Expr(value, _) => {
+                self.expr(value, 0);
+            }
+        }
+    }
+
+    fn use_printf(&mut self) {
+        let functions = self.program.extern_functions.get_or_insert_with(Vec::new);
+        if !functions.iter().any(|f| f == "printf") {
+            functions.push("printf".to_string());
+        }
+    }
+
+    /// Evaluates a condition, jumping to `false_label` when it does not hold
+    fn condition(&mut self, cond: &Cond, false_label: &str) {
+        let left = self.expr(&cond.left, 0);
+
+        let Some((op, right)) = &cond.compare else {
+            self.emit(InstructionType::CMP, &[left, "0"]);
+            self.emit(InstructionType::JEQ, &[false_label]);
+            return;
+        };
+
+        let right = self.operand(right, 1);
+        self.emit(InstructionType::CMP, &[left, &right]);
+
+        let jumps: &[InstructionType] = match *op {
+            "==" => &[InstructionType::JLT, InstructionType::JGT],
+            "!=" => &[InstructionType::JEQ],
+            "<" => &[InstructionType::JEQ, InstructionType::JGT],
+            "<=" => &[InstructionType::JGT],
+            ">" => &[InstructionType::JEQ, InstructionType::JLT],
+            ">=" => &[InstructionType::JLT],
+            _ => unreachable!("the parser only produces comparison operators"),
+        };
+
+        for jump in jumps {
+            self.emit(jump.clone(), &[false_label]);
+        }
+    }
+
+    /// Numbers and variables can be used as operands directly, anything else goes into a register
+    fn operand(&mut self, expr: &Expr, depth: usize) -> String {
+        match expr {
+            Expr::Number(value) => value.to_string(),
+            Expr::Var(name, line) => self.variable(name, *line),
+            _ => self.expr(expr, depth).to_string(),
+        }
+    }
+
+    /// Evaluates an expression into the register at `depth` on the expression stack
+    fn expr(&mut self, expr: &Expr, depth: usize) -> &'static str {
+        let Some(&reg) = TEMPS.get(depth) else {
+            self.diagnostics.push(
+                None,
+                "Expression is too deeply nested, split it into several variables",
+            );
+            return TEMPS[0];
+        };
+
+        match expr {
+            Expr::Number(value) => self.emit(InstructionType::LOAD, &[&value.to_string(), reg]),
+
+            Expr::Var(name, line) => {
+                let var = self.variable(name, *line);
+                self.emit(InstructionType::LOAD, &[&var, reg]);
+            }
+
+            Expr::Neg(value) => {
+                self.expr(value, depth);
+                self.emit(InstructionType::MUL, &[reg, "-1"]);
+            }
+
+            Expr::Binary(op, left, right) => {
+                self.expr(left, depth);
+                let right = self.operand(right, depth + 1);
+                let ty = match *op {
+                    "+" => InstructionType::ADD,
+                    "-" => InstructionType::SUB,
+                    "*" => InstructionType::MUL,
+                    "/" => InstructionType::DIV,
+                    _ => unreachable!("the parser only produces arithmetic operators"),
+                };
+                self.emit(ty, &[reg, &right]);
+            }
+
+            Expr::Call(name, args, line) => self.call(name, args, *line, depth),
+        }
+
+        reg
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], line: usize, depth: usize) {
+        let Some(params) = self.functions.get(name).cloned() else {
+            self.diagnostics
+                .push(Some(line), format!("Unknown function \"{}\"", name));
+            return;
+        };
+
+        if params.len() != args.len() {
+            self.diagnostics.push(
+                Some(line),
+                format!(
+                    "\"{}\" takes {} argument(s) but {} were given",
+                    name,
+                    params.len(),
+                    args.len()
+                ),
+            );
+            return;
+        }
+
+        // without recursion a call site can't be reached again before its call returned, so
+        // variables of the call site are not overwritten while they are in use
+        self.calls += 1;
+        let site = format!("call.{}", self.calls);
+
+        // the registers below `depth` hold values of the expression we are in the middle of,
+        // the callee is free to overwrite them so they are saved around the call
+        let spills = (0..depth)
+            .map(|i| format!("{}.spill.{}", site, i))
+            .collect::<Vec<String>>();
+
+        for (spill, reg) in spills.iter().zip(TEMPS) {
+            self.declare(spill.clone());
+            self.emit(InstructionType::LOAD, &[reg, spill]);
+        }
+
+        // an argument calling the same function, like `f(1, f(2, 3))`, would overwrite the
+        // parameters set before it, so those arguments are all evaluated before any is passed
+        let staged = args.iter().any(Expr::has_call);
+        let mut passed = Vec::new();
+        for (index, (param, arg)) in params.iter().zip(args).enumerate() {
+            let param = format!("{}.{}", name, param);
+            let value = self.operand(arg, depth);
+            if staged {
+                let slot = format!("{}.arg.{}", site, index);
+                self.declare(slot.clone());
+                self.emit(InstructionType::LOAD, &[&value, &slot]);
+                passed.push((slot, param));
+            } else {
+                self.emit(InstructionType::LOAD, &[&value, &param]);
+            }
+        }
+        for (slot, param) in &passed {
+            self.emit(InstructionType::LOAD, &[slot, param]);
+        }
+
+        self.emit(InstructionType::CALL, &[&format!("@fn_{}", name)]);
+
+        if depth > 0 {
+            self.emit(InstructionType::LOAD, &[TEMPS[0], TEMPS[depth]]);
+        }
+        for (spill, reg) in spills.iter().zip(TEMPS) {
+            self.emit(InstructionType::LOAD, &[spill, reg]);
+        }
+    }
+}
+
+/// Looks for a chain of calls from `from` to `to`, appending the functions on it to `path`
+fn call_path(
+    graph: &BTreeMap<String, Vec<String>>,
+    from: &str,
+    to: &str,
+    path: &mut Vec<String>,
+    visited: &mut BTreeSet<String>,
+) -> bool {
+    for callee in graph.get(from).into_iter().flatten() {
+        path.push(callee.clone());
+        if callee == to {
+            return true;
+        }
+        if visited.insert(callee.clone()) && call_path(graph, callee, to, path, visited) {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{emit, translate, Diagnostic};
+
+    fn compile(source: &str) -> Result<Program, Diagnostics> {
+        TinyFrontend.compile(source)
+    }
+
+    fn errors(source: &str) -> Vec<String> {
+        compile(source)
+            .expect_err("the program should not compile")
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect()
+    }
+
+    /// Instructions of a label as `TY operands` lines
+    fn code(program: &Program, label: &str) -> Vec<String> {
+        program
+            .labels
+            .iter()
+            .flatten()
+            .find(|l| l.label_name == label)
+            .and_then(|l| l.instructions.clone())
+            .unwrap_or_default()
+            .iter()
+            .map(|instruction| match &instruction.val {
+                InstructionValue::SingleValue(val) => format!("{:?} {}", instruction.ty, val),
+                InstructionValue::MultipleValue((l, r)) => {
+                    format!("{:?} {},{}", instruction.ty, l, r)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lexer_splits_tokens_and_tracks_lines() {
+        let tokens = lex("var x = 0x10; // comment\nif (x <= -2) print(\"hi\");").unwrap();
+        let toks = tokens.iter().map(|t| t.tok.clone()).collect::<Vec<Tok>>();
+        assert_eq!(
+            toks,
+            [
+                Tok::Ident("var".into()),
+                Tok::Ident("x".into()),
+                Tok::Sym("="),
+                Tok::Number(16),
+                Tok::Sym(";"),
+                Tok::Ident("if".into()),
+                Tok::Sym("("),
+                Tok::Ident("x".into()),
+                Tok::Sym("<="),
+                Tok::Sym("-"),
+                Tok::Number(2),
+                Tok::Sym(")"),
+                Tok::Ident("print".into()),
+                Tok::Sym("("),
+                Tok::Str("hi".into()),
+                Tok::Sym(")"),
+                Tok::Sym(";"),
+                Tok::Eof,
+            ]
+        );
+        assert_eq!(tokens[4].line, 1);
+        assert_eq!(tokens[5].line, 2);
+    }
+
+    #[test]
+    fn lexer_reports_every_bad_token() {
+        let diagnostics = lex("var a = 12ab;\nvar s = \"open\nx = 1 $ 2;").unwrap_err();
+        let messages = diagnostics
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            messages,
+            [
+                "line 1: Invalid number \"12ab\"",
+                "line 2: Unterminated string",
+                "line 3: Unexpected character '$'",
+            ]
+        );
+    }
+
+    #[test]
+    fn parser_respects_precedence() {
+        let tokens = lex("x = 1 + 2 * -3 - 4;").unwrap();
+        let items = Parser { tokens, pos: 0 }.parse_program().unwrap();
+        let [Item::Stmt(Stmt::Assign(name, value, 1))] = items.as_slice() else {
+            panic!("expected one assignment, got {:?}", items);
+        };
+        assert_eq!(name, "x");
+        // ((1 + (2 * -3)) - 4)
+        assert_eq!(
+            format!("{:?}", value),
+            "Binary(\"-\", Binary(\"+\", Number(1), Binary(\"*\", Number(2), \
+             Neg(Number(3)))), Number(4))"
+        );
+    }
+
+    #[test]
+    fn parser_reports_the_first_syntax_error() {
+        assert_eq!(
+            errors("var x = 1\nvar y = 2;"),
+            ["line 2: Expected ';', found \"var\""]
+        );
+        assert_eq!(
+            errors("var while = 1;"),
+            ["line 1: \"while\" is a keyword and cannot be used as a name"]
+        );
+        assert_eq!(
+            errors("var rax = 1;"),
+            ["line 1: \"rax\" is a register name and cannot be used as a name"]
+        );
+        assert_eq!(
+            errors("fn f() {\n  return 1;\n"),
+            ["line 2: Expected '}' before the end of the file"]
+        );
+    }
+
+    #[test]
+    fn codegen_reports_semantic_errors() {
+        assert_eq!(
+            errors("x = 1;\nfn f(a) { return a; }\nvar y = f(1, 2);\nreturn 1;"),
+            [
+                "line 1: Unknown variable \"x\"",
+                "line 3: \"f\" takes 1 argument(s) but 2 were given",
+                "line 4: return can only be used inside of a function",
+            ]
+        );
+    }
+
+    #[test]
+    fn variables_declared_in_top_level_blocks_are_globals() {
+        let program = compile("if (1) { var x = 1; print(x); }").unwrap();
+        assert_eq!(program.static_variables[0].name, "x");
+        assert_eq!(
+            code(&program, "@start:"),
+            [
+                "LOAD 1 srax",
+                "CMP srax 0",
+                "JEQ @if_else_1",
+                "LOAD 1 x",
+                "LOAD x srax",
+                "CALL printf srax",
+            ]
+        );
+        assert_eq!(code(&program, "@if_end_2:"), ["EXIT 0"]);
+
+        let program =
+            compile("var i = 0;\nwhile (i < 2) { var y = i; i = i + 1; }\nfn get() { return y; }")
+                .unwrap();
+        let names = program
+            .static_variables
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, ["i", "y"]);
+    }
+
+    #[test]
+    fn expressions_use_signed_registers() {
+        let program = compile("var a = -5;\nprint(a * 2);").unwrap();
+        assert_eq!(
+            code(&program, "@start:"),
+            [
+                "LOAD 5 srax",
+                "MUL srax -1",
+                "LOAD srax a",
+                "LOAD a srax",
+                "MUL srax 2",
+                "CALL printf srax",
+                "EXIT 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn recursion_is_rejected() {
+        assert_eq!(
+            errors("fn fib(n) {\n  if (n < 2) { return n; }\n  return fib(n - 1) + fib(n - 2);\n}"),
+            [
+                "line 1: Function \"fib\" is recursive (fib -> fib), which is not supported since \
+              parameters and locals are static variables"
+            ]
+        );
+
+        let messages = errors("fn even(n) { return odd(n - 1); }\nfn odd(n) { return even(n); }");
+        assert_eq!(messages.len(), 2);
+        assert!(
+            messages[0].contains("(even -> odd -> even)"),
+            "{:?}",
+            messages
+        );
+        assert!(
+            messages[1].contains("(odd -> even -> odd)"),
+            "{:?}",
+            messages
+        );
+    }
+
+    #[test]
+    fn nested_calls_stage_their_arguments() {
+        let program = compile("fn f(a, b) { return a - b; }\nvar x = f(1, f(2, 3));").unwrap();
+        let start = code(&program, "@start:");
+        // the outer arguments are only passed once the inner call returned
+        let inner_call = start.iter().position(|i| i == "CALL @fn_f").unwrap();
+        let outer_a = start
+            .iter()
+            .position(|i| i == "LOAD call.1.arg.0 f.a")
+            .unwrap();
+        assert!(inner_call < outer_a, "{:#?}", start);
+    }
+
+    #[test]
+    fn compiled_programs_translate() {
+        let source = include_str!("../../examples/example.tiny");
+        let program = compile(source).unwrap();
+        translate(&program).unwrap_or_else(|e| panic!("{}\n{}", e, emit(&program)));
+        assert_eq!(program.extern_functions, Some(vec!["printf".to_string()]));
+    }
+}
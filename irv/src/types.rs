@@ -18,6 +18,7 @@ impl Program {
         }
     }
 
+    #[allow(clippy::result_unit_err)]
     pub fn get_start_label(&self) -> Result<Label, ()> {
         for label in self.labels.as_ref().unwrap() {
             if label.label_name.contains("start") {
@@ -40,6 +41,18 @@ pub struct Label {
     pub instructions: Option<Vec<Instruction>>,
 }
 
+impl Label {
+    /// The label name without the leading `@` and trailing `:`
+    pub fn name(&self) -> &str {
+        label_name(&self.label_name)
+    }
+}
+
+/// Strips the `@` and `:` decorations from a label definition or jump target.
+pub fn label_name(raw: &str) -> &str {
+    raw.trim().trim_start_matches('@').trim_end_matches(':')
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum DataType {
     Byte,
@@ -66,6 +79,25 @@ pub enum DataType {
 #[derive(Debug, Clone)]
 pub struct DataTypeInfo {}
 
+impl DataType {
+    /// Size of the type in bytes when laid out in memory
+    pub fn size(&self) -> usize {
+        match self {
+            DataType::Byte | DataType::SByte => 1,
+            DataType::TByte => 10,
+            DataType::Word | DataType::SWord => 2,
+            DataType::DWord | DataType::SDWord | DataType::Real4 => 4,
+            DataType::QWord | DataType::Real8 => 8,
+            DataType::Str4 => 4,
+            DataType::Str8 => 8,
+            DataType::Str16 => 16,
+            DataType::Str32 => 32,
+            DataType::Str64 => 64,
+            DataType::Str128 => 128,
+        }
+    }
+}
+
 impl FromStr for DataType {
     type Err = String;
 
@@ -104,10 +136,20 @@ pub struct Variable {
     pub inital_value: String, // TODO: Make sure that type matches value
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Instruction {
     pub ty: InstructionType,
     pub val: InstructionValue,
+    /// 1-based line of the source this instruction was compiled from, used by the debugger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+/// Instructions are equal if they do the same thing, no matter where they came from
+impl PartialEq for Instruction {
+    fn eq(&self, other: &Self) -> bool {
+        self.ty == other.ty && self.val == other.val
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -116,6 +158,24 @@ pub enum InstructionValue {
     MultipleValue((String, String)),
 }
 
+impl Instruction {
+    /// Splits the raw instruction value into its operand tokens, dropping any trailing comment
+    pub fn operand_tokens(&self) -> Vec<&str> {
+        fn strip(s: &str) -> &str {
+            s.split_once("//").map_or(s, |(code, _)| code)
+        }
+
+        match &self.val {
+            InstructionValue::SingleValue(val) => strip(val).split_whitespace().collect(),
+            InstructionValue::MultipleValue((left, right)) => [left, right]
+                .into_iter()
+                .map(|val| strip(val).trim())
+                .filter(|val| !val.is_empty())
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum InstructionType {
     CALL,
@@ -127,6 +187,8 @@ pub enum InstructionType {
 
     ADD,
     SUB,
+    MUL,
+    DIV,
 
     CMP,
     JEQ,
@@ -134,6 +196,7 @@ pub enum InstructionType {
     JGT,
     JMP,
 
+    RET,
     NOP,
     BRK,
     EXIT,
@@ -145,7 +208,7 @@ impl InstructionType {
     }
 
     pub fn is_valueless(&self) -> bool {
-        matches!(self, Self::NOP)
+        matches!(self, Self::NOP | Self::RET | Self::BRK)
     }
 }
 
@@ -161,11 +224,14 @@ impl FromStr for InstructionType {
             "dec" => Ok(InstructionType::DEC),
             "add" => Ok(InstructionType::ADD),
             "sub" => Ok(InstructionType::SUB),
+            "mul" => Ok(InstructionType::MUL),
+            "div" => Ok(InstructionType::DIV),
             "cmp" => Ok(InstructionType::CMP),
             "jeq" => Ok(InstructionType::JEQ),
             "jlt" => Ok(InstructionType::JLT),
             "jgt" => Ok(InstructionType::JGT),
             "jmp" => Ok(InstructionType::JMP),
+            "ret" => Ok(InstructionType::RET),
             "nop" => Ok(InstructionType::NOP),
             "brk" => Ok(InstructionType::BRK),
             "exit" => Ok(InstructionType::EXIT),
@@ -213,6 +279,7 @@ impl FromStr for Instruction {
                 }
                 None => InstructionValue::SingleValue(value.to_string()),
             },
+            line: None,
         })
     }
 }
@@ -307,3 +374,50 @@ pub struct Registers {
     pub sdh: i8,
     pub sdl: i8,
 }
+
+impl Registers {
+    /// Every register name, in declaration order
+    pub const NAMES: [&'static str; 72] = [
+        "rax", "rbx", "rcx", "rsp", "rbp", "rdi", "rsi", "rdx", "eax", "ebx", "ecx", "esp", "ebp",
+        "edi", "esi", "edx", "ax", "bx", "cx", "sp", "bp", "di", "si", "dx", "ah", "al", "bh",
+        "bl", "ch", "cl", "spl", "bpl", "dil", "sil", "dh", "dl", "srax", "srbx", "srcx", "srsp",
+        "srbp", "srdi", "srsi", "srdx", "seax", "sebx", "secx", "sesp", "sebp", "sedi", "sesi",
+        "sedx", "sax", "sbx", "scx", "ssp", "sbp", "sdi", "ssi", "sdx", "sah", "sal", "sbh", "sbl",
+        "sch", "scl", "sspl", "sbpl", "sdil", "ssil", "sdh", "sdl",
+    ];
+
+    pub fn is_register(name: &str) -> bool {
+        Self::NAMES.contains(&name)
+    }
+}
+
+macro_rules! register_access {
+    ($($name:ident),* $(,)?) => {
+        impl Registers {
+            /// Value of the register called `name`
+            pub fn get(&self, name: &str) -> Option<i128> {
+                match name {
+                    $(stringify!($name) => Some(self.$name as i128),)*
+                    _ => None,
+                }
+            }
+
+            /// Sets the register called `name`, wrapping `value` to the width of the register.
+            /// Returns false if there is no such register.
+            pub fn set(&mut self, name: &str, value: i128) -> bool {
+                match name {
+                    $(stringify!($name) => self.$name = value as _,)*
+                    _ => return false,
+                }
+                true
+            }
+        }
+    };
+}
+
+register_access!(
+    rax, rbx, rcx, rsp, rbp, rdi, rsi, rdx, eax, ebx, ecx, esp, ebp, edi, esi, edx, ax, bx, cx, sp,
+    bp, di, si, dx, ah, al, bh, bl, ch, cl, spl, bpl, dil, sil, dh, dl, srax, srbx, srcx, srsp,
+    srbp, srdi, srsi, srdx, seax, sebx, secx, sesp, sebp, sedi, sesi, sedx, sax, sbx, scx, ssp,
+    sbp, sdi, ssi, sdx, sah, sal, sbh, sbl, sch, scl, sspl, sbpl, sdil, ssil, sdh, sdl,
+);